@@ -0,0 +1,690 @@
+//! 基于 GitHub tarball 的后端：无需系统 git，只解压请求的子目录
+
+use super::{is_under, Backend, EntryKind, Placement, Remote, ResolvedRef, TreeEntry};
+use crate::copy::{copy_all, CopyStats};
+use crate::filter::Filter;
+use crate::host::Host;
+use crate::http::HttpClient;
+use crate::progress::Progress;
+use crate::refs::RemoteRef;
+use crate::repo::github_repo_slug;
+use anyhow::{anyhow, bail, Context, Result};
+use flate2::read::GzDecoder;
+use serde::Deserialize;
+use std::path::{Component, Path, PathBuf};
+
+/// github.com 的源码包下载地址，可通过 GIT_GET_CODELOAD_URL 覆盖
+const DEFAULT_CODELOAD_BASE: &str = "https://codeload.github.com";
+
+/// 通过 GitHub API + codeload tarball 抓取的后端
+pub struct ArchiveBackend {
+    owner: String,
+    repo: String,
+    api_base: String,
+    /// 为 None 时通过 API 的 tarball 端点下载（GitHub Enterprise）
+    codeload_base: Option<String>,
+    http: HttpClient,
+    progress: Progress,
+}
+
+#[derive(Deserialize)]
+struct RepoInfo {
+    default_branch: String,
+}
+
+#[derive(Deserialize)]
+struct RefItem {
+    #[serde(rename = "ref")]
+    name: String,
+    object: RefObject,
+}
+
+#[derive(Deserialize)]
+struct RefObject {
+    sha: String,
+}
+
+#[derive(Deserialize)]
+struct CommitInfo {
+    tree: RefObject,
+}
+
+#[derive(Deserialize)]
+struct ContentItem {
+    name: String,
+    sha: String,
+}
+
+#[derive(Deserialize)]
+struct TreeResponse {
+    tree: Vec<TreeItem>,
+    #[serde(default)]
+    truncated: bool,
+}
+
+#[derive(Deserialize)]
+struct TreeItem {
+    path: String,
+    #[serde(rename = "type")]
+    kind: String,
+    size: Option<u64>,
+}
+
+impl ArchiveBackend {
+    /// 为 GitHub 仓库创建后端
+    ///
+    /// github.com 的端点地址可由环境变量覆盖；GitHub Enterprise 使用配置中的
+    /// api_url（默认 https://<domain>/api/v3），源码包经 API 的 tarball 端点下载。
+    pub fn new(remote: Remote, progress: Progress) -> Result<Self> {
+        let unsupported = || anyhow!("archive 后端仅支持 GitHub 仓库: {}", remote.url);
+        let (owner, repo) = github_repo_slug(&remote.url).ok_or_else(unsupported)?;
+        let host = Host::from_url(&remote.url).ok_or_else(unsupported)?;
+        let mut api_base = host.github_api_url().ok_or_else(unsupported)?;
+        let mut codeload_base = None;
+        if host.is_github_com() {
+            if let Ok(url) = std::env::var("GIT_GET_GITHUB_API_URL") {
+                api_base = url;
+            }
+            codeload_base = Some(
+                std::env::var("GIT_GET_CODELOAD_URL")
+                    .unwrap_or_else(|_| DEFAULT_CODELOAD_BASE.to_string()),
+            );
+        }
+
+        Ok(Self::from_parts(
+            owner,
+            repo,
+            api_base,
+            codeload_base,
+            remote.token,
+            progress,
+        ))
+    }
+
+    /// 使用自定义 API 与 codeload 地址创建后端
+    pub fn with_endpoints(
+        owner: impl Into<String>,
+        repo: impl Into<String>,
+        api_base: impl Into<String>,
+        codeload_base: impl Into<String>,
+        token: Option<String>,
+        progress: Progress,
+    ) -> Self {
+        Self::from_parts(
+            owner.into(),
+            repo.into(),
+            api_base.into(),
+            Some(codeload_base.into()),
+            token,
+            progress,
+        )
+    }
+
+    fn from_parts(
+        owner: String,
+        repo: String,
+        api_base: String,
+        codeload_base: Option<String>,
+        token: Option<String>,
+        progress: Progress,
+    ) -> Self {
+        Self {
+            owner,
+            repo,
+            api_base: api_base.trim_end_matches('/').to_string(),
+            codeload_base: codeload_base.map(|base| base.trim_end_matches('/').to_string()),
+            http: HttpClient::new(token),
+            progress,
+        }
+    }
+
+    fn api_url(&self, suffix: &str) -> String {
+        format!("{}/repos/{}/{}{}", self.api_base, self.owner, self.repo, suffix)
+    }
+}
+
+impl Backend for ArchiveBackend {
+    fn name(&self) -> &'static str {
+        "archive"
+    }
+
+    fn list_refs(&mut self) -> Result<Vec<RemoteRef>> {
+        const PER_PAGE: usize = 100;
+
+        // 注意：附注标签的 commit 为标签对象本身的 SHA，GitHub API 不提供剥离后的提交
+        let mut refs = Vec::new();
+        for namespace in ["heads", "tags"] {
+            for page in 1.. {
+                let body = self.http.get_string(
+                    &self.api_url(&format!(
+                        "/git/matching-refs/{}?per_page={}&page={}",
+                        namespace, PER_PAGE, page
+                    )),
+                    "application/vnd.github+json",
+                )?;
+                let items: Vec<RefItem> =
+                    serde_json::from_str(&body).context("无法解析远程引用列表")?;
+                let count = items.len();
+                refs.extend(items.into_iter().map(|item| RemoteRef {
+                    name: item.name,
+                    commit: item.object.sha,
+                }));
+                if count < PER_PAGE {
+                    break;
+                }
+            }
+        }
+        Ok(refs)
+    }
+
+    fn resolve_ref(&mut self, reference: Option<&str>) -> Result<ResolvedRef> {
+        self.progress.emit("🔎 正在解析引用...");
+
+        let name = match reference {
+            Some(reference) => reference.to_string(),
+            None => {
+                let body = self
+                    .http
+                    .get_string(&self.api_url(""), "application/vnd.github+json")
+                    .context("无法获取仓库信息，请检查仓库地址是否正确")?;
+                let info: RepoInfo =
+                    serde_json::from_str(&body).context("无法解析仓库信息")?;
+                info.default_branch
+            }
+        };
+
+        // GitHub API 接受分支名、标签名与（缩写的）SHA；完整引用名需去掉 refs/ 前缀
+        let api_ref = name
+            .strip_prefix("refs/heads/")
+            .or_else(|| name.strip_prefix("refs/tags/"))
+            .or_else(|| name.strip_prefix("refs/"))
+            .unwrap_or(&name);
+
+        // 以 sha 媒体类型请求时，响应体就是提交 SHA 本身
+        let commit = self
+            .http
+            .get_string(
+                &self.api_url(&format!("/commits/{}", encode_path(api_ref))),
+                "application/vnd.github.sha",
+            )
+            .with_context(|| format!("无法解析引用 '{}'，请检查引用是否正确", name))?
+            .trim()
+            .to_string();
+
+        Ok(ResolvedRef { name, commit })
+    }
+
+    fn list_tree(&mut self, resolved: &ResolvedRef, path: Option<&str>) -> Result<Vec<TreeEntry>> {
+        let body = self.http.get_string(
+            &self.api_url(&format!("/git/trees/{}?recursive=1", resolved.commit)),
+            "application/vnd.github+json",
+        )?;
+        let response: TreeResponse = serde_json::from_str(&body).context("无法解析仓库树")?;
+        if response.truncated {
+            self.progress.emit("⚠️  仓库树过大，GitHub API 返回的列表不完整");
+        }
+
+        Ok(response
+            .tree
+            .into_iter()
+            .filter(|item| is_under(&item.path, path))
+            .map(|item| TreeEntry {
+                kind: match item.kind.as_str() {
+                    "tree" => EntryKind::Tree,
+                    "commit" => EntryKind::Commit,
+                    _ => EntryKind::Blob,
+                },
+                path: item.path,
+                size: item.size,
+            })
+            .collect())
+    }
+
+    fn path_kind(&mut self, resolved: &ResolvedRef, path: &str) -> Result<Option<EntryKind>> {
+        // contents API 对目录返回数组，对其他条目返回带 type 字段的对象
+        let Some(body) = self.http.get_optional_string(
+            &self.api_url(&format!("/contents/{}?ref={}", encode_path(path), resolved.commit)),
+            "application/vnd.github+json",
+        )?
+        else {
+            return Ok(None);
+        };
+        let value: serde_json::Value =
+            serde_json::from_str(&body).context("无法解析仓库内容信息")?;
+        Ok(Some(match &value {
+            serde_json::Value::Array(_) => EntryKind::Tree,
+            _ => match value["type"].as_str() {
+                Some("dir") => EntryKind::Tree,
+                Some("submodule") => EntryKind::Commit,
+                _ => EntryKind::Blob,
+            },
+        }))
+    }
+
+    fn path_id(&mut self, resolved: &ResolvedRef, path: Option<&str>) -> Result<Option<String>> {
+        let Some(path) = path else {
+            let body = self.http.get_string(
+                &self.api_url(&format!("/git/commits/{}", resolved.commit)),
+                "application/vnd.github+json",
+            )?;
+            let commit: CommitInfo = serde_json::from_str(&body).context("无法解析提交信息")?;
+            return Ok(Some(commit.tree.sha));
+        };
+
+        // contents API 不返回目录自身的哈希，从父目录的列表中查找
+        let (parent, name) = path.rsplit_once('/').unwrap_or(("", path));
+        let Some(body) = self.http.get_optional_string(
+            &self.api_url(&format!("/contents/{}?ref={}", encode_path(parent), resolved.commit)),
+            "application/vnd.github+json",
+        )?
+        else {
+            return Ok(None);
+        };
+        let items: Vec<ContentItem> =
+            serde_json::from_str(&body).context("无法解析仓库内容信息")?;
+        Ok(items.into_iter().find(|item| item.name == name).map(|item| item.sha))
+    }
+
+    fn materialize(
+        &mut self,
+        resolved: &ResolvedRef,
+        path: Option<&str>,
+        filter: &Filter,
+        target: &Path,
+    ) -> Result<CopyStats> {
+        let placement = Placement {
+            path,
+            filter,
+            target,
+        };
+        let mut stats = self.materialize_many(resolved, &[placement])?;
+        Ok(stats.remove(0))
+    }
+
+    fn materialize_many(
+        &mut self,
+        resolved: &ResolvedRef,
+        placements: &[Placement<'_>],
+    ) -> Result<Vec<CopyStats>> {
+        let url = match &self.codeload_base {
+            Some(base) => format!(
+                "{}/{}/{}/tar.gz/{}",
+                base, self.owner, self.repo, resolved.commit
+            ),
+            None => self.api_url(&format!("/tarball/{}", resolved.commit)),
+        };
+        self.progress.emit("📥 正在下载源码包...");
+        let reader = self.http.get_reader(&url)?;
+
+        // 边下载边解压，只保留各 path 下的条目，所有路径共用一次下载
+        let mut archive = tar::Archive::new(GzDecoder::new(reader));
+        let mut stats = vec![CopyStats::default(); placements.len()];
+        let mut matched = vec![false; placements.len()];
+
+        for entry in archive.entries().context("无法读取源码包")? {
+            let mut entry = entry.context("无法读取源码包")?;
+            let entry_type = entry.header().entry_type();
+            if !(entry_type.is_file() || entry_type.is_dir() || entry_type.is_symlink()) {
+                continue;
+            }
+
+            let entry_path = entry.path().context("源码包中存在无效路径")?.into_owned();
+            // 条目已解压到的位置，路径互相包含时其余目标从这里复制
+            let mut unpacked: Option<PathBuf> = None;
+            for (index, placement) in placements.iter().enumerate() {
+                let Some(relative) = archive_relative_path(&entry_path, placement.path)? else {
+                    continue;
+                };
+                matched[index] = true;
+
+                // path 指向单个文件时 relative 为空，target 即文件路径
+                let dest_path = if relative.as_os_str().is_empty() {
+                    placement.target.to_path_buf()
+                } else {
+                    placement.target.join(&relative)
+                };
+                if entry_type.is_dir() {
+                    // 有过滤规则时目录随文件创建，不留下空目录
+                    if placement.filter.is_empty() {
+                        std::fs::create_dir_all(&dest_path)
+                            .with_context(|| format!("无法创建目录: {}", dest_path.display()))?;
+                    }
+                    continue;
+                }
+                if !placement.filter.matches(&relative.to_string_lossy()) {
+                    continue;
+                }
+                if let Some(parent) = dest_path.parent().filter(|p| !p.as_os_str().is_empty()) {
+                    std::fs::create_dir_all(parent)?;
+                }
+                match &unpacked {
+                    Some(source) => copy_all(source, &dest_path)?,
+                    None => {
+                        entry
+                            .unpack(&dest_path)
+                            .with_context(|| format!("无法写入文件: {}", dest_path.display()))?;
+                        unpacked = Some(dest_path);
+                    }
+                }
+                if entry_type.is_file() {
+                    stats[index].files += 1;
+                    stats[index].bytes += entry.size();
+                }
+            }
+        }
+
+        if let Some(index) = matched.iter().position(|matched| !matched) {
+            bail!(
+                "远程仓库中未找到指定路径: {}",
+                placements[index].path.unwrap_or("")
+            );
+        }
+
+        Ok(stats)
+    }
+}
+
+/// 对仓库内路径逐段做百分号编码，保留分隔各段的 `/`
+///
+/// 路径中的 `#`、`?`、`%`、空格等字符不编码会改变请求的地址。
+fn encode_path(path: &str) -> String {
+    let mut encoded = String::with_capacity(path.len());
+    for byte in path.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+/// 把源码包中的路径（首段为 "<repo>-<sha>/" 前缀）转换为相对 path 的路径
+///
+/// 不在 path 之下的条目返回 None；包含 `..` 等越界成分的条目直接报错。
+fn archive_relative_path(entry_path: &Path, path: Option<&str>) -> Result<Option<PathBuf>> {
+    let mut components = entry_path.components();
+    components.next();
+
+    let mut repo_path = PathBuf::new();
+    for component in components {
+        match component {
+            Component::Normal(part) => repo_path.push(part),
+            Component::CurDir => {}
+            _ => bail!("源码包中存在越界路径: {}", entry_path.display()),
+        }
+    }
+
+    Ok(match path {
+        None => Some(repo_path),
+        Some(path) => repo_path
+            .strip_prefix(path)
+            .ok()
+            .map(Path::to_path_buf),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{git, serve, FixtureRepo, Response};
+    use std::process::Command;
+    use std::sync::{Arc, Mutex};
+    use tempfile::TempDir;
+
+    /// 把百分号编码还原为原始字节
+    fn decode(text: &str) -> String {
+        let bytes = text.as_bytes();
+        let mut decoded = Vec::new();
+        let mut i = 0;
+        while i < bytes.len() {
+            if bytes[i] == b'%' && i + 2 < bytes.len() {
+                if let Ok(byte) = u8::from_str_radix(&text[i + 1..i + 3], 16) {
+                    decoded.push(byte);
+                    i += 3;
+                    continue;
+                }
+            }
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+        String::from_utf8(decoded).unwrap()
+    }
+
+    /// 用夹具仓库模拟 GitHub API（/api）与 codeload（/codeload），返回服务地址与请求记录
+    fn github_stub(repo: &FixtureRepo) -> (String, Arc<Mutex<Vec<String>>>) {
+        let dir = repo.path().to_path_buf();
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let log = Arc::clone(&seen);
+        let url = serve(move |request| {
+            log.lock().unwrap().push(request.target.clone());
+            let (path, query) = request
+                .target
+                .split_once('?')
+                .unwrap_or((&request.target, ""));
+            let path = decode(path);
+            let json = |value: serde_json::Value| Response::new(200, value.to_string());
+            let not_found = || Response::new(404, "{}");
+            let rev_parse = |rev: &str| {
+                let output = Command::new("git")
+                    .current_dir(&dir)
+                    .args(["rev-parse", "--verify", "--quiet", rev])
+                    .output()
+                    .unwrap();
+                output
+                    .status
+                    .success()
+                    .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+            };
+
+            if let Some(rest) = path.strip_prefix("/codeload/o/r/tar.gz/") {
+                let output = Command::new("git")
+                    .current_dir(&dir)
+                    .args(["archive", "--format=tar.gz"])
+                    .arg(format!("--prefix=r-{}/", rest))
+                    .arg(rest)
+                    .output()
+                    .unwrap();
+                return Response::new(200, output.stdout);
+            }
+            let Some(rest) = path.strip_prefix("/api/repos/o/r") else {
+                return not_found();
+            };
+            if rest.is_empty() {
+                return json(serde_json::json!({ "default_branch": "main" }));
+            }
+            if let Some(reference) = rest.strip_prefix("/commits/") {
+                return match rev_parse(&format!("{}^{{commit}}", reference)) {
+                    Some(sha) => Response::new(200, sha),
+                    None => Response::new(422, "{}"),
+                };
+            }
+            if let Some(sha) = rest.strip_prefix("/git/commits/") {
+                let tree = rev_parse(&format!("{}^{{tree}}", sha)).unwrap();
+                return json(serde_json::json!({ "tree": { "sha": tree } }));
+            }
+            if let Some(sha) = rest.strip_prefix("/git/trees/") {
+                let listing = git(&dir, &["ls-tree", "-r", "-t", "-l", "-z", sha]);
+                let tree: Vec<serde_json::Value> = listing
+                    .split('\0')
+                    .filter(|record| !record.is_empty())
+                    .map(|record| {
+                        let (meta, path) = record.split_once('\t').unwrap();
+                        let fields: Vec<&str> = meta.split_whitespace().collect();
+                        serde_json::json!({
+                            "path": path,
+                            "type": fields[1],
+                            "size": fields[3].parse::<u64>().ok(),
+                        })
+                    })
+                    .collect();
+                return json(serde_json::json!({ "tree": tree, "truncated": false }));
+            }
+            if let Some(content_path) = rest.strip_prefix("/contents/") {
+                let sha = query.strip_prefix("ref=").unwrap();
+                let content_path = content_path.trim_end_matches('/');
+                let kind = if content_path.is_empty() {
+                    "tree".to_string()
+                } else {
+                    let listing =
+                        git(&dir, &["--literal-pathspecs", "ls-tree", sha, "--", content_path]);
+                    match listing.split_whitespace().nth(1) {
+                        Some(kind) => kind.to_string(),
+                        None => return not_found(),
+                    }
+                };
+                if kind != "tree" {
+                    let name = content_path.rsplit('/').next().unwrap();
+                    return json(serde_json::json!({ "type": "file", "name": name }));
+                }
+                let spec = format!("{}:{}", sha, content_path);
+                let listing = git(&dir, &["ls-tree", "-z", &spec]);
+                let items: Vec<serde_json::Value> = listing
+                    .split('\0')
+                    .filter(|record| !record.is_empty())
+                    .map(|record| {
+                        let (meta, name) = record.split_once('\t').unwrap();
+                        let fields: Vec<&str> = meta.split_whitespace().collect();
+                        serde_json::json!({ "name": name, "sha": fields[2] })
+                    })
+                    .collect();
+                return json(serde_json::Value::Array(items));
+            }
+            not_found()
+        });
+        (url, seen)
+    }
+
+    fn backend(url: &str) -> ArchiveBackend {
+        ArchiveBackend::with_endpoints(
+            "o",
+            "r",
+            format!("{}/api", url),
+            format!("{}/codeload", url),
+            None,
+            Progress::silent(),
+        )
+    }
+
+    fn read(path: &Path) -> String {
+        std::fs::read_to_string(path).unwrap()
+    }
+
+    #[test]
+    fn extracts_requested_path_from_tarball() {
+        let repo = FixtureRepo::new();
+        repo.write("examples/a.txt", "a\n")
+            .write("examples/sub/b.txt", "b\n")
+            .write("other/c.txt", "c\n")
+            .write("README.md", "readme\n");
+        let commit = repo.commit("init");
+        let (url, _) = github_stub(&repo);
+        let mut backend = backend(&url);
+
+        let resolved = backend.resolve_ref(None).unwrap();
+        assert_eq!(resolved, ResolvedRef { name: "main".into(), commit: commit.clone() });
+        assert_eq!(backend.path_kind(&resolved, "examples").unwrap(), Some(EntryKind::Tree));
+        assert_eq!(backend.path_kind(&resolved, "README.md").unwrap(), Some(EntryKind::Blob));
+        assert_eq!(backend.path_kind(&resolved, "missing").unwrap(), None);
+        assert_eq!(
+            backend.path_id(&resolved, Some("examples")).unwrap(),
+            Some(git(repo.path(), &["rev-parse", &format!("{}:examples", commit)]))
+        );
+        assert_eq!(
+            backend.path_id(&resolved, None).unwrap(),
+            Some(git(repo.path(), &["rev-parse", &format!("{}^{{tree}}", commit)]))
+        );
+        let entries = backend.list_tree(&resolved, Some("examples")).unwrap();
+        let mut paths: Vec<&str> = entries.iter().map(|entry| entry.path.as_str()).collect();
+        paths.sort_unstable();
+        assert_eq!(
+            paths,
+            ["examples", "examples/a.txt", "examples/sub", "examples/sub/b.txt"]
+        );
+
+        let out = TempDir::new().unwrap();
+        let target = out.path().join("examples");
+        let stats = backend
+            .materialize(&resolved, Some("examples"), &Filter::default(), &target)
+            .unwrap();
+        assert_eq!(stats.files, 2);
+        assert_eq!(read(&target.join("a.txt")), "a\n");
+        assert_eq!(read(&target.join("sub/b.txt")), "b\n");
+        assert!(!target.join("c.txt").exists());
+        assert!(!target.join("README.md").exists());
+
+        let readme = out.path().join("README.md");
+        let stats = backend.materialize(&resolved, Some("README.md"), &Filter::default(), &readme).unwrap();
+        assert_eq!(stats.files, 1);
+        assert_eq!(read(&readme), "readme\n");
+    }
+
+    #[test]
+    fn missing_path_in_tarball_is_an_error() {
+        let repo = FixtureRepo::new();
+        repo.write("examples/a.txt", "a\n");
+        repo.commit("init");
+        let (url, _) = github_stub(&repo);
+        let mut backend = backend(&url);
+
+        let resolved = backend.resolve_ref(Some("main")).unwrap();
+        let out = TempDir::new().unwrap();
+        let error = backend
+            .materialize(&resolved, Some("exampels"), &Filter::default(), out.path())
+            .unwrap_err();
+        assert!(error.to_string().contains("exampels"));
+        assert!(backend.resolve_ref(Some("no-such-branch")).is_err());
+    }
+
+    #[test]
+    fn special_characters_in_refs_and_paths_are_handled() {
+        let repo = FixtureRepo::new();
+        repo.write("dir #1/a b%?.txt", "special\n")
+            .write("dir #1/plain.txt", "plain\n")
+            .write("dir/other.txt", "other\n");
+        let commit = repo.commit("init");
+        git(repo.path(), &["branch", "fix#1%"]);
+        let (url, seen) = github_stub(&repo);
+        let mut backend = backend(&url);
+
+        let resolved = backend.resolve_ref(Some("fix#1%")).unwrap();
+        assert_eq!(resolved.commit, commit);
+        assert!(seen
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|target| target.ends_with("/commits/fix%231%25")));
+
+        assert_eq!(backend.path_kind(&resolved, "dir #1").unwrap(), Some(EntryKind::Tree));
+        assert_eq!(
+            backend.path_kind(&resolved, "dir #1/a b%?.txt").unwrap(),
+            Some(EntryKind::Blob)
+        );
+        assert_eq!(
+            backend.path_id(&resolved, Some("dir #1/a b%?.txt")).unwrap(),
+            Some(git(repo.path(), &["rev-parse", &format!("{}:dir #1/a b%?.txt", commit)]))
+        );
+        assert!(seen
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|target| target.contains("/contents/dir%20%231/a%20b%25%3F.txt?ref=")));
+
+        let out = TempDir::new().unwrap();
+        let target = out.path().join("dir");
+        backend
+            .materialize(&resolved, Some("dir #1"), &Filter::default(), &target)
+            .unwrap();
+        assert_eq!(read(&target.join("a b%?.txt")), "special\n");
+        assert_eq!(read(&target.join("plain.txt")), "plain\n");
+        assert!(!target.join("other.txt").exists());
+    }
+
+    #[test]
+    fn encode_path_keeps_separators() {
+        assert_eq!(encode_path("a/b-c_d.e~f"), "a/b-c_d.e~f");
+        assert_eq!(encode_path("dir #1/a b%?.txt"), "dir%20%231/a%20b%25%3F.txt");
+        assert_eq!(encode_path("中"), "%E4%B8%AD");
+    }
+}
@@ -0,0 +1,770 @@
+//! 基于系统 git 命令的后端：部分克隆（可经由本地缓存仓库）+ 临时目录中 sparse-checkout
+
+use super::{Backend, EntryKind, Placement, Remote, ResolvedRef, TreeEntry};
+use crate::cache;
+use crate::cleanup::TempDir;
+use crate::copy::{copy_directory, copy_file, CopyStats};
+use crate::filter::{anchored_pattern, Filter};
+use crate::git::{
+    git_output, ls_remote, remote_default_branch, run_git_checkout, run_git_command, run_git_fetch,
+    run_git_fetch_with_stderr,
+};
+use crate::progress::Progress;
+use crate::refs::{find_commit_by_prefix, find_ref, is_hex_sha, parse_ls_remote, RemoteRef};
+use anyhow::{anyhow, bail, Context, Result};
+use std::path::{Path, PathBuf};
+
+/// 缓存仓库中记录远程默认分支的符号引用
+const DEFAULT_BRANCH_REF: &str = "refs/git-get/HEAD";
+
+/// 服务端不支持部分克隆时 git fetch 给出的警告（拉取仍会成功）
+const SERVER_IGNORES_FILTER: &str = "filtering not recognized by server";
+
+/// 本地 git 不认识 --filter 选项时的报错
+const CLIENT_LACKS_FILTER: &str = "unknown option `filter";
+
+/// 拉取策略
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Strategy {
+    /// 部分克隆：只拉取提交与目录树，文件内容在检出时按需拉取
+    Partial,
+    /// 浅克隆：拉取提交的全部内容，用于不支持部分克隆的 git
+    Shallow,
+}
+
+impl Strategy {
+    fn describe(self) -> &'static str {
+        match self {
+            Self::Partial => "部分克隆 --filter=blob:none",
+            Self::Shallow => "浅克隆 --depth=1",
+        }
+    }
+}
+
+/// 调用系统 git 的后端
+///
+/// 对象以部分克隆的方式拉取（git 不支持时退回浅克隆），检出与复制发生在后端持有的临时目录中，
+/// 后端被 drop 时自动清理。启用缓存时对象拉取到 [`crate::cache`] 中的裸仓库，
+/// 临时目录检出时直接使用缓存仓库的对象目录，按需拉取的文件内容也写入缓存。
+pub struct GitCliBackend {
+    remote: Remote,
+    progress: Progress,
+    workdir: TempDir,
+    /// 执行拉取与对象查询的仓库：缓存仓库，或未启用缓存时的临时目录
+    repo: PathBuf,
+    cached: bool,
+    /// 缓存仓库的使用锁，持有期间其他进程不会删除或重建该仓库
+    _cache_lock: Option<cache::RepoLock>,
+    /// 离线模式：只从缓存仓库中解析引用，不访问远程
+    offline: bool,
+    strategy: Strategy,
+    /// 本次拉取使对象目录增加的字节数（磁盘占用的增长，不等于网络传输量：
+    /// 拉取的包可能被解开成松散对象，也可能与已有的包合并）
+    objects_growth: u64,
+}
+
+impl GitCliBackend {
+    /// 创建临时目录并初始化仓库；cache 为 true 时对象拉取到本地缓存仓库
+    pub fn new(remote: Remote, progress: Progress, cache: bool) -> Result<Self> {
+        let workdir = TempDir::new()?;
+        progress.emit(format!("🔧 临时目录: {}", workdir.path().display()));
+        progress.emit("📥 正在初始化仓库...");
+
+        // git init && git remote add origin <url>
+        // 检出时缺少的文件内容从 origin 按需拉取（部分克隆的 promisor 远程）
+        run_git_command(workdir.path(), &["init"])?;
+        run_git_command(workdir.path(), &["remote", "add", "origin", &remote.url])?;
+        run_git_command(workdir.path(), &["config", "remote.origin.promisor", "true"])?;
+        run_git_command(
+            workdir.path(),
+            &["config", "remote.origin.partialclonefilter", "blob:none"],
+        )?;
+
+        let (cache_repo, cache_lock) = match cache.then(|| cache::open(&remote.url)) {
+            Some(Ok((repo, lock))) => (Some(repo), Some(lock)),
+            Some(Err(err)) => {
+                progress.emit(format!("⚠️  无法使用本地缓存（{:#}），改为临时目录", err));
+                (None, None)
+            }
+            None => (None, None),
+        };
+        if let Some(repo) = &cache_repo {
+            progress.emit(format!("🗃️  缓存仓库: {}", repo.display()));
+        }
+
+        Ok(Self {
+            remote,
+            progress,
+            cached: cache_repo.is_some(),
+            repo: cache_repo.unwrap_or_else(|| workdir.path().to_path_buf()),
+            _cache_lock: cache_lock,
+            workdir,
+            offline: false,
+            strategy: Strategy::Partial,
+            objects_growth: 0,
+        })
+    }
+
+    /// 离线模式：只使用 remote 对应的缓存仓库，缓存中没有该仓库时报错
+    pub fn offline(remote: Remote, progress: Progress) -> Result<Self> {
+        let Some((repo, lock)) = cache::find(&remote.url)? else {
+            bail!(
+                "离线模式：本地缓存中没有仓库 {}\n提示: 联网时先运行 git-get prefetch {} 预热缓存",
+                remote.url,
+                remote.url
+            );
+        };
+        progress.emit("📴 离线模式：只使用本地缓存");
+        progress.emit(format!("🗃️  缓存仓库: {}", repo.display()));
+
+        // 不添加 origin，缓存中缺少文件内容时检出直接失败而不是访问远程
+        let workdir = TempDir::new()?;
+        progress.emit(format!("🔧 临时目录: {}", workdir.path().display()));
+        run_git_command(workdir.path(), &["init"])?;
+
+        Ok(Self {
+            remote,
+            progress,
+            workdir,
+            repo,
+            _cache_lock: Some(lock),
+            cached: true,
+            offline: true,
+            strategy: Strategy::Partial,
+            objects_growth: 0,
+        })
+    }
+
+    /// 对象目录：缓存仓库的 objects，或临时仓库的 .git/objects
+    fn objects_dir(&self) -> PathBuf {
+        if self.cached {
+            self.repo.join("objects")
+        } else {
+            self.repo.join(".git/objects")
+        }
+    }
+
+    /// 对象目录当前占用的字节数，用于统计本地对象的增长
+    fn objects_size(&self) -> u64 {
+        cache::dir_size(&self.objects_dir()).unwrap_or(0)
+    }
+
+    /// 按当前策略执行 git fetch
+    ///
+    /// 本地 git 不支持 --filter 时退回浅克隆并重试；服务端不支持部分克隆时 git 只给出警告
+    /// 并拉取完整内容，此时提示用户，之后的拉取也不再使用 --filter。
+    fn run_fetch(&mut self, args: &[&str]) -> Result<String> {
+        if self.strategy == Strategy::Partial {
+            let mut filtered = vec!["fetch", "--filter=blob:none"];
+            filtered.extend(args);
+            match run_git_fetch_with_stderr(&self.repo, &filtered, &self.remote) {
+                Ok((stdout, stderr)) => {
+                    if stderr.contains(SERVER_IGNORES_FILTER) {
+                        self.progress
+                            .emit("⚠️  远程服务器不支持部分克隆，将下载所选提交的全部文件内容");
+                        self.strategy = Strategy::Shallow;
+                    }
+                    return Ok(stdout);
+                }
+                Err(err) if format!("{:#}", err).contains(CLIENT_LACKS_FILTER) => {
+                    self.progress.emit("⚠️  当前 git 不支持部分克隆，改为浅克隆");
+                    self.strategy = Strategy::Shallow;
+                }
+                Err(err) => return Err(err),
+            }
+        }
+        let mut plain = vec!["fetch"];
+        plain.extend(args);
+        run_git_fetch(&self.repo, &plain, &self.remote)
+    }
+
+    fn fetch(&mut self, refspec: &str) -> Result<String> {
+        self.run_fetch(&["--depth=1", "origin", refspec])
+    }
+
+    /// 拉取所有分支与标签的完整历史，用于服务端不允许按 SHA 拉取的情况
+    fn fetch_full_history(&mut self) -> Result<String> {
+        let mut args = vec!["--tags"];
+        // 缓存仓库可能已被之前的浅拉取截断，需要补全历史才能找到更早的提交
+        if git_output(&self.repo, &["rev-parse", "--is-shallow-repository"])? == "true" {
+            args.push("--unshallow");
+        }
+        args.extend(["origin", "+refs/heads/*:refs/remotes/origin/*"]);
+        self.run_fetch(&args)
+    }
+
+    /// 把 rev 剥离到提交并返回完整 SHA
+    fn peel_commit(&self, rev: &str) -> Result<String> {
+        git_output(
+            &self.repo,
+            &["rev-parse", "--verify", "--quiet", &format!("{}^{{commit}}", rev)],
+        )
+    }
+
+    /// 缓存仓库中记录的引用（refs/git-get/ 下的分支、标签等），名称还原为远程的引用名
+    fn cached_refs(&self) -> Result<Vec<RemoteRef>> {
+        let output = git_output(
+            &self.repo,
+            &[
+                "for-each-ref",
+                "--format=%(if)%(*objectname)%(then)%(*objectname)%(else)%(objectname)%(end)%09%(refname)",
+                "refs/git-get/",
+            ],
+        )?;
+        Ok(parse_ls_remote(&output)
+            .into_iter()
+            .filter(|r| r.name != DEFAULT_BRANCH_REF && !r.name.starts_with("refs/git-get/commits/"))
+            .map(|r| RemoteRef {
+                name: format!("refs/{}", &r.name["refs/git-get/".len()..]),
+                commit: r.commit,
+            })
+            .collect())
+    }
+
+    /// 离线解析引用：分支、标签使用缓存中记录的位置，提交 SHA 须已在缓存中
+    fn resolve_cached(&self, reference: Option<&str>) -> Result<ResolvedRef> {
+        let name = match reference {
+            Some(reference) => reference.to_string(),
+            None => git_output(&self.repo, &["symbolic-ref", "--quiet", DEFAULT_BRANCH_REF])
+                .ok()
+                .and_then(|target| target.strip_prefix("refs/git-get/heads/").map(str::to_string))
+                .with_context(|| {
+                    format!(
+                        "离线模式：本地缓存中没有仓库 {} 的默认分支记录，请用 --ref 指定引用",
+                        self.remote.url
+                    )
+                })?,
+        };
+
+        let refs = self.cached_refs()?;
+        let commit = if let Some(found) = find_ref(&name, &refs) {
+            self.progress.emit(format!("📴 使用缓存中记录的 {}", found.name));
+            found.commit.clone()
+        } else if is_hex_sha(&name) {
+            self.peel_commit(&name).map_err(|_| {
+                anyhow!(
+                    "离线模式：本地缓存中没有提交 {}（仓库 {}）",
+                    name,
+                    self.remote.url
+                )
+            })?
+        } else {
+            bail!(
+                "离线模式：本地缓存中没有引用 {}（仓库 {}）",
+                name,
+                self.remote.url
+            );
+        };
+        Ok(ResolvedRef { name, commit })
+    }
+
+    /// 按提交 SHA（可能是缩写）拉取，缩写先用远程引用指向的提交补全
+    fn fetch_commit(&mut self, rev: &str, refs: &[RemoteRef]) -> Result<String> {
+        let commit = find_commit_by_prefix(rev, refs)?.unwrap_or_else(|| rev.to_string());
+
+        // 完整 SHA 先尝试直接浅拉取，这需要服务端允许获取未公布的对象
+        if commit.len() == 40 && self.fetch(&commit).is_ok() {
+            // 记录一个本地引用，避免缓存仓库中的对象被 git gc 当作不可达对象清理
+            let local = format!("refs/git-get/commits/{}", commit);
+            run_git_command(&self.repo, &["update-ref", &local, &commit])?;
+            return self.peel_commit(&commit);
+        }
+
+        self.progress.emit("⚠️  无法直接按提交拉取，改为拉取完整历史...");
+        self.fetch_full_history()
+            .context("无法拉取仓库，请检查仓库地址是否正确")?;
+        self.peel_commit(&commit)
+            .map_err(|_| anyhow!("远程仓库中不存在提交: {}", rev))
+    }
+}
+
+impl Backend for GitCliBackend {
+    fn name(&self) -> &'static str {
+        "git"
+    }
+
+    fn list_refs(&mut self) -> Result<Vec<RemoteRef>> {
+        if self.offline {
+            return self.cached_refs();
+        }
+        ls_remote(&self.repo, &self.remote)
+    }
+
+    fn resolve_ref(&mut self, reference: Option<&str>) -> Result<ResolvedRef> {
+        if self.offline {
+            return self.resolve_cached(reference);
+        }
+
+        // 未指定引用时询问远程 HEAD 指向的默认分支
+        let name = match reference {
+            Some(reference) => reference.to_string(),
+            None => remote_default_branch(&self.repo, &self.remote)?,
+        };
+
+        self.progress.emit("📥 正在拉取仓库...");
+        let before = self.objects_size();
+        let refs = ls_remote(&self.repo, &self.remote)?;
+        let commit = if let Some(found) = find_ref(&name, &refs) {
+            // 分支、标签或任意公布的引用：git fetch --depth=1 origin +<ref>:refs/git-get/<ref>
+            let local = format!(
+                "refs/git-get/{}",
+                found.name.strip_prefix("refs/").unwrap_or(&found.name)
+            );
+            if self.cached && self.peel_commit(&found.commit).is_ok() {
+                // 缓存中已有远程引用指向的提交，只更新本地引用
+                run_git_command(&self.repo, &["update-ref", &local, &found.commit])?;
+                self.progress.emit("🗃️  缓存中已有该提交，跳过拉取");
+            } else {
+                self.fetch(&format!("+{}:{}", found.name, local))
+                    .context("无法拉取仓库，请检查仓库地址和引用是否正确")?;
+            }
+            self.peel_commit(&local)?
+        } else if is_hex_sha(&name) {
+            self.fetch_commit(&name, &refs)?
+        } else {
+            bail!("远程仓库中不存在引用: {}", name);
+        };
+        let fetched = self.objects_size().saturating_sub(before);
+        self.objects_growth += fetched;
+        self.progress.emit(format!(
+            "📥 拉取完成（{}，本地对象增加 {}）",
+            self.strategy.describe(),
+            cache::format_size(fetched)
+        ));
+
+        if self.cached {
+            if reference.is_none() {
+                // 记录默认分支，离线模式下未指定引用时使用
+                let target = format!("refs/git-get/heads/{}", name);
+                run_git_command(&self.repo, &["symbolic-ref", DEFAULT_BRANCH_REF, &target])?;
+            }
+
+            // 拉取后缓存可能超出上限，清理最久未使用的其他仓库
+            for entry in cache::gc(cache::max_size()?, Some(&self.repo))? {
+                self.progress.emit(format!(
+                    "🧹 缓存超出上限，已删除: {}（{}）",
+                    entry.url,
+                    cache::format_size(entry.size)
+                ));
+            }
+        }
+
+        Ok(ResolvedRef { name, commit })
+    }
+
+    fn list_tree(&mut self, resolved: &ResolvedRef, path: Option<&str>) -> Result<Vec<TreeEntry>> {
+        // 不使用 -l：部分克隆中查询文件大小会逐个拉取文件内容
+        // 路径按字面匹配，不作为 glob 解释
+        let mut args = vec![
+            "--literal-pathspecs",
+            "ls-tree",
+            "-r",
+            "-t",
+            "-z",
+            resolved.commit.as_str(),
+        ];
+        if let Some(path) = path {
+            args.extend(["--", path]);
+        }
+        let output = git_output(&self.repo, &args)?;
+
+        // 每条记录格式: "<mode> <type> <object>\t<path>"，以 NUL 分隔
+        output
+            .split('\0')
+            .filter(|record| !record.is_empty())
+            .map(|record| {
+                let (meta, path) = record
+                    .split_once('\t')
+                    .with_context(|| format!("无法解析 git ls-tree 输出: {}", record))?;
+                let fields: Vec<&str> = meta.split_whitespace().collect();
+                let kind = match fields.get(1) {
+                    Some(&"blob") => EntryKind::Blob,
+                    Some(&"tree") => EntryKind::Tree,
+                    Some(&"commit") => EntryKind::Commit,
+                    _ => bail!("无法解析 git ls-tree 输出: {}", record),
+                };
+                Ok(TreeEntry {
+                    path: path.to_string(),
+                    kind,
+                    size: None,
+                })
+            })
+            .collect()
+    }
+
+    fn path_kind(&mut self, resolved: &ResolvedRef, path: &str) -> Result<Option<EntryKind>> {
+        // 目录树已在本地，从上级目录的条目读取类型，不需要文件内容
+        let output = git_output(
+            &self.repo,
+            &["--literal-pathspecs", "ls-tree", "-z", &resolved.commit, "--", path],
+        )?;
+        Ok(output
+            .split('\0')
+            .filter_map(|record| record.split_once('\t'))
+            .find(|(_, name)| *name == path)
+            .and_then(|(meta, _)| match meta.split_whitespace().nth(1) {
+                Some("blob") => Some(EntryKind::Blob),
+                Some("tree") => Some(EntryKind::Tree),
+                Some("commit") => Some(EntryKind::Commit),
+                _ => None,
+            }))
+    }
+
+    fn path_id(&mut self, resolved: &ResolvedRef, path: Option<&str>) -> Result<Option<String>> {
+        let object = match path {
+            Some(path) => format!("{}:{}", resolved.commit, path),
+            None => format!("{}^{{tree}}", resolved.commit),
+        };
+        Ok(git_output(&self.repo, &["rev-parse", "--verify", "--quiet", &object]).ok())
+    }
+
+    fn materialize(
+        &mut self,
+        resolved: &ResolvedRef,
+        path: Option<&str>,
+        filter: &Filter,
+        target: &Path,
+    ) -> Result<CopyStats> {
+        let placement = Placement {
+            path,
+            filter,
+            target,
+        };
+        let mut stats = self.materialize_many(resolved, &[placement])?;
+        Ok(stats.remove(0))
+    }
+
+    fn materialize_many(
+        &mut self,
+        resolved: &ResolvedRef,
+        placements: &[Placement<'_>],
+    ) -> Result<Vec<CopyStats>> {
+        self.checkout(resolved, placements)?;
+
+        let workdir = self.workdir.path();
+        self.progress.emit("📋 正在复制文件...");
+        placements
+            .iter()
+            .map(|placement| {
+                // 确定源路径并复制到目标路径
+                let source_path = match placement.path {
+                    Some(subdir) => {
+                        let source_path = workdir.join(subdir);
+                        if source_path.symlink_metadata().is_err() {
+                            bail!(
+                                "远程仓库中未找到指定路径: {}",
+                                subdir
+                            );
+                        }
+                        source_path
+                    }
+                    None => workdir.to_path_buf(),
+                };
+
+                // 符号链接（包括指向目录的）按链接本身复制，不跟随到工作区之外
+                let is_dir = source_path.symlink_metadata().is_ok_and(|m| m.is_dir());
+                if !is_dir {
+                    copy_file(&source_path, placement.target)
+                } else {
+                    copy_directory(&source_path, placement.target, placement.filter)
+                }
+            })
+            .collect()
+    }
+
+    fn prefetch(&mut self, resolved: &ResolvedRef, paths: &[Option<&str>]) -> Result<()> {
+        // 检出一次即可把这些路径的文件内容拉取到缓存中
+        let filter = Filter::default();
+        let placements: Vec<Placement<'_>> = paths
+            .iter()
+            .map(|&path| Placement {
+                path,
+                filter: &filter,
+                target: Path::new(""),
+            })
+            .collect();
+        self.checkout(resolved, &placements)
+    }
+}
+
+impl GitCliBackend {
+    /// 在临时目录中检出各路径，部分克隆时由 git 批量拉取所需的文件内容
+    fn checkout(&mut self, resolved: &ResolvedRef, placements: &[Placement<'_>]) -> Result<()> {
+        let workdir = self.workdir.path().to_path_buf();
+        let objects = self.cached.then(|| self.objects_dir());
+
+        // 所有路径共用一次检出，用锚定到仓库根目录的 sparse-checkout 模式只检出指定路径。
+        // 不使用 cone 模式：它总会检出根目录及各上级目录中直接包含的文件，部分克隆时
+        // 还要额外拉取这些文件的内容
+        let sparse = match sparse_patterns(placements) {
+            Some(patterns) => {
+                run_git_command(&workdir, &["config", "core.sparseCheckout", "true"])?;
+                run_git_command(&workdir, &["config", "core.sparseCheckoutCone", "false"])?;
+                let sparse_checkout_path = workdir.join(".git/info/sparse-checkout");
+                std::fs::create_dir_all(sparse_checkout_path.parent().unwrap())?;
+                std::fs::write(&sparse_checkout_path, patterns.join("\n") + "\n")
+                    .context("无法写入 sparse-checkout 配置")?;
+                true
+            }
+            None => false,
+        };
+        if sparse {
+            self.progress.emit("📂 正在检出（仅指定路径，sparse-checkout）...");
+        } else {
+            self.progress.emit("📂 正在检出（完整仓库）...");
+        }
+
+        let before = self.objects_size();
+        let remote = (!self.offline).then_some(&self.remote);
+        let mut result = run_git_checkout(
+            &workdir,
+            &["checkout", &resolved.commit],
+            remote,
+            objects.as_deref(),
+        )
+        .map(|_| ());
+        if result.is_ok() {
+            // 无法读取文件内容时 git checkout 只输出错误而不失败，检出后这些文件显示为已删除
+            let output =
+                run_git_checkout(&workdir, &["ls-files", "--deleted"], None, objects.as_deref())?;
+            let missing: Vec<&str> = output.lines().collect();
+            if !missing.is_empty() {
+                result = Err(anyhow!(
+                    "{} 个文件的内容无法获取: {}",
+                    missing.len(),
+                    missing.iter().take(5).copied().collect::<Vec<_>>().join(", ")
+                ));
+            }
+        }
+        if self.offline {
+            if let Err(err) = result {
+                bail!(
+                    "离线模式：本地缓存中缺少检出所需的文件内容（仓库 {}，提交 {}）: {:#}\n提示: 联网时先运行 git-get prefetch 预热缓存",
+                    self.remote.url,
+                    resolved.commit,
+                    err
+                );
+            }
+            return Ok(());
+        }
+        result.context("检出失败")?;
+
+        let fetched = self.objects_size().saturating_sub(before);
+        self.objects_growth += fetched;
+        if fetched > 0 {
+            self.progress.emit(format!(
+                "📥 检出时按需拉取文件内容，本地对象增加 {}",
+                cache::format_size(fetched)
+            ));
+        }
+        self.progress.emit(format!(
+            "📊 本地对象共增加 {}（{}{}）",
+            cache::format_size(self.objects_growth),
+            self.strategy.describe(),
+            if sparse { " + sparse-checkout" } else { "" }
+        ));
+        Ok(())
+    }
+}
+
+/// 各路径合并后的 sparse-checkout 模式，需要完整检出（或路径无法用模式表示）时返回 None
+///
+/// 有过滤规则时尽量由 sparse-checkout 跳过被排除的文件。多个路径时一个路径的排除规则
+/// 可能排除另一个路径需要的文件，因此只保留包含规则，复制时再逐个文件过滤。
+fn sparse_patterns(placements: &[Placement<'_>]) -> Option<Vec<String>> {
+    let mut lines = Vec::new();
+    for placement in placements {
+        let filter = placement.filter;
+        match (filter.sparse_patterns(placement.path).filter(|_| !filter.is_empty()), placement.path) {
+            (Some(patterns), _) => lines.extend(patterns),
+            (None, Some(path)) => lines.push(anchored_pattern(path)?),
+            (None, None) => return None,
+        }
+    }
+    if placements.len() > 1 {
+        lines.retain(|line| !line.starts_with('!'));
+    }
+    Some(lines)
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::provenance::collect_files;
+    use crate::test_support::{git, snapshot, FixtureRepo};
+    use sha2::{Digest, Sha256};
+    use std::sync::{Arc, Mutex};
+    use tempfile::TempDir;
+
+    fn backend(repo: &FixtureRepo) -> GitCliBackend {
+        let remote = Remote {
+            url: repo.url(),
+            token: None,
+            ssh_key: None,
+        };
+        GitCliBackend::new(remote, Progress::silent(), false).unwrap()
+    }
+
+    #[test]
+    fn symlinks_are_copied_as_links_and_never_followed() {
+        let outside = TempDir::new().unwrap();
+        let secret = outside.path().join("secret.txt");
+        std::fs::write(&secret, "local secret\n").unwrap();
+        std::fs::create_dir(outside.path().join("private")).unwrap();
+        std::fs::write(outside.path().join("private/key"), "key\n").unwrap();
+
+        let repo = FixtureRepo::new();
+        repo.write("examples/a.txt", "a\n")
+            .symlink("examples/leak", &secret.to_string_lossy())
+            .symlink("examples/dir-leak", &outside.path().join("private").to_string_lossy())
+            .symlink("examples/relative", "a.txt");
+        repo.commit("init");
+
+        let mut backend = backend(&repo);
+        let resolved = backend.resolve_ref(None).unwrap();
+        let out = TempDir::new().unwrap();
+        let target = out.path().join("examples");
+        let stats = backend
+            .materialize(&resolved, Some("examples"), &Filter::default(), &target)
+            .unwrap();
+        assert_eq!(stats.files, 1);
+
+        assert_eq!(std::fs::read_link(target.join("leak")).unwrap(), secret);
+        assert_eq!(std::fs::read_link(target.join("relative")).unwrap(), Path::new("a.txt"));
+        assert!(target.join("dir-leak").is_symlink());
+        assert!(snapshot(&target).iter().all(|(_, content, _)| {
+            content != "file:local secret\n" && content != "file:key\n"
+        }));
+
+        // 来源记录中记录的是链接本身，而不是本地文件的内容
+        let records = collect_files(&target).unwrap();
+        let leak = records.iter().find(|record| record.path == "leak").unwrap();
+        let expected = format!("{:x}", Sha256::digest(secret.to_string_lossy().as_bytes()));
+        assert_eq!(leak.sha256, expected);
+        assert!(records.iter().all(|record| !record.path.starts_with("dir-leak/")));
+
+        // 路径本身是指向目录的链接时同样按链接复制
+        let link_target = out.path().join("dir-leak");
+        backend
+            .materialize(&resolved, Some("examples/dir-leak"), &Filter::default(), &link_target)
+            .unwrap();
+        assert!(link_target.is_symlink());
+    }
+
+    #[test]
+    fn sparse_checkout_matches_only_the_requested_path() {
+        let repo = FixtureRepo::new();
+        let cases = [
+            ("examples", "foo/examples"),
+            ("star*", "starX"),
+            ("q?", "qq"),
+            ("br[ac]k", "brak"),
+            ("a\\b", "ab"),
+            ("sp ", "sp"),
+            ("!bang", "bang"),
+            ("#hash", "hash"),
+            ("-dash", "dash"),
+        ];
+        for (path, lookalike) in cases {
+            repo.write(&format!("{}/wanted.txt", path), path)
+                .write(&format!("{}/other.txt", lookalike), lookalike);
+        }
+        repo.commit("init");
+
+        let include = Filter::new(&["*.txt".to_string()], &[]).unwrap();
+        for (path, lookalike) in cases {
+            // 有无过滤规则都只检出指定路径
+            for filter in [Filter::default(), include.clone()] {
+                let mut backend = backend(&repo);
+                let resolved = backend.resolve_ref(None).unwrap();
+                let out = TempDir::new().unwrap();
+                let target = out.path().join("out");
+                backend.materialize(&resolved, Some(path), &filter, &target).unwrap();
+                assert_eq!(
+                    std::fs::read_to_string(target.join("wanted.txt")).unwrap(),
+                    path,
+                    "{:?}",
+                    path
+                );
+                let checked_out = backend.workdir.path().join(lookalike).join("other.txt");
+                assert!(!checked_out.exists(), "{:?} 检出了 {:?}", path, lookalike);
+            }
+        }
+    }
+
+    #[test]
+    fn files_in_parent_directories_are_not_checked_out() {
+        let repo = FixtureRepo::new();
+        repo.write("top.txt", "top\n")
+            .write("a/mid.txt", "mid\n")
+            .write("a/b/wanted.txt", "wanted\n");
+        repo.commit("init");
+
+        let mut backend = backend(&repo);
+        let resolved = backend.resolve_ref(None).unwrap();
+        let out = TempDir::new().unwrap();
+        backend
+            .materialize(&resolved, Some("a/b"), &Filter::default(), &out.path().join("b"))
+            .unwrap();
+        let workdir = backend.workdir.path();
+        assert!(workdir.join("a/b/wanted.txt").exists());
+        assert!(!workdir.join("top.txt").exists());
+        assert!(!workdir.join("a/mid.txt").exists());
+    }
+
+    /// 记录进度消息的后端
+    fn recording_backend(url: String) -> (GitCliBackend, Arc<Mutex<Vec<String>>>) {
+        let messages = Arc::new(Mutex::new(Vec::new()));
+        let log = Arc::clone(&messages);
+        let progress = Progress::new(move |message| log.lock().unwrap().push(message.to_string()));
+        let remote = Remote {
+            url,
+            token: None,
+            ssh_key: None,
+        };
+        (GitCliBackend::new(remote, progress, false).unwrap(), messages)
+    }
+
+    #[test]
+    fn failed_fetch_keeps_partial_clone() {
+        let repo = FixtureRepo::new();
+        repo.write("a.txt", "a\n");
+        repo.commit("init");
+
+        // 命令行本身含有 --filter，报错信息中也会出现，不能据此判断 git 不支持部分克隆
+        let (mut backend, messages) = recording_backend(repo.url());
+        let missing = "0123456789abcdef0123456789abcdef01234567";
+        assert!(backend.resolve_ref(Some(missing)).is_err());
+        assert_eq!(backend.strategy, Strategy::Partial);
+        assert!(messages.lock().unwrap().iter().all(|m| !m.contains("不支持部分克隆")));
+
+        backend.resolve_ref(None).unwrap();
+        assert_eq!(backend.strategy, Strategy::Partial);
+    }
+
+    #[test]
+    fn server_without_partial_clone_is_reported() {
+        let repo = FixtureRepo::new();
+        repo.write("examples/a.txt", "a\n");
+        repo.commit("init");
+        git(repo.path(), &["config", "uploadpack.allowFilter", "false"]);
+
+        let (mut backend, messages) = recording_backend(repo.url());
+        let resolved = backend.resolve_ref(None).unwrap();
+        assert_eq!(backend.strategy, Strategy::Shallow);
+        let messages = messages.lock().unwrap().clone();
+        assert!(
+            messages.iter().any(|m| m.contains("远程服务器不支持部分克隆")),
+            "{:?}",
+            messages
+        );
+        assert!(messages.iter().any(|m| m.contains(Strategy::Shallow.describe())));
+
+        let out = TempDir::new().unwrap();
+        let target = out.path().join("examples");
+        backend
+            .materialize(&resolved, Some("examples"), &Filter::default(), &target)
+            .unwrap();
+        assert_eq!(std::fs::read_to_string(target.join("a.txt")).unwrap(), "a\n");
+    }
+}
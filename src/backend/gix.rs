@@ -0,0 +1,514 @@
+//! 基于 gix 的纯 Rust 后端：不依赖系统 git，在进程内完成浅拉取与检出
+//!
+//! 仅在启用 `gix` 特性时编译。
+
+use super::{Backend, EntryKind, Remote, ResolvedRef, TreeEntry};
+use crate::cleanup::TempDir;
+use crate::copy::CopyStats;
+use crate::filter::Filter;
+use crate::git::{auth_header, redact_token, ssh_command};
+use crate::progress::Progress;
+use crate::refs::{find_ref, is_hex_sha, RemoteRef};
+use anyhow::{anyhow, bail, Context, Result};
+use gix::bstr::{BStr, BString, ByteSlice};
+use gix::objs::tree::EntryMode;
+use gix::ObjectId;
+use std::num::NonZeroU32;
+use std::path::Path;
+
+/// 使用 gix 在进程内拉取的后端
+///
+/// 拉取得到的裸仓库保存在后端持有的临时目录中，后端被 drop 时自动清理。
+pub struct GixBackend {
+    remote: Remote,
+    progress: Progress,
+    workdir: TempDir,
+    repo: Option<gix::Repository>,
+}
+
+/// 树中的一个条目，路径相对仓库根目录
+struct Node {
+    /// 用于列表与过滤的路径（非 UTF-8 的字节按替换字符显示）
+    path: String,
+    /// 原始路径字节，用于写入文件
+    raw: BString,
+    mode: EntryMode,
+    id: ObjectId,
+}
+
+impl GixBackend {
+    /// 创建用于存放裸仓库的临时目录
+    pub fn new(remote: Remote, progress: Progress) -> Result<Self> {
+        let workdir = TempDir::new()?;
+        progress.emit(format!("🔧 临时目录: {}", workdir.path().display()));
+
+        Ok(Self {
+            remote,
+            progress,
+            workdir,
+            repo: None,
+        })
+    }
+
+    /// 浅拉取 reference（为 None 时为远程 HEAD）指向的提交到临时目录中的裸仓库
+    fn fetch(&self, reference: Option<&str>) -> Result<gix::Repository> {
+        let prepare = self
+            .prepare("shallow.git")?
+            .with_shallow(gix::remote::fetch::Shallow::DepthAtRemote(NonZeroU32::MIN))
+            .with_ref_name(reference)
+            .with_context(|| format!("无效的引用名: {}", reference.unwrap_or_default()))?;
+        self.run_fetch(prepare)
+    }
+
+    /// 拉取所有分支与标签的完整历史，用于按提交 SHA 查找
+    fn fetch_full_history(&self) -> Result<gix::Repository> {
+        let prepare = self.prepare("full.git")?.configure_remote(|remote| {
+            Ok(remote.with_fetch_tags(gix::remote::fetch::Tags::All))
+        });
+        self.run_fetch(prepare)
+    }
+
+    fn prepare(&self, dir: &str) -> Result<gix::clone::PrepareFetch> {
+        let mut overrides = Vec::new();
+        if let Some(token) = &self.remote.token {
+            // 仅保存在内存中的配置，不会写入仓库的 config 文件
+            let header = auth_header(&self.remote.url, token);
+            overrides.push(format!("http.extraHeader={}", header));
+        }
+        if let Some(key) = &self.remote.ssh_key {
+            // 自定义命令无法自动识别变体，显式声明为 OpenSSH 以支持端口等参数
+            overrides.push(format!("core.sshCommand={}", ssh_command(key)));
+            overrides.push("ssh.variant=ssh".to_string());
+        }
+
+        Ok(gix::prepare_clone_bare(self.remote.url.as_str(), self.workdir.path().join(dir))
+            .with_context(|| format!("无法初始化仓库: {}", self.remote.url))?
+            .with_in_memory_config_overrides(overrides))
+    }
+
+    fn run_fetch(&self, mut prepare: gix::clone::PrepareFetch) -> Result<gix::Repository> {
+        let (repo, _) = prepare
+            .fetch_only(gix::progress::Discard, &gix::interrupt::IS_INTERRUPTED)
+            .map_err(|e| anyhow!(redact_token(&e.to_string(), self.remote.token.as_deref())))?;
+        Ok(repo)
+    }
+
+    fn repo(&self) -> Result<&gix::Repository> {
+        self.repo
+            .as_ref()
+            .context("尚未拉取仓库，请先解析引用")
+    }
+
+    /// 收集 commit 中 path 下的条目（包含 path 本身）
+    fn collect(&self, commit: &str, path: Option<&str>) -> Result<Vec<Node>> {
+        let repo = self.repo()?;
+        let commit_id = ObjectId::from_hex(commit.as_bytes())
+            .with_context(|| format!("无效的提交: {}", commit))?;
+        let root = repo.find_commit(commit_id)?.tree_id()?.detach();
+
+        let mut nodes = Vec::new();
+        let Some(path) = path else {
+            walk_tree(repo, root, b"".as_bstr(), &mut nodes)?;
+            return Ok(nodes);
+        };
+
+        let Some(entry) = repo.find_tree(root)?.lookup_entry_by_path(path)? else {
+            return Ok(nodes);
+        };
+        let node = Node {
+            path: path.to_string(),
+            raw: path.into(),
+            mode: entry.mode(),
+            id: entry.object_id(),
+        };
+        if node.mode.is_tree() {
+            let id = node.id;
+            nodes.push(node);
+            walk_tree(repo, id, path.as_bytes().as_bstr(), &mut nodes)?;
+        } else {
+            nodes.push(node);
+        }
+        Ok(nodes)
+    }
+}
+
+impl Backend for GixBackend {
+    fn name(&self) -> &'static str {
+        "gix"
+    }
+
+    fn list_refs(&mut self) -> Result<Vec<RemoteRef>> {
+        use gix::protocol::handshake::Ref;
+        use gix::remote::Direction;
+
+        // 只需握手拿到引用列表，使用一个空的裸仓库作为连接载体（多次调用时复用）
+        let dir = self.workdir.path().join("refs.git");
+        let mut repo = if dir.exists() {
+            gix::open(&dir).context("无法打开仓库")?
+        } else {
+            gix::init_bare(&dir).context("无法初始化仓库")?
+        };
+        if let Some(token) = &self.remote.token {
+            let mut config = repo.config_snapshot_mut();
+            let header = auth_header(&self.remote.url, token);
+            config.set_raw_value(&"http.extraHeader", header.as_str())?;
+        }
+        if let Some(key) = &self.remote.ssh_key {
+            let mut config = repo.config_snapshot_mut();
+            config.set_raw_value(&"core.sshCommand", ssh_command(key).as_str())?;
+            config.set_raw_value(&"ssh.variant", "ssh")?;
+        }
+
+        let remote = repo
+            .remote_at(self.remote.url.as_str())?
+            .with_refspecs(Some("+refs/*:refs/*"), Direction::Fetch)?;
+        let (ref_map, _) = remote
+            .connect(Direction::Fetch)
+            .map_err(|e| anyhow!(redact_token(&e.to_string(), self.remote.token.as_deref())))?
+            .ref_map(
+                gix::progress::Discard,
+                gix::remote::ref_map::Options {
+                    prefix_from_spec_as_filter_on_remote: false,
+                    ..Default::default()
+                },
+            )
+            .map_err(|e| anyhow!(redact_token(&e.to_string(), self.remote.token.as_deref())))
+            .context("无法访问远程仓库，请检查仓库地址是否正确")?;
+
+        Ok(ref_map
+            .remote_refs
+            .into_iter()
+            .filter_map(|r| match r {
+                Ref::Peeled {
+                    full_ref_name,
+                    object,
+                    ..
+                }
+                | Ref::Direct {
+                    full_ref_name,
+                    object,
+                }
+                | Ref::Symbolic {
+                    full_ref_name,
+                    object,
+                    ..
+                } => Some(RemoteRef {
+                    name: full_ref_name.to_string(),
+                    commit: object.to_string(),
+                }),
+                Ref::Unborn { .. } => None,
+            })
+            .collect())
+    }
+
+    fn resolve_ref(&mut self, reference: Option<&str>) -> Result<ResolvedRef> {
+        self.progress.emit("📥 正在拉取仓库...");
+
+        // 未指定引用时 gix 会跟随远程 HEAD 公布的默认分支；
+        // 完整 SHA 不能作为引用名匹配，直接走下面的完整历史分支
+        let fetch_result = match reference {
+            Some(rev) if rev.len() == 40 && is_hex_sha(rev) => {
+                Err(anyhow!("提交 SHA 不是引用名: {}", rev))
+            }
+            _ => self.fetch(reference),
+        };
+        let (repo, commit) = match (fetch_result, reference) {
+            (Ok(repo), _) => {
+                let commit = repo
+                    .head_commit()
+                    .context("无法读取拉取到的提交")?
+                    .id
+                    .to_string();
+                (repo, commit)
+            }
+            // 提交 SHA 无法作为引用名拉取，改为拉取完整历史后在本地查找；
+            // 只在远程确实没有该引用时这样做，网络、认证等错误直接返回
+            (Err(e), Some(rev)) if is_hex_sha(rev) => {
+                match self.list_refs() {
+                    Ok(refs) if find_ref(rev, &refs).is_none() => {}
+                    _ => return Err(e.context("无法拉取仓库，请检查仓库地址和引用是否正确")),
+                }
+                self.progress.emit("⚠️  无法直接按提交拉取，改为拉取完整历史...");
+                let repo = self
+                    .fetch_full_history()
+                    .with_context(|| format!("按引用拉取失败: {:#}", e))
+                    .context("无法拉取仓库，请检查仓库地址是否正确")?;
+                let commit = repo
+                    .rev_parse_single(rev)
+                    .ok()
+                    .and_then(|id| id.object().ok()?.peel_to_commit().ok())
+                    .with_context(|| format!("远程仓库中不存在提交: {}", rev))?
+                    .id
+                    .to_string();
+                (repo, commit)
+            }
+            (Err(e), _) => {
+                return Err(e.context("无法拉取仓库，请检查仓库地址和引用是否正确"));
+            }
+        };
+
+        let name = match reference {
+            Some(reference) => reference.to_string(),
+            None => repo
+                .head_name()?
+                .map(|name| name.shorten().to_string())
+                .context("远程仓库未公布默认分支，请使用 --ref 明确指定分支")?,
+        };
+        self.repo = Some(repo);
+        self.progress.emit("📥 拉取完成");
+
+        Ok(ResolvedRef { name, commit })
+    }
+
+    fn list_tree(&mut self, resolved: &ResolvedRef, path: Option<&str>) -> Result<Vec<TreeEntry>> {
+        let repo = self.repo()?;
+        self.collect(&resolved.commit, path)?
+            .into_iter()
+            .map(|node| {
+                let (kind, size) = if node.mode.is_tree() {
+                    (EntryKind::Tree, None)
+                } else if node.mode.is_commit() {
+                    (EntryKind::Commit, None)
+                } else {
+                    (EntryKind::Blob, Some(repo.find_header(node.id)?.size()))
+                };
+                Ok(TreeEntry {
+                    path: node.path,
+                    kind,
+                    size,
+                })
+            })
+            .collect()
+    }
+
+    fn path_id(&mut self, resolved: &ResolvedRef, path: Option<&str>) -> Result<Option<String>> {
+        let repo = self.repo()?;
+        let commit_id = ObjectId::from_hex(resolved.commit.as_bytes())
+            .with_context(|| format!("无效的提交: {}", resolved.commit))?;
+        let root = repo.find_commit(commit_id)?.tree_id()?.detach();
+        let Some(path) = path else {
+            return Ok(Some(root.to_string()));
+        };
+        Ok(repo
+            .find_tree(root)?
+            .lookup_entry_by_path(path)?
+            .map(|entry| entry.object_id().to_string()))
+    }
+
+    fn materialize(
+        &mut self,
+        resolved: &ResolvedRef,
+        path: Option<&str>,
+        filter: &Filter,
+        target: &Path,
+    ) -> Result<CopyStats> {
+        let nodes = self.collect(&resolved.commit, path)?;
+        let root = match path {
+            Some(subdir) => match nodes.first() {
+                Some(node) if node.mode.is_tree() => format!("{}/", subdir),
+                Some(node) if node.mode.is_blob_or_symlink() => {
+                    // 单个文件：target 即文件路径
+                    let blob = self.repo()?.find_blob(node.id)?;
+                    write_blob(target, &blob.data, node.mode)
+                        .with_context(|| format!("无法写入文件: {}", target.display()))?;
+                    return Ok(CopyStats {
+                        files: 1,
+                        bytes: blob.data.len() as u64,
+                    });
+                }
+                _ => bail!(
+                    "远程仓库中未找到指定路径: {}",
+                    subdir
+                ),
+            },
+            None => String::new(),
+        };
+
+        self.progress.emit("📋 正在写入文件...");
+        std::fs::create_dir_all(target)
+            .with_context(|| format!("无法创建目标目录: {}", target.display()))?;
+
+        let repo = self.repo()?;
+        let mut stats = CopyStats::default();
+        for node in nodes.iter().filter(|node| node.raw.starts_with(root.as_bytes())) {
+            let relative = &node.path[root.len()..];
+            let dest_path = target.join(gix::path::from_byte_slice(&node.raw[root.len()..]));
+            if node.mode.is_tree() || node.mode.is_commit() {
+                // 子模块与 git checkout 一样只留下空目录；有过滤规则时目录随文件创建
+                if filter.is_empty() {
+                    std::fs::create_dir_all(&dest_path)?;
+                }
+                continue;
+            }
+            if !filter.matches(relative) {
+                continue;
+            }
+
+            let blob = repo.find_blob(node.id)?;
+            write_blob(&dest_path, &blob.data, node.mode)
+                .with_context(|| format!("无法写入文件: {}", dest_path.display()))?;
+            if !node.mode.is_link() {
+                stats.files += 1;
+                stats.bytes += blob.data.len() as u64;
+            }
+        }
+
+        Ok(stats)
+    }
+}
+
+/// 深度优先遍历树，按 git ls-tree 的顺序收集条目
+fn walk_tree(
+    repo: &gix::Repository,
+    tree_id: ObjectId,
+    prefix: &BStr,
+    nodes: &mut Vec<Node>,
+) -> Result<()> {
+    let tree = repo.find_tree(tree_id)?;
+    for entry in tree.iter() {
+        let entry = entry?;
+        let mut raw = BString::from(prefix);
+        if !raw.is_empty() {
+            raw.push(b'/');
+        }
+        raw.extend_from_slice(entry.filename());
+        let node = Node {
+            path: raw.to_str_lossy().into_owned(),
+            raw,
+            mode: entry.mode(),
+            id: entry.object_id(),
+        };
+        if node.mode.is_tree() {
+            let (id, raw) = (node.id, node.raw.clone());
+            nodes.push(node);
+            walk_tree(repo, id, raw.as_bstr(), nodes)?;
+        } else {
+            nodes.push(node);
+        }
+    }
+    Ok(())
+}
+
+/// 按条目类型写入 blob：普通文件、可执行文件或符号链接
+fn write_blob(dest_path: &Path, data: &[u8], mode: EntryMode) -> Result<()> {
+    if let Some(parent) = dest_path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    #[cfg(unix)]
+    if mode.is_link() {
+        std::os::unix::fs::symlink(gix::path::from_byte_slice(data), dest_path)?;
+        return Ok(());
+    }
+
+    std::fs::write(dest_path, data)?;
+
+    // 与 git checkout 一样遵循 umask：在已有的读权限位上加执行权限
+    #[cfg(unix)]
+    if mode.is_executable() {
+        use std::os::unix::fs::PermissionsExt;
+        let mut permissions = std::fs::metadata(dest_path)?.permissions();
+        let readable = permissions.mode() & 0o444;
+        permissions.set_mode(permissions.mode() | readable >> 2);
+        std::fs::set_permissions(dest_path, permissions)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::backend::GitCliBackend;
+    use crate::test_support::{snapshot, FixtureRepo, Snapshot};
+    use std::os::unix::ffi::OsStrExt;
+    use std::os::unix::fs::PermissionsExt;
+
+    fn remote(repo: &FixtureRepo) -> Remote {
+        Remote {
+            url: repo.url(),
+            token: None,
+            ssh_key: None,
+        }
+    }
+
+    /// 用两个后端分别抓取同一路径，返回各自输出的快照
+    fn fetch_both(
+        repo: &FixtureRepo,
+        path: Option<&str>,
+        filter: &Filter,
+    ) -> (Snapshot, Snapshot) {
+        let out = tempfile::TempDir::new().unwrap();
+        let mut backends: [Box<dyn Backend>; 2] = [
+            Box::new(GitCliBackend::new(remote(repo), Progress::silent(), false).unwrap()),
+            Box::new(GixBackend::new(remote(repo), Progress::silent()).unwrap()),
+        ];
+        let mut snapshots = Vec::new();
+        for (index, backend) in backends.iter_mut().enumerate() {
+            let resolved = backend.resolve_ref(None).unwrap();
+            let target = out.path().join(index.to_string());
+            backend.materialize(&resolved, path, filter, &target).unwrap();
+            snapshots.push(snapshot(&target));
+        }
+        let gix = snapshots.pop().unwrap();
+        (snapshots.pop().unwrap(), gix)
+    }
+
+    #[test]
+    fn commit_shas_fall_back_to_full_history_only_when_no_ref_matches() {
+        let repo = FixtureRepo::new();
+        repo.write("a.txt", "v1\n");
+        let first = repo.commit("v1");
+        repo.write("a.txt", "v2\n");
+        repo.commit("v2");
+
+        for rev in [&first[..7], first.as_str()] {
+            let mut backend = GixBackend::new(remote(&repo), Progress::silent()).unwrap();
+            let resolved = backend.resolve_ref(Some(rev)).unwrap();
+            assert_eq!(resolved.commit, first, "{}", rev);
+            // 之前列出过引用时也能再次列出
+            assert!(!backend.list_refs().unwrap().is_empty());
+        }
+
+        // 远程无法访问时返回原始错误，而不是报告提交不存在
+        let missing = Remote {
+            url: format!("file://{}/missing", repo.path().display()),
+            token: None,
+            ssh_key: None,
+        };
+        let mut backend = GixBackend::new(missing, Progress::silent()).unwrap();
+        let err = format!("{:#}", backend.resolve_ref(Some(&first[..7])).unwrap_err());
+        assert!(err.contains("请检查仓库地址和引用是否正确"), "{}", err);
+        assert!(!err.contains("不存在提交"), "{}", err);
+    }
+
+    #[test]
+    fn output_matches_git_backend() {
+        let repo = FixtureRepo::new();
+        repo.write("examples/a.txt", "a\n")
+            .write("examples/sub/b.txt", "b\n")
+            .write("examples/run.sh", "#!/bin/sh\n")
+            .write("other/c.txt", "c\n")
+            .symlink("examples/link", "sub/b.txt")
+            .symlink("examples/outside", "/etc/hostname");
+        let run = repo.path().join("examples/run.sh");
+        std::fs::set_permissions(&run, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let name = std::ffi::OsStr::from_bytes(b"examples/caf\xe9.txt");
+        std::fs::write(repo.path().join(name), "latin-1\n").unwrap();
+        repo.commit("init");
+
+        let (git, gix) = fetch_both(&repo, Some("examples"), &Filter::default());
+        assert_eq!(git, gix);
+        assert!(git.iter().any(|(path, _, _)| path == b"caf\xe9.txt"));
+        assert!(git
+            .iter()
+            .any(|(path, content, _)| path == b"outside" && content.starts_with("link:")));
+
+        let (git, gix) = fetch_both(&repo, None, &Filter::default());
+        assert_eq!(git, gix);
+
+        let filter = Filter::new(&["**/*.txt".to_string()], &["sub/".to_string()]).unwrap();
+        let (git, gix) = fetch_both(&repo, Some("examples"), &filter);
+        assert_eq!(git, gix);
+    }
+}
@@ -0,0 +1,253 @@
+//! 可替换的抓取后端
+//!
+//! 一次抓取被拆成几个步骤：列出远程引用、把引用解析为提交、列出仓库树、把子目录落盘到
+//! 本地目录。不同后端只需实现 [`Backend`]，抓取流程本身无需改动：
+//! - [`GitCliBackend`]：调用系统 git，部分克隆（默认经由本地缓存）+ sparse-checkout
+//! - [`ArchiveBackend`]：通过 GitHub API 解析引用，下载 codeload 的 tar.gz 并只解压所需子目录
+//! - `GixBackend`（需启用 `gix` 特性）：纯 Rust 实现，不依赖系统 git
+
+mod archive;
+mod git_cli;
+#[cfg(feature = "gix")]
+mod gix;
+
+pub use archive::ArchiveBackend;
+pub use git_cli::GitCliBackend;
+#[cfg(feature = "gix")]
+pub use self::gix::GixBackend;
+
+use crate::copy::CopyStats;
+use crate::filter::Filter;
+use crate::progress::Progress;
+use crate::refs::RemoteRef;
+use crate::repo::github_repo_slug;
+use anyhow::{bail, Result};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::str::FromStr;
+
+/// 要访问的远程仓库
+#[derive(Clone)]
+pub struct Remote {
+    /// 完整的 Git 仓库 URL
+    pub url: String,
+    /// 访问 token
+    pub token: Option<String>,
+    /// SSH 私钥路径，用于 ssh:// 与 scp 风格的地址
+    pub ssh_key: Option<PathBuf>,
+}
+
+impl fmt::Debug for Remote {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // token 不能出现在调试输出中
+        f.debug_struct("Remote")
+            .field("url", &self.url)
+            .field("token", &self.token.as_ref().map(|_| "***"))
+            .field("ssh_key", &self.ssh_key)
+            .finish()
+    }
+}
+
+/// 解析后的引用
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResolvedRef {
+    /// 实际使用的引用名
+    pub name: String,
+    /// 引用指向的提交 SHA
+    pub commit: String,
+}
+
+/// 仓库树中条目的类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntryKind {
+    /// 文件（包括符号链接）
+    Blob,
+    /// 目录
+    Tree,
+    /// 子模块
+    Commit,
+}
+
+/// 仓库树中的一个条目
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TreeEntry {
+    /// 相对仓库根目录的路径
+    pub path: String,
+    /// 条目类型
+    pub kind: EntryKind,
+    /// 文件大小（仅 blob 有值；后端无法直接获得时为 None）
+    pub size: Option<u64>,
+}
+
+/// 一次批量落盘中的一项：把提交中 path 的内容写入 target
+#[derive(Debug, Clone, Copy)]
+pub struct Placement<'a> {
+    /// 仓库内路径，None 表示整个仓库
+    pub path: Option<&'a str>,
+    /// 过滤规则（仅对目录生效）
+    pub filter: &'a Filter,
+    /// 目标路径
+    pub target: &'a Path,
+}
+
+/// 抓取后端
+pub trait Backend {
+    /// 后端名称，用于输出与结果记录
+    fn name(&self) -> &'static str;
+
+    /// 列出远程公布的所有引用
+    fn list_refs(&mut self) -> Result<Vec<RemoteRef>>;
+
+    /// 把引用解析为提交；reference 为 None 时使用仓库的默认分支
+    fn resolve_ref(&mut self, reference: Option<&str>) -> Result<ResolvedRef>;
+
+    /// 递归列出提交中 path（为 None 时为整个仓库）下的所有条目
+    fn list_tree(&mut self, resolved: &ResolvedRef, path: Option<&str>) -> Result<Vec<TreeEntry>>;
+
+    /// 查询提交中 path 的类型，不存在时返回 None
+    fn path_kind(&mut self, resolved: &ResolvedRef, path: &str) -> Result<Option<EntryKind>> {
+        Ok(self
+            .list_tree(resolved, Some(path))?
+            .into_iter()
+            .find(|entry| entry.path == path)
+            .map(|entry| entry.kind))
+    }
+
+    /// 查询提交中 path（为 None 时为根目录）对应的 git 对象 ID，不存在时返回 None
+    ///
+    /// 目录为 tree 哈希，文件为 blob 哈希，可用于校验抓取内容是否一致。
+    fn path_id(&mut self, resolved: &ResolvedRef, path: Option<&str>) -> Result<Option<String>>;
+
+    /// 把提交中 path 的内容写入 target（不包含 .git 元数据）
+    ///
+    /// path 为目录（或 None）时 target 为目录，只写入 filter 保留的文件；
+    /// path 为文件时 target 为文件路径，filter 不起作用。
+    fn materialize(
+        &mut self,
+        resolved: &ResolvedRef,
+        path: Option<&str>,
+        filter: &Filter,
+        target: &Path,
+    ) -> Result<CopyStats>;
+
+    /// 把同一提交中的多个路径分别写入各自的目标路径，结果与 placements 一一对应
+    ///
+    /// 默认逐个调用 [`materialize`](Self::materialize)；能一次完成检出或下载的后端可覆盖此方法。
+    fn materialize_many(
+        &mut self,
+        resolved: &ResolvedRef,
+        placements: &[Placement<'_>],
+    ) -> Result<Vec<CopyStats>> {
+        placements
+            .iter()
+            .map(|placement| {
+                self.materialize(resolved, placement.path, placement.filter, placement.target)
+            })
+            .collect()
+    }
+
+    /// 把提交中各路径（None 表示整个仓库）的内容下载到本地缓存，供离线模式使用
+    ///
+    /// 默认不做任何事；只有会写入本地缓存的后端需要覆盖此方法。
+    fn prefetch(&mut self, _resolved: &ResolvedRef, _paths: &[Option<&str>]) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// 后端选择
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BackendKind {
+    /// 有系统 git 时使用 git，否则优先使用 gix（若已启用），再否则对 GitHub 仓库使用 archive
+    #[default]
+    Auto,
+    /// 系统 git + sparse-checkout
+    Git,
+    /// GitHub tarball 下载
+    Archive,
+    /// 纯 Rust 的 gix 实现（需启用 `gix` 特性）
+    Gix,
+}
+
+impl FromStr for BackendKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(Self::Auto),
+            "git" => Ok(Self::Git),
+            "archive" => Ok(Self::Archive),
+            "gix" => Ok(Self::Gix),
+            other => Err(format!("未知的后端: {}（可选: git、archive、gix、auto）", other)),
+        }
+    }
+}
+
+impl fmt::Display for BackendKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Auto => "auto",
+            Self::Git => "git",
+            Self::Archive => "archive",
+            Self::Gix => "gix",
+        })
+    }
+}
+
+/// 按选择创建后端；cache 只对 git 后端生效，offline 时只能使用 git 后端的本地缓存
+pub fn open_backend(
+    kind: BackendKind,
+    remote: Remote,
+    progress: Progress,
+    cache: bool,
+    offline: bool,
+) -> Result<Box<dyn Backend>> {
+    if offline {
+        return match kind {
+            BackendKind::Auto | BackendKind::Git => {
+                Ok(Box::new(GitCliBackend::offline(remote, progress)?))
+            }
+            kind => bail!("离线模式只能使用 git 后端的本地缓存，不支持 {} 后端", kind),
+        };
+    }
+
+    let kind = match kind {
+        BackendKind::Auto if git_available() => BackendKind::Git,
+        BackendKind::Auto if cfg!(feature = "gix") => BackendKind::Gix,
+        BackendKind::Auto if github_repo_slug(&remote.url).is_some() => BackendKind::Archive,
+        BackendKind::Auto => BackendKind::Git,
+        kind => kind,
+    };
+
+    Ok(match kind {
+        BackendKind::Archive => Box::new(ArchiveBackend::new(remote, progress)?),
+        #[cfg(feature = "gix")]
+        BackendKind::Gix => Box::new(GixBackend::new(remote, progress)?),
+        #[cfg(not(feature = "gix"))]
+        BackendKind::Gix => bail!("gix 后端未启用，请使用 `--features gix` 重新编译 git-get"),
+        _ => Box::new(GitCliBackend::new(remote, progress, cache)?),
+    })
+}
+
+/// 系统中是否有可用的 git 命令
+fn git_available() -> bool {
+    Command::new("git")
+        .arg("--version")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success())
+}
+
+/// 判断 entry_path 是否位于 path 之下（或就是 path 本身）
+pub(crate) fn is_under(entry_path: &str, path: Option<&str>) -> bool {
+    match path {
+        None => true,
+        Some(path) => {
+            entry_path == path
+                || entry_path
+                    .strip_prefix(path)
+                    .is_some_and(|rest| rest.starts_with('/'))
+        }
+    }
+}
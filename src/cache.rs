@@ -0,0 +1,456 @@
+//! 裸仓库的本地缓存：重复抓取同一仓库时只增量拉取缺少的对象
+//!
+//! 缓存位于 `<系统缓存目录>/git-get/repos`，每个远程仓库按规范化后的 URL 对应一个裸仓库。
+//! git 后端把对象拉取到缓存仓库中，再在临时工作区中直接使用缓存仓库的对象目录完成检出，
+//! 因此同一仓库的多次抓取只需为缺少的对象付出网络开销。缓存总大小超过上限时，
+//! 最久未使用的仓库会被删除。离线模式下只从缓存仓库中解析引用与读取对象。
+//!
+//! 每个缓存仓库旁有一个 `<仓库>.lock` 锁文件：使用仓库期间持有共享锁，创建、重建与删除
+//! 仓库时持有独占锁，因此多个 git-get 进程可以同时使用缓存，清理时只删除无人使用的仓库。
+//!
+//! 缓存目录与大小上限可在配置文件中用 `cache_dir`、`cache_max_size` 指定，
+//! 也可用环境变量 GIT_GET_CACHE_DIR、GIT_GET_CACHE_MAX_SIZE 覆盖。
+
+use crate::config::config;
+use crate::git::{git_output, run_git_command};
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs::{File, TryLockError};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// 默认的缓存大小上限（5 GiB）
+pub const DEFAULT_MAX_SIZE: u64 = 5 << 30;
+
+/// 缓存仓库中记录来源的文件，修改时间即最近使用时间
+const INFO_FILE: &str = "git-get-cache.json";
+
+/// 缓存仓库的来源记录
+#[derive(Debug, Serialize, Deserialize)]
+struct CacheInfo {
+    /// 远程仓库 URL
+    url: String,
+}
+
+/// 缓存中的一个仓库
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CacheEntry {
+    /// 远程仓库 URL
+    pub url: String,
+    /// 裸仓库路径
+    pub path: PathBuf,
+    /// 占用的磁盘空间（字节）
+    pub size: u64,
+    /// 最近一次使用的时间
+    pub last_used: SystemTime,
+}
+
+/// 缓存根目录
+pub fn cache_dir() -> Result<PathBuf> {
+    match &config().cache_dir {
+        Some(dir) => Ok(dir.clone()),
+        None => dirs::cache_dir()
+            .map(|dir| dir.join("git-get"))
+            .context("无法确定缓存目录，请通过 GIT_GET_CACHE_DIR 指定"),
+    }
+}
+
+/// 缓存大小上限（字节）
+pub fn max_size() -> Result<u64> {
+    match &config().cache_max_size {
+        Some(size) => parse_size(size),
+        None => Ok(DEFAULT_MAX_SIZE),
+    }
+}
+
+/// 存放裸仓库的目录
+fn repos_dir() -> Result<PathBuf> {
+    Ok(cache_dir()?.join("repos"))
+}
+
+/// 规范化仓库 URL：scp 风格地址改写为 ssh://，主机名小写，去掉末尾的 / 与 .git（本地仓库只去掉 /）
+///
+/// 指向同一仓库的不同写法（如是否带 .git）因此共用一个缓存仓库。
+pub fn normalize_url(url: &str) -> String {
+    let url = url.trim().trim_end_matches('/');
+    // 本地仓库的目录名可能以 .git 结尾，去掉后可能指向另一个仓库
+    if url.starts_with("file://") || !url.contains(':') {
+        return url.to_string();
+    }
+    let url = url.strip_suffix(".git").unwrap_or(url);
+
+    let (scheme, rest) = match url.split_once("://") {
+        Some((scheme, rest)) => (scheme.to_ascii_lowercase(), rest.to_string()),
+        // git@host:owner/repo
+        None => match url.split_once(':') {
+            Some((host, path)) if host.contains('@') && !path.starts_with("//") => {
+                ("ssh".to_string(), format!("{}/{}", host, path))
+            }
+            _ => return url.to_string(),
+        },
+    };
+    let (authority, path) = rest.split_once('/').unwrap_or((rest.as_str(), ""));
+    let authority = match authority.rsplit_once('@') {
+        Some((user, host)) => format!("{}@{}", user, host.to_ascii_lowercase()),
+        None => authority.to_ascii_lowercase(),
+    };
+    format!("{}://{}/{}", scheme, authority, path)
+}
+
+/// 仓库在缓存中的路径：`<仓库名>-<URL 哈希>.git`
+pub fn repo_path(url: &str) -> Result<PathBuf> {
+    let normalized = normalize_url(url);
+    let hash = format!("{:x}", Sha256::digest(normalized.as_bytes()));
+    let name: String = normalized
+        .rsplit(['/', ':'])
+        .next()
+        .unwrap_or_default()
+        .trim_end_matches(".git")
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .collect();
+    Ok(repos_dir()?.join(format!("{}-{}.git", name, &hash[..16])))
+}
+
+/// 缓存仓库的锁，drop 时释放
+///
+/// 共享锁表示仓库正在使用，独占锁用于创建、重建与删除仓库。
+#[derive(Debug)]
+pub(crate) struct RepoLock {
+    _file: File,
+}
+
+impl RepoLock {
+    /// 仓库对应的锁文件：`<仓库>.lock`
+    ///
+    /// 锁文件放在仓库目录之外，删除仓库时不受影响；锁文件本身从不删除，
+    /// 避免两个进程分别锁住新旧两个文件。
+    fn file(repo: &Path) -> Result<File> {
+        let mut path = repo.as_os_str().to_owned();
+        path.push(".lock");
+        let path = PathBuf::from(path);
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)
+                .with_context(|| format!("无法创建缓存目录: {}", dir.display()))?;
+        }
+        File::options()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&path)
+            .with_context(|| format!("无法打开缓存锁文件: {}", path.display()))
+    }
+
+    /// 等待并获取共享锁
+    fn shared(repo: &Path) -> Result<Self> {
+        let file = Self::file(repo)?;
+        file.lock_shared()
+            .with_context(|| format!("无法锁定缓存仓库: {}", repo.display()))?;
+        Ok(Self { _file: file })
+    }
+
+    /// 等待并获取独占锁
+    fn exclusive(repo: &Path) -> Result<Self> {
+        let file = Self::file(repo)?;
+        file.lock()
+            .with_context(|| format!("无法锁定缓存仓库: {}", repo.display()))?;
+        Ok(Self { _file: file })
+    }
+
+    /// 尝试获取独占锁，仓库正在被使用时返回 None
+    fn try_exclusive(repo: &Path) -> Result<Option<Self>> {
+        let file = Self::file(repo)?;
+        match file.try_lock() {
+            Ok(()) => Ok(Some(Self { _file: file })),
+            Err(TryLockError::WouldBlock) => Ok(None),
+            Err(TryLockError::Error(e)) => {
+                Err(e).with_context(|| format!("无法锁定缓存仓库: {}", repo.display()))
+            }
+        }
+    }
+}
+
+/// 打开（必要时创建）url 对应的缓存仓库，并记录本次使用
+///
+/// 返回的锁在使用仓库期间需要一直持有。
+pub(crate) fn open(url: &str) -> Result<(PathBuf, RepoLock)> {
+    let path = repo_path(url)?;
+    let lock = open_at(&path, url)?;
+    Ok((path, lock))
+}
+
+/// 打开（必要时创建）path 处的缓存仓库
+fn open_at(path: &Path, url: &str) -> Result<RepoLock> {
+    let path = path.to_path_buf();
+    loop {
+        let lock = RepoLock::shared(&path)?;
+        if path.join(INFO_FILE).is_file() {
+            // 同一仓库的不同写法共用缓存，始终使用本次的地址访问远程
+            let current = git_output(&path, &["remote", "get-url", "origin"]);
+            if current.ok().as_deref() != Some(url) {
+                run_git_command(&path, &["remote", "set-url", "origin", url])?;
+            }
+            touch(&path, url)?;
+            return Ok(lock);
+        }
+        drop(lock);
+
+        // 尚未创建或上次创建到一半：在独占锁下（重新）创建，完成后重新以共享锁打开
+        let _lock = RepoLock::exclusive(&path)?;
+        if !path.join(INFO_FILE).is_file() {
+            create(&path, url)?;
+        }
+    }
+}
+
+/// 创建缓存仓库，最后写入来源记录，表示仓库可以使用；调用方需持有独占锁
+fn create(path: &Path, url: &str) -> Result<()> {
+    if path.exists() {
+        std::fs::remove_dir_all(path)
+            .with_context(|| format!("无法删除损坏的缓存仓库: {}", path.display()))?;
+    }
+    std::fs::create_dir_all(path)
+        .with_context(|| format!("无法创建缓存目录: {}", path.display()))?;
+    run_git_command(path, &["init", "--bare", "--quiet"])?;
+    run_git_command(path, &["remote", "add", "origin", url])?;
+    touch(path, url)
+}
+
+/// 查找 url 对应的缓存仓库并记录本次使用，不存在时返回 None；用于离线模式
+///
+/// 返回的锁在使用仓库期间需要一直持有。
+pub(crate) fn find(url: &str) -> Result<Option<(PathBuf, RepoLock)>> {
+    let path = repo_path(url)?;
+    if !path.join(INFO_FILE).is_file() {
+        return Ok(None);
+    }
+    let lock = RepoLock::shared(&path)?;
+    // 等待锁期间可能已被其他进程删除
+    if !path.join(INFO_FILE).is_file() {
+        return Ok(None);
+    }
+    touch(&path, url)?;
+    Ok(Some((path, lock)))
+}
+
+/// 写入来源记录，同时把修改时间更新为当前时间
+///
+/// 先写入临时文件再改名，其他进程不会读到写了一半的记录。
+fn touch(path: &Path, url: &str) -> Result<()> {
+    let info = CacheInfo {
+        url: normalize_url(url),
+    };
+    let content = serde_json::to_string_pretty(&info).context("无法生成缓存记录")?;
+    let temp = path.join(format!("{}.{}.tmp", INFO_FILE, std::process::id()));
+    std::fs::write(&temp, content + "\n")
+        .and_then(|()| std::fs::rename(&temp, path.join(INFO_FILE)))
+        .with_context(|| format!("无法写入缓存记录: {}", path.display()))
+}
+
+/// 列出缓存中的全部仓库，最近使用的在前
+pub fn list() -> Result<Vec<CacheEntry>> {
+    list_in(&repos_dir()?)
+}
+
+/// 列出 dir 中的全部缓存仓库，最近使用的在前
+fn list_in(dir: &Path) -> Result<Vec<CacheEntry>> {
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+
+    let mut entries = Vec::new();
+    for entry in std::fs::read_dir(dir)
+        .with_context(|| format!("无法读取缓存目录: {}", dir.display()))?
+    {
+        let path = entry?.path();
+        let info_path = path.join(INFO_FILE);
+        let Ok(content) = std::fs::read_to_string(&info_path) else {
+            continue;
+        };
+        let Ok(info) = serde_json::from_str::<CacheInfo>(&content) else {
+            continue;
+        };
+        let last_used = std::fs::metadata(&info_path)?.modified()?;
+        entries.push(CacheEntry {
+            url: info.url,
+            size: dir_size(&path)?,
+            path,
+            last_used,
+        });
+    }
+    entries.sort_by_key(|entry| std::cmp::Reverse(entry.last_used));
+    Ok(entries)
+}
+
+/// 按最近使用时间从旧到新删除仓库，直到缓存总大小不超过 max_size，返回被删除的仓库
+///
+/// 正在被使用（包括被本进程使用）的仓库与 keep 指定的仓库不会被删除。
+pub fn gc(max_size: u64, keep: Option<&Path>) -> Result<Vec<CacheEntry>> {
+    gc_in(&repos_dir()?, max_size, keep)
+}
+
+/// 在 dir 中执行 [`gc`]
+fn gc_in(dir: &Path, max_size: u64, keep: Option<&Path>) -> Result<Vec<CacheEntry>> {
+    let entries = list_in(dir)?;
+    let mut total: u64 = entries.iter().map(|entry| entry.size).sum();
+    let mut removed = Vec::new();
+    for entry in entries.into_iter().rev() {
+        if total <= max_size {
+            break;
+        }
+        if keep == Some(entry.path.as_path()) {
+            continue;
+        }
+        if remove_unused(&entry.path)? {
+            total -= entry.size;
+            removed.push(entry);
+        }
+    }
+    Ok(removed)
+}
+
+/// 删除缓存中无人使用的全部仓库，返回被删除的仓库
+pub fn clear() -> Result<Vec<CacheEntry>> {
+    let mut removed = Vec::new();
+    for entry in list()? {
+        if remove_unused(&entry.path)? {
+            removed.push(entry);
+        }
+    }
+    // 一并清理创建到一半、没有来源记录的仓库（正在创建的会被锁跳过）
+    let dir = repos_dir()?;
+    if dir.is_dir() {
+        for entry in std::fs::read_dir(&dir)
+            .with_context(|| format!("无法读取缓存目录: {}", dir.display()))?
+        {
+            let path = entry?.path();
+            if path.is_dir() && !path.join(INFO_FILE).is_file() {
+                remove_unused(&path)?;
+            }
+        }
+    }
+    Ok(removed)
+}
+
+/// 在独占锁下删除仓库；仓库正在被使用或已被删除时返回 false
+fn remove_unused(path: &Path) -> Result<bool> {
+    let Some(_lock) = RepoLock::try_exclusive(path)? else {
+        return Ok(false);
+    };
+    match std::fs::remove_dir_all(path) {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+        Err(e) => Err(e).with_context(|| format!("无法删除缓存仓库: {}", path.display())),
+    }
+}
+
+/// 解析大小，如 "500M"、"5GiB"、"1.5 GB"、"1048576"；单位均按 1024 进制
+pub fn parse_size(text: &str) -> Result<u64> {
+    let text = text.trim();
+    let split = text
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(text.len());
+    let (number, unit) = text.split_at(split);
+    let number: f64 = number
+        .parse()
+        .with_context(|| format!("无效的大小: {}", text))?;
+    let shift = match unit.trim().to_ascii_lowercase().as_str() {
+        "" | "b" => 0,
+        "k" | "kb" | "kib" => 10,
+        "m" | "mb" | "mib" => 20,
+        "g" | "gb" | "gib" => 30,
+        "t" | "tb" | "tib" => 40,
+        _ => bail!("无效的大小单位: {}（可用 K、M、G、T）", text),
+    };
+    Ok((number * (1u64 << shift) as f64) as u64)
+}
+
+/// 以合适的单位显示大小，如 "1.5 GiB"
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
+/// 目录占用的总字节数（不跟随符号链接）
+pub(crate) fn dir_size(path: &Path) -> Result<u64> {
+    let mut total = 0;
+    for entry in std::fs::read_dir(path)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        total += if metadata.is_dir() {
+            dir_size(&entry.path())?
+        } else {
+            metadata.len()
+        };
+    }
+    Ok(total)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn open_rebuilds_half_created_repo() {
+        let dir = TempDir::new().unwrap();
+        let repo = dir.path().join("repo.git");
+        std::fs::create_dir_all(&repo).unwrap();
+        std::fs::write(repo.join("leftover"), "x").unwrap();
+
+        let _lock = open_at(&repo, "https://example.com/o/r.git").unwrap();
+        assert!(!repo.join("leftover").exists());
+        assert!(repo.join(INFO_FILE).is_file());
+        assert!(repo.join("HEAD").is_file());
+        // 来源记录通过改名写入，不留下临时文件
+        let names: Vec<_> = std::fs::read_dir(&repo)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        assert!(!names.iter().any(|name| name.ends_with(".tmp")), "{:?}", names);
+    }
+
+    #[test]
+    fn gc_skips_repos_in_use() {
+        let dir = TempDir::new().unwrap();
+        let used = dir.path().join("used.git");
+        let idle = dir.path().join("idle.git");
+        let lock = open_at(&used, "https://example.com/o/used.git").unwrap();
+        drop(open_at(&idle, "https://example.com/o/idle.git").unwrap());
+
+        let removed = gc_in(dir.path(), 0, None).unwrap();
+        assert_eq!(removed.len(), 1);
+        assert_eq!(removed[0].path, idle);
+        assert!(used.join(INFO_FILE).is_file());
+
+        drop(lock);
+        let removed = gc_in(dir.path(), 0, None).unwrap();
+        assert_eq!(removed.len(), 1);
+        assert!(!used.exists());
+    }
+
+    #[test]
+    fn repo_being_created_is_not_removed() {
+        let dir = TempDir::new().unwrap();
+        let repo = dir.path().join("creating.git");
+        std::fs::create_dir_all(&repo).unwrap();
+
+        let lock = RepoLock::exclusive(&repo).unwrap();
+        assert!(!remove_unused(&repo).unwrap());
+        assert!(repo.is_dir());
+        drop(lock);
+        assert!(remove_unused(&repo).unwrap());
+        assert!(!repo.exists());
+    }
+}
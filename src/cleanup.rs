@@ -0,0 +1,92 @@
+//! 临时目录的登记与中断清理
+//!
+//! `tempfile::TempDir` 只在 drop 时删除目录，进程被 Ctrl-C 或 SIGTERM 终止时
+//! 不会执行。这里登记仍在使用的临时目录，由 [`install_signal_handler`]
+//! 安装的信号处理函数在退出前统一删除。替换目标路径的改名操作放在
+//! [`critical`] 中执行，信号处理函数会等它完成，不会留下替换到一半的目标路径。
+
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// 仍在使用的临时目录
+static ACTIVE: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+
+/// 改名替换目标路径期间持有，信号处理函数需要等待它释放
+static CRITICAL: Mutex<()> = Mutex::new(());
+
+/// 被信号中断时的退出码（128 + SIGINT）
+const INTERRUPTED_EXIT_CODE: i32 = 130;
+
+/// 会在进程被中断时一并清理的临时目录，drop 时删除
+#[derive(Debug)]
+pub(crate) struct TempDir {
+    inner: tempfile::TempDir,
+}
+
+impl TempDir {
+    /// 在系统临时目录中创建
+    pub(crate) fn new() -> Result<TempDir> {
+        let inner = tempfile::tempdir().context("无法创建临时目录")?;
+        Ok(Self::track(inner))
+    }
+
+    /// 在指定目录中创建以 prefix 开头的隐藏目录，用于与目标路径位于同一文件系统的暂存区
+    pub(crate) fn new_in(dir: &Path, prefix: &str) -> Result<TempDir> {
+        let inner = tempfile::Builder::new()
+            .prefix(prefix)
+            .tempdir_in(dir)
+            .with_context(|| format!("无法在 {} 中创建临时目录", dir.display()))?;
+        Ok(Self::track(inner))
+    }
+
+    fn track(inner: tempfile::TempDir) -> TempDir {
+        if let Ok(mut active) = ACTIVE.lock() {
+            active.push(inner.path().to_path_buf());
+        }
+        TempDir { inner }
+    }
+
+    pub(crate) fn path(&self) -> &Path {
+        self.inner.path()
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        if let Ok(mut active) = ACTIVE.lock() {
+            active.retain(|path| path != self.inner.path());
+        }
+    }
+}
+
+/// 安装 Ctrl-C / SIGTERM 处理函数：删除仍在使用的临时目录后以退出码 130 退出
+///
+/// 供命令行等拥有整个进程的调用方使用，每个进程只需调用一次。
+pub fn install_signal_handler() -> Result<()> {
+    ctrlc::set_handler(|| {
+        eprintln!("\n⛔ 已中断，正在清理临时文件...");
+        // 持有锁直到退出，正在进行的替换先完成，之后的替换不再开始
+        let _guard = CRITICAL.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        remove_active();
+        std::process::exit(INTERRUPTED_EXIT_CODE);
+    })
+    .context("无法安装信号处理函数")
+}
+
+/// 执行不应被信号处理打断的操作
+pub(crate) fn critical<T>(f: impl FnOnce() -> T) -> T {
+    let _guard = CRITICAL.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    f()
+}
+
+/// 删除全部登记的临时目录
+fn remove_active() {
+    let paths = match ACTIVE.lock() {
+        Ok(mut active) => std::mem::take(&mut *active),
+        Err(poisoned) => std::mem::take(&mut *poisoned.into_inner()),
+    };
+    for path in paths {
+        let _ = std::fs::remove_dir_all(path);
+    }
+}
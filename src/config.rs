@@ -0,0 +1,200 @@
+//! 用户级配置：`~/.config/git-get/config.toml` 与 GIT_GET_* 环境变量
+//!
+//! 用于声明 GitHub Enterprise、自建 GitLab/Gitea 等额外主机、`owner/repo` 简写默认
+//! 使用的主机，以及本地缓存的位置与大小上限。配置示例：
+//!
+//! ```toml
+//! default_host = "github.example.com"
+//! cache_max_size = "10GiB"
+//!
+//! [[hosts]]
+//! domain = "github.example.com"
+//! kind = "github"
+//! clone_url = "https://github.example.com/{repo}.git"
+//! api_url = "https://github.example.com/api/v3"
+//! token_env = "GHE_TOKEN"
+//! ```
+
+use crate::cache::parse_size;
+use crate::host::HostKind;
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+/// 用户配置
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// `owner/repo` 简写默认使用的主机域名（默认 github.com）
+    #[serde(default)]
+    pub default_host: Option<String>,
+    /// 额外声明的主机，优先于内置平台
+    #[serde(default)]
+    pub hosts: Vec<HostEntry>,
+    /// 本地缓存目录（默认为系统缓存目录下的 git-get）
+    #[serde(default)]
+    pub cache_dir: Option<PathBuf>,
+    /// 本地缓存的大小上限，如 "5GiB"、"500M"（默认 5 GiB）
+    #[serde(default)]
+    pub cache_max_size: Option<String>,
+}
+
+/// 配置文件中声明的一个主机
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct HostEntry {
+    /// 域名，可带端口
+    pub domain: String,
+    /// 平台类型，决定网页 URL 的解析方式
+    pub kind: HostKind,
+    /// 克隆地址模板，`{repo}` 会替换为仓库路径（如 owner/repo）
+    #[serde(default)]
+    pub clone_url: Option<String>,
+    /// REST API 地址（archive 后端使用）
+    #[serde(default)]
+    pub api_url: Option<String>,
+    /// 读取 token 的环境变量，替代平台默认的变量
+    #[serde(default)]
+    pub token_env: Option<String>,
+}
+
+static CONFIG: OnceLock<Config> = OnceLock::new();
+
+impl Config {
+    /// 读取配置文件并叠加环境变量
+    ///
+    /// - GIT_GET_CONFIG：配置文件路径（默认 `<配置目录>/git-get/config.toml`，不存在时忽略）
+    /// - GIT_GET_HOSTS：以逗号分隔的主机列表，每项为 `域名=平台`，可用 `;键=值` 追加
+    ///   clone_url、api_url、token_env，例如
+    ///   `github.example.com=github;api_url=https://github.example.com/api/v3`
+    /// - GIT_GET_DEFAULT_HOST：覆盖 default_host
+    /// - GIT_GET_CACHE_DIR、GIT_GET_CACHE_MAX_SIZE：覆盖 cache_dir、cache_max_size
+    pub fn load() -> Result<Config> {
+        let mut config = match config_path() {
+            Some(path) if path.exists() => {
+                let content = std::fs::read_to_string(&path)
+                    .with_context(|| format!("无法读取配置文件: {}", path.display()))?;
+                toml::from_str(&content)
+                    .with_context(|| format!("配置文件格式错误: {}", path.display()))?
+            }
+            Some(path) if std::env::var_os("GIT_GET_CONFIG").is_some() => {
+                bail!("配置文件不存在: {}", path.display())
+            }
+            _ => Config::default(),
+        };
+
+        if let Ok(hosts) = std::env::var("GIT_GET_HOSTS") {
+            config.hosts.extend(parse_env_hosts(&hosts)?);
+        }
+        if let Ok(default_host) = std::env::var("GIT_GET_DEFAULT_HOST") {
+            config.default_host = Some(default_host.trim().to_string()).filter(|s| !s.is_empty());
+        }
+        if let Some(dir) = std::env::var_os("GIT_GET_CACHE_DIR").filter(|dir| !dir.is_empty()) {
+            config.cache_dir = Some(PathBuf::from(dir));
+        }
+        if let Ok(size) = std::env::var("GIT_GET_CACHE_MAX_SIZE") {
+            config.cache_max_size = Some(size);
+        }
+        if let Some(size) = &config.cache_max_size {
+            parse_size(size).context("缓存大小上限（cache_max_size）无效")?;
+        }
+
+        Ok(config)
+    }
+
+    /// 加载配置并设为进程内的全局配置，配置有误时返回错误
+    ///
+    /// 未调用时，首次用到配置会自动加载，出错则退回默认配置。
+    pub fn init() -> Result<&'static Config> {
+        if let Some(config) = CONFIG.get() {
+            return Ok(config);
+        }
+        let config = Config::load()?;
+        Ok(CONFIG.get_or_init(|| config))
+    }
+}
+
+/// 当前生效的全局配置
+pub(crate) fn config() -> &'static Config {
+    CONFIG.get_or_init(|| Config::load().unwrap_or_default())
+}
+
+/// 解析 GIT_GET_HOSTS：`域名=平台[;clone_url=...][;api_url=...][;token_env=...]`，多项以逗号分隔
+fn parse_env_hosts(value: &str) -> Result<Vec<HostEntry>> {
+    let mut hosts = Vec::new();
+    for item in value.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+        let mut fields = item.split(';').map(str::trim);
+        let Some((domain, kind)) = fields.next().and_then(|field| field.split_once('=')) else {
+            bail!("GIT_GET_HOSTS 格式错误（应为 域名=平台）: {}", item);
+        };
+        let mut host = HostEntry {
+            domain: domain.trim().to_string(),
+            kind: kind.trim().parse()?,
+            clone_url: None,
+            api_url: None,
+            token_env: None,
+        };
+        for field in fields.filter(|field| !field.is_empty()) {
+            let Some((key, value)) = field.split_once('=') else {
+                bail!("GIT_GET_HOSTS 格式错误（应为 键=值）: {}", field);
+            };
+            let value = Some(value.trim().to_string());
+            match key.trim() {
+                "clone_url" => host.clone_url = value,
+                "api_url" => host.api_url = value,
+                "token_env" => host.token_env = value,
+                other => bail!(
+                    "GIT_GET_HOSTS 中未知的设置: {}（可选: clone_url、api_url、token_env）",
+                    other
+                ),
+            }
+        }
+        hosts.push(host);
+    }
+    Ok(hosts)
+}
+
+/// 配置文件路径
+fn config_path() -> Option<PathBuf> {
+    match std::env::var_os("GIT_GET_CONFIG") {
+        Some(path) => Some(PathBuf::from(path)),
+        None => dirs::config_dir().map(|dir| dir.join("git-get").join("config.toml")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn env_hosts_accept_optional_settings() {
+        let hosts = parse_env_hosts(
+            "github.example.com=github;api_url=https://github.example.com/api/v3;\
+             clone_url=ssh://git@github.example.com:2222/{repo}.git;token_env=GHE_TOKEN, \
+             git.corp.com=gitlab",
+        )
+        .unwrap();
+        assert_eq!(hosts.len(), 2);
+        assert_eq!(hosts[0].domain, "github.example.com");
+        assert_eq!(hosts[0].kind, HostKind::GitHub);
+        assert_eq!(hosts[0].api_url.as_deref(), Some("https://github.example.com/api/v3"));
+        assert_eq!(
+            hosts[0].clone_url.as_deref(),
+            Some("ssh://git@github.example.com:2222/{repo}.git")
+        );
+        assert_eq!(hosts[0].token_env.as_deref(), Some("GHE_TOKEN"));
+        assert_eq!(hosts[1].domain, "git.corp.com");
+        assert_eq!(hosts[1].kind, HostKind::GitLab);
+        assert_eq!(hosts[1].clone_url, None);
+    }
+
+    #[test]
+    fn env_hosts_reject_malformed_items() {
+        assert!(parse_env_hosts("github.example.com").is_err());
+        assert!(parse_env_hosts("github.example.com=svn").is_err());
+        assert!(parse_env_hosts("github.example.com=github;api").is_err());
+        assert!(parse_env_hosts("github.example.com=github;apiurl=x").is_err());
+        assert!(parse_env_hosts(" , ").unwrap().is_empty());
+    }
+}
@@ -0,0 +1,603 @@
+//! 目标路径检查、目录复制，以及目标路径非空时的处理策略
+
+use crate::cleanup::{critical, TempDir};
+use crate::filter::Filter;
+use crate::provenance::{hash_file, now_rfc3339};
+use anyhow::{bail, Context, Result};
+use std::fmt;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+
+/// 一次复制写入的文件统计
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CopyStats {
+    /// 写入的文件数
+    pub files: usize,
+    /// 写入的总字节数
+    pub bytes: u64,
+}
+
+/// 检查目标路径的安全性
+/// 只允许不存在的路径或空目录，防止覆盖已有文件造成数据损失
+pub fn check_dest_path_safety(dest_path: &Path) -> Result<()> {
+    let dest_str = dest_path.display();
+
+    // 如果路径不存在，直接返回（安全）
+    if !dest_path.exists() {
+        return Ok(());
+    }
+
+    // 如果存在但不是目录，报错
+    if !dest_path.is_dir() {
+        bail!(
+            "目标路径已存在且不是目录: {}",
+            dest_str
+        );
+    }
+
+    // 检查目录是否为空
+    let entries = std::fs::read_dir(dest_path)
+        .with_context(|| format!("无法读取目标目录: {}", dest_str))?;
+
+    // 如果目录包含任何内容，报错
+    if entries.count() > 0 {
+        bail!(
+            "目标目录已存在且不为空: {}\n提示: 为了安全起见，git-get 只能写入空目录或不存在的目录",
+            dest_str
+        );
+    }
+
+    // 目录存在但为空，安全
+    Ok(())
+}
+
+/// 检查单个文件目标路径的安全性：只允许写入尚不存在的路径
+pub fn check_file_dest_safety(dest_path: &Path) -> Result<()> {
+    if dest_path.exists() || dest_path.is_symlink() {
+        bail!(
+            "目标文件已存在: {}\n提示: 为了安全起见，git-get 不会覆盖已有文件",
+            dest_path.display()
+        );
+    }
+    Ok(())
+}
+
+/// 复制单个文件，按需创建父目录
+///
+/// 符号链接按链接本身复制（不计入统计），不会读取或写入链接指向的文件；
+/// dest 已是符号链接时先删除，避免写穿到链接指向的位置。
+pub(crate) fn copy_file(src: &Path, dest: &Path) -> Result<CopyStats> {
+    if let Some(parent) = dest.parent().filter(|p| !p.as_os_str().is_empty()) {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("无法创建目标目录: {}", parent.display()))?;
+    }
+    let is_link = src.is_symlink();
+    if dest.is_symlink() || (is_link && dest.exists()) {
+        std::fs::remove_file(dest)
+            .with_context(|| format!("无法替换文件: {}", dest.display()))?;
+    }
+    if is_link {
+        copy_all(src, dest)?;
+        return Ok(CopyStats::default());
+    }
+    let bytes = std::fs::copy(src, dest)
+        .with_context(|| format!("无法复制文件: {}", src.display()))?;
+    Ok(CopyStats { files: 1, bytes })
+}
+
+/// 递归复制目录，排除 .git 目录，只复制 filter 保留的文件
+pub(crate) fn copy_directory(src: &Path, dest: &Path, filter: &Filter) -> Result<CopyStats> {
+    // 创建目标目录
+    std::fs::create_dir_all(dest)
+        .with_context(|| format!("无法创建目标目录: {}", dest.display()))?;
+
+    let mut stats = CopyStats::default();
+    copy_dir_recursive(src, dest, "", filter, &mut stats)?;
+
+    Ok(stats)
+}
+
+/// 递归复制目录内容，跳过 .git 目录；prefix 为 src 相对复制根目录的路径
+fn copy_dir_recursive(
+    src: &Path,
+    dest: &Path,
+    prefix: &str,
+    filter: &Filter,
+    stats: &mut CopyStats,
+) -> Result<()> {
+    for entry in std::fs::read_dir(src)
+        .with_context(|| format!("无法读取目录: {}", src.display()))?
+    {
+        let entry = entry?;
+        let file_name = entry.file_name();
+        let file_name_str = file_name.to_string_lossy();
+
+        // 跳过 .git 目录
+        if file_name_str == ".git" {
+            continue;
+        }
+
+        let src_path = entry.path();
+        let dest_path = dest.join(&file_name);
+        let relative = format!("{}{}", prefix, file_name_str);
+
+        // 不跟随符号链接：指向目录的链接也按链接复制
+        if entry.file_type()?.is_dir() {
+            // 有过滤规则时目录随文件创建，不留下空目录
+            if filter.is_empty() {
+                std::fs::create_dir_all(&dest_path)?;
+            }
+            copy_dir_recursive(&src_path, &dest_path, &format!("{}/", relative), filter, stats)?;
+        } else if filter.matches(&relative) {
+            let copied = copy_file(&src_path, &dest_path)?;
+            stats.files += copied.files;
+            stats.bytes += copied.bytes;
+        }
+    }
+
+    Ok(())
+}
+
+/// 目标路径已有内容时的处理策略
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OnConflict {
+    /// 报错退出，不写入任何文件
+    #[default]
+    Error,
+    /// 写入全部文件，只替换同名文件，其他已有文件保持不动
+    Overwrite,
+    /// 写入已有目录，同名且内容不同的文件保留原样，上游版本另存为 `<文件>.rej` 并逐个报告
+    Merge,
+    /// 把已有的目标路径加上时间戳移到一旁，再重新写入
+    Backup,
+    /// 只写入尚不存在的文件，同名文件保持原样
+    SkipExisting,
+}
+
+impl FromStr for OnConflict {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "error" => Ok(Self::Error),
+            "overwrite" => Ok(Self::Overwrite),
+            "merge" => Ok(Self::Merge),
+            "backup" => Ok(Self::Backup),
+            "skip-existing" => Ok(Self::SkipExisting),
+            other => Err(format!(
+                "未知的冲突处理策略: {}（可选: error、overwrite、merge、backup、skip-existing）",
+                other
+            )),
+        }
+    }
+}
+
+impl fmt::Display for OnConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Error => "error",
+            Self::Overwrite => "overwrite",
+            Self::Merge => "merge",
+            Self::Backup => "backup",
+            Self::SkipExisting => "skip-existing",
+        })
+    }
+}
+
+/// 询问回调函数类型
+type PromptCallback = dyn Fn(&Path) -> Result<OnConflict> + Send + Sync;
+
+/// 目标路径已有内容时询问处理策略的回调，参数为目标路径
+///
+/// 命令行在终端中运行且未指定 `--on-conflict` 时用它询问用户；
+/// 设置后优先于 [`OnConflict`] 选项。
+#[derive(Clone)]
+pub struct ConflictPrompt(Arc<PromptCallback>);
+
+impl ConflictPrompt {
+    /// 使用自定义回调决定处理策略
+    pub fn new(callback: impl Fn(&Path) -> Result<OnConflict> + Send + Sync + 'static) -> Self {
+        Self(Arc::new(callback))
+    }
+
+    pub(crate) fn ask(&self, dest: &Path) -> Result<OnConflict> {
+        (self.0)(dest)
+    }
+}
+
+impl fmt::Debug for ConflictPrompt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ConflictPrompt(<callback>)")
+    }
+}
+
+/// 一个与已有文件同名的文件
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Collision {
+    /// 相对目标路径的文件路径（抓取单个文件时为文件名）
+    pub path: String,
+    /// 对该文件的处理
+    pub action: CollisionAction,
+    /// 上游版本另存的路径（相对目标路径），只有 [`CollisionAction::SavedAsRej`] 时有值
+    pub saved_as: Option<String>,
+}
+
+/// 同名文件的处理结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CollisionAction {
+    /// 替换为上游版本
+    Overwritten,
+    /// 保留已有文件
+    Skipped,
+    /// 保留已有文件，上游版本另存为 `<文件>.rej`，需要手动处理
+    SavedAsRej,
+}
+
+impl fmt::Display for CollisionAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Overwritten => "已覆盖",
+            Self::Skipped => "已跳过，保留原文件",
+            Self::SavedAsRej => "内容不同，保留原文件，上游版本另存为",
+        })
+    }
+}
+
+/// 目标路径是否已有内容（已存在的文件，或非空目录）
+pub(crate) fn is_occupied(dest: &Path) -> bool {
+    if dest.is_symlink() || dest.is_file() {
+        return true;
+    }
+    std::fs::read_dir(dest).is_ok_and(|mut entries| entries.next().is_some())
+}
+
+/// backup 策略下原有内容的新路径：`<名称>.backup-<时间戳>`
+pub(crate) fn backup_path(dest: &Path) -> Result<PathBuf> {
+    let stamp: String = now_rfc3339().chars().filter(|c| c.is_ascii_alphanumeric()).collect();
+    let name = dest
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "dest".to_string());
+    let backup = dest.with_file_name(format!("{}.backup-{}", name, stamp));
+    if backup.exists() {
+        bail!("备份路径已存在: {}", backup.display());
+    }
+    Ok(backup)
+}
+
+/// 在 path 旁另存文件时使用的后缀：`.<扩展名>`，已被占用时依次尝试 `.1.<扩展名>`、`.2.<扩展名>`……
+pub(crate) fn unused_suffix(path: &Path, extension: &str) -> String {
+    (0..)
+        .map(|n| match n {
+            0 => format!(".{}", extension),
+            n => format!(".{}.{}", n, extension),
+        })
+        .find(|suffix| {
+            let candidate = with_suffix(path, suffix);
+            !candidate.exists() && !candidate.is_symlink()
+        })
+        .expect("后缀序号不会用尽")
+}
+
+/// 在文件名后追加后缀
+pub(crate) fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+/// 与目标路径位于同一目录的暂存区
+///
+/// 内容先完整写入暂存区，再通过改名一次性替换目标路径：原有内容先移入暂存区
+/// （backup 策略下移到备份路径），替换失败时改回原处。暂存区在 drop 时删除，
+/// 进程被 Ctrl-C 中断时也会清理，因此中途失败不会留下写了一半的目标路径。
+pub(crate) struct Staging {
+    dir: TempDir,
+}
+
+impl Staging {
+    /// 在目标路径所在目录中创建暂存区（按需创建该目录）
+    pub(crate) fn new(dest: &Path) -> Result<Staging> {
+        if dest.file_name().is_none() {
+            bail!("无效的目标路径: {}", dest.display());
+        }
+        let parent = dest
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+            .unwrap_or(Path::new("."));
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("无法创建目标目录: {}", parent.display()))?;
+        Ok(Staging {
+            dir: TempDir::new_in(parent, ".git-get-")?,
+        })
+    }
+
+    /// 最终要替换到目标路径的内容
+    pub(crate) fn content(&self) -> PathBuf {
+        self.dir.path().join("content")
+    }
+
+    /// 暂存区中的其他工作路径
+    pub(crate) fn join(&self, name: &str) -> PathBuf {
+        self.dir.path().join(name)
+    }
+
+    /// 把目标路径的现有内容复制到暂存区，作为逐个文件修改的起点
+    pub(crate) fn seed(&self, dest: &Path) -> Result<()> {
+        copy_all(dest, &self.content())
+    }
+
+    /// 用暂存区的内容替换目标路径；backup 为 Some 时原有内容移到该路径保留
+    pub(crate) fn commit(self, dest: &Path, backup: Option<&Path>) -> Result<()> {
+        let content = self.content();
+        if !content.exists() {
+            std::fs::create_dir_all(&content).context("无法创建暂存目录")?;
+        }
+        critical(|| {
+            let previous = if dest.exists() || dest.is_symlink() {
+                let aside = backup
+                    .map(Path::to_path_buf)
+                    .unwrap_or_else(|| self.join("previous"));
+                std::fs::rename(dest, &aside)
+                    .with_context(|| format!("无法移走目标路径的原有内容: {}", dest.display()))?;
+                Some(aside)
+            } else {
+                None
+            };
+            if let Err(e) = std::fs::rename(&content, dest) {
+                // 恢复原有内容
+                if let Some(aside) = &previous {
+                    let _ = std::fs::rename(aside, dest);
+                }
+                return Err(e).with_context(|| format!("无法写入目标路径: {}", dest.display()));
+            }
+            Ok(())
+        })
+    }
+}
+
+/// 原样复制文件或目录（包括隐藏文件与 .git，符号链接保持为链接）
+pub(crate) fn copy_all(src: &Path, dest: &Path) -> Result<()> {
+    let metadata = std::fs::symlink_metadata(src)
+        .with_context(|| format!("无法读取: {}", src.display()))?;
+    if metadata.is_symlink() {
+        let link = std::fs::read_link(src)?;
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(&link, dest)
+            .with_context(|| format!("无法创建符号链接: {}", dest.display()))?;
+        #[cfg(not(unix))]
+        std::fs::copy(src, dest).with_context(|| format!("无法复制文件: {}", src.display()))?;
+    } else if metadata.is_dir() {
+        std::fs::create_dir_all(dest)
+            .with_context(|| format!("无法创建目录: {}", dest.display()))?;
+        for entry in std::fs::read_dir(src)
+            .with_context(|| format!("无法读取目录: {}", src.display()))?
+        {
+            let entry = entry?;
+            copy_all(&entry.path(), &dest.join(entry.file_name()))?;
+        }
+    } else {
+        std::fs::copy(src, dest).with_context(|| format!("无法复制文件: {}", src.display()))?;
+    }
+    Ok(())
+}
+
+/// 把抓取好的内容按策略逐个写入已有内容的 target（通常是暂存区中目标路径的副本）
+///
+/// staged 是文件时写入单个文件（冲突报告使用 file_name 作为名称），是目录时
+/// 逐个写入其中的文件；内容相同的同名文件不算冲突。写入前先检查全部路径，
+/// 文件与已有目录同名（或反过来）时不写入任何文件直接报错。
+pub(crate) fn install(
+    staged: &Path,
+    target: &Path,
+    file_name: &str,
+    policy: OnConflict,
+) -> Result<(CopyStats, Vec<Collision>)> {
+    let is_dir = staged.is_dir();
+    let mut files = Vec::new();
+    if is_dir {
+        list_files(staged, "", &mut files)?;
+    } else {
+        files.push(String::new());
+    }
+    let entries: Vec<(PathBuf, PathBuf, String)> = files
+        .into_iter()
+        .map(|relative| {
+            if relative.is_empty() {
+                (staged.to_path_buf(), target.to_path_buf(), file_name.to_string())
+            } else {
+                (staged.join(&relative), target.join(&relative), relative)
+            }
+        })
+        .collect();
+
+    for (_, path, name) in &entries {
+        if path.is_dir() && !path.is_symlink() {
+            bail!("目标中已有同名目录，无法写入文件: {}", path.display());
+        }
+        // 上级目录在目标中是文件（或链接）时同样无法写入
+        let blocked = Path::new(name)
+            .ancestors()
+            .skip(1)
+            .filter(|parent| is_dir && !parent.as_os_str().is_empty())
+            .map(|parent| target.join(parent))
+            .find(|parent| parent.is_symlink() || parent.is_file());
+        if let Some(parent) = blocked {
+            bail!("目标中已有同名文件，无法创建目录: {}", parent.display());
+        }
+    }
+
+    let mut stats = CopyStats::default();
+    let mut collided = Vec::new();
+    for (source, path, name) in entries {
+        if !path.exists() && !path.is_symlink() {
+            stats.files += 1;
+            stats.bytes += copy_file(&source, &path)?.bytes;
+        } else if hash_file(&path)? != hash_file(&source)? {
+            collided.push((source, path, name));
+        }
+    }
+
+    // 新文件写完后再处理同名文件，另存的 .rej 不会与上游的文件重名
+    let mut collisions = Vec::new();
+    for (source, path, name) in collided {
+        let mut saved_as = None;
+        let action = match policy {
+            OnConflict::SkipExisting => CollisionAction::Skipped,
+            OnConflict::Merge => {
+                let suffix = unused_suffix(&path, "rej");
+                copy_file(&source, &with_suffix(&path, &suffix))?;
+                saved_as = Some(format!("{}{}", name, suffix));
+                CollisionAction::SavedAsRej
+            }
+            _ => {
+                std::fs::remove_file(&path)
+                    .with_context(|| format!("无法替换文件: {}", path.display()))?;
+                stats.files += 1;
+                stats.bytes += copy_file(&source, &path)?.bytes;
+                CollisionAction::Overwritten
+            }
+        };
+        collisions.push(Collision {
+            path: name,
+            action,
+            saved_as,
+        });
+    }
+    Ok((stats, collisions))
+}
+
+/// 列出目录下全部文件的相对路径（以 / 分隔）
+fn list_files(dir: &Path, prefix: &str, files: &mut Vec<String>) -> Result<()> {
+    let mut entries = std::fs::read_dir(dir)
+        .with_context(|| format!("无法读取目录: {}", dir.display()))?
+        .collect::<std::io::Result<Vec<_>>>()?;
+    entries.sort_by_key(|entry| entry.file_name());
+    for entry in entries {
+        let relative = format!("{}{}", prefix, entry.file_name().to_string_lossy());
+        if entry.file_type()?.is_dir() {
+            list_files(&entry.path(), &format!("{}/", relative), files)?;
+        } else {
+            files.push(relative);
+        }
+    }
+    Ok(())
+}
+
+/// 能否按文本合并：不是符号链接，也不是二进制文件
+pub(crate) fn is_text(path: &Path) -> Result<bool> {
+    Ok(!path.is_symlink() && !is_binary(path)?)
+}
+
+/// 按 git 的规则判断二进制文件：前 8000 字节中含有 NUL
+fn is_binary(path: &Path) -> Result<bool> {
+    let mut buffer = Vec::with_capacity(8000);
+    std::fs::File::open(path)
+        .and_then(|file| file.take(8000).read_to_end(&mut buffer))
+        .with_context(|| format!("无法读取文件: {}", path.display()))?;
+    Ok(buffer.contains(&0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn merge_keeps_local_files_and_saves_upstream_as_rej() {
+        let dir = TempDir::new().unwrap();
+        let staged = dir.path().join("staged");
+        let target = dir.path().join("target");
+        for (root, changed) in [(&staged, "upstream\n"), (&target, "local\n")] {
+            std::fs::create_dir_all(root.join("sub")).unwrap();
+            std::fs::write(root.join("same.txt"), "same\n").unwrap();
+            std::fs::write(root.join("sub/changed.txt"), changed).unwrap();
+        }
+        std::fs::write(staged.join("new.txt"), "new\n").unwrap();
+        std::fs::write(target.join("local-only.txt"), "mine\n").unwrap();
+        // 已有的 .rej 不会被覆盖
+        std::fs::write(target.join("sub/changed.txt.rej"), "old reject\n").unwrap();
+
+        let (stats, collisions) = install(&staged, &target, "target", OnConflict::Merge).unwrap();
+        assert_eq!(
+            collisions,
+            vec![Collision {
+                path: "sub/changed.txt".to_string(),
+                action: CollisionAction::SavedAsRej,
+                saved_as: Some("sub/changed.txt.1.rej".to_string()),
+            }]
+        );
+        assert_eq!(stats.files, 1);
+        let read = |path: &str| std::fs::read_to_string(target.join(path)).unwrap();
+        assert_eq!(read("sub/changed.txt"), "local\n");
+        assert_eq!(read("sub/changed.txt.1.rej"), "upstream\n");
+        assert_eq!(read("sub/changed.txt.rej"), "old reject\n");
+        assert_eq!(read("new.txt"), "new\n");
+        assert_eq!(read("local-only.txt"), "mine\n");
+    }
+
+    #[test]
+    fn path_kind_mismatches_are_rejected_before_writing() {
+        let dir = TempDir::new().unwrap();
+        let staged = dir.path().join("staged");
+        std::fs::create_dir_all(staged.join("sub")).unwrap();
+        std::fs::write(staged.join("a.txt"), "a\n").unwrap();
+        std::fs::write(staged.join("sub/b.txt"), "b\n").unwrap();
+        std::fs::write(staged.join("z.txt"), "z\n").unwrap();
+
+        // 上游的文件在目标中是目录，或上游的目录在目标中是文件
+        for conflict in ["z.txt/", "sub"] {
+            let target = dir.path().join("target");
+            std::fs::create_dir_all(&target).unwrap();
+            match conflict.strip_suffix('/') {
+                Some(name) => std::fs::create_dir(target.join(name)).unwrap(),
+                None => std::fs::write(target.join(conflict), "file\n").unwrap(),
+            }
+
+            assert!(install(&staged, &target, "target", OnConflict::Overwrite).is_err());
+            assert!(!target.join("a.txt").exists(), "{}", conflict);
+            std::fs::remove_dir_all(&target).unwrap();
+        }
+    }
+
+    #[test]
+    fn skip_existing_leaves_local_files_untouched() {
+        let dir = TempDir::new().unwrap();
+        let staged = dir.path().join("file.txt");
+        let target = dir.path().join("dest.txt");
+        std::fs::write(&staged, "upstream\n").unwrap();
+        std::fs::write(&target, "local\n").unwrap();
+
+        let (stats, collisions) =
+            install(&staged, &target, "dest.txt", OnConflict::SkipExisting).unwrap();
+        assert_eq!(stats.files, 0);
+        assert_eq!(collisions[0].action, CollisionAction::Skipped);
+        assert_eq!(std::fs::read_to_string(&target).unwrap(), "local\n");
+    }
+
+    #[test]
+    fn failed_commit_restores_the_original_destination() {
+        let dir = TempDir::new().unwrap();
+        let dest = dir.path().join("dest");
+        let aside = dir.path().join("aside");
+        std::fs::create_dir_all(&dest).unwrap();
+        std::fs::write(dest.join("a.txt"), "original\n").unwrap();
+
+        // 暂存区建在 dest 内部：dest 移走后暂存内容随之消失，最后一步改名必然失败
+        let staging = Staging::new(&dest.join("inner")).unwrap();
+        std::fs::create_dir_all(staging.content()).unwrap();
+        std::fs::write(staging.content().join("a.txt"), "new\n").unwrap();
+
+        let err = staging.commit(&dest, Some(&aside)).unwrap_err();
+        assert!(format!("{:#}", err).contains("无法写入目标路径"));
+        assert!(!aside.exists());
+        assert_eq!(std::fs::read_to_string(dest.join("a.txt")).unwrap(), "original\n");
+        let leftovers: Vec<_> = std::fs::read_dir(&dest)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        assert_eq!(leftovers, vec!["a.txt"]);
+    }
+}
@@ -0,0 +1,949 @@
+//! 对外的抓取入口：描述一次抓取的 [`FetchRequest`] 与结果 [`FetchOutcome`]
+
+use crate::backend::{
+    open_backend, Backend, BackendKind, EntryKind, Placement, Remote, ResolvedRef,
+};
+use crate::copy::{
+    backup_path, check_dest_path_safety, check_file_dest_safety, install, is_occupied, Collision,
+    ConflictPrompt, CopyStats, OnConflict, Staging,
+};
+use crate::filter::Filter;
+use crate::git::resolve_token;
+use crate::progress::Progress;
+use crate::provenance::{collect_files, now_rfc3339, Provenance, PROVENANCE_FILE};
+use crate::refs::{find_ref, short_name, split_ref_and_path};
+use crate::repo::{build_repo_url, parse_web_url};
+use crate::suggest::{closest, hint};
+use crate::update::{update, UpdateOutcome};
+use anyhow::{anyhow, bail, Context, Result};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// 抓取选项
+#[derive(Clone, Default)]
+pub struct FetchOptions {
+    /// 访问 token；为 None 时回退到仓库所在平台对应的环境变量
+    pub token: Option<String>,
+    /// SSH 私钥路径，用于 ssh:// 与 scp 风格的仓库地址
+    pub ssh_key: Option<PathBuf>,
+    /// 抓取后端，默认自动选择
+    pub backend: BackendKind,
+    /// 进度回调，默认不输出
+    pub progress: Progress,
+    /// 抓取目录时是否在目标目录中写入来源记录（.git-get.json）
+    pub metadata: bool,
+    /// 目标路径已有内容时的处理策略，默认报错
+    pub on_conflict: OnConflict,
+    /// 目标路径已有内容时询问处理策略，设置后优先于 on_conflict
+    pub conflict_prompt: Option<ConflictPrompt>,
+    /// git 后端是否把对象拉取到本地缓存仓库（见 [`crate::cache`]），默认不使用
+    pub cache: bool,
+    /// 离线模式：只从本地缓存解析引用与读取对象，不访问远程（仅 git 后端）
+    pub offline: bool,
+}
+
+impl fmt::Debug for FetchOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // token 不能出现在调试输出中
+        f.debug_struct("FetchOptions")
+            .field("token", &self.token.as_ref().map(|_| "***"))
+            .field("ssh_key", &self.ssh_key)
+            .field("backend", &self.backend)
+            .field("progress", &self.progress)
+            .field("metadata", &self.metadata)
+            .field("on_conflict", &self.on_conflict)
+            .field("conflict_prompt", &self.conflict_prompt)
+            .field("cache", &self.cache)
+            .field("offline", &self.offline)
+            .finish()
+    }
+}
+
+/// 一次抓取的描述：从哪个仓库、哪个引用抓取哪个子目录，写到哪里
+///
+/// ```no_run
+/// use git_get::FetchRequest;
+///
+/// let outcome = FetchRequest::new("modelcontextprotocol/rust-sdk")
+///     .reference("main")
+///     .path("examples/servers")
+///     .dest("./example-servers")
+///     .fetch()?;
+/// println!("{} @ {}", outcome.dest.display(), outcome.commit);
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct FetchRequest {
+    repo: String,
+    reference: Option<String>,
+    path: Option<String>,
+    tree_spec: Option<String>,
+    dest: Option<PathBuf>,
+    include: Vec<String>,
+    exclude: Vec<String>,
+    expected_tree: Option<String>,
+    options: FetchOptions,
+}
+
+impl FetchRequest {
+    /// 以仓库标识（owner/repo 或完整 Git URL）创建请求
+    pub fn new(repo: impl Into<String>) -> Self {
+        Self {
+            repo: repo.into(),
+            reference: None,
+            path: None,
+            tree_spec: None,
+            dest: None,
+            include: Vec::new(),
+            exclude: Vec::new(),
+            expected_tree: None,
+            options: FetchOptions::default(),
+        }
+    }
+
+    /// 以仓库参数或网页 URL 创建请求
+    ///
+    /// 网页 URL 中的 "<ref>/<path>" 记为 tree_spec，抓取时再根据远程引用拆分。
+    pub fn from_source(source: &str) -> Result<Self> {
+        Ok(match parse_web_url(source)? {
+            Some(parsed) => {
+                let request = Self::new(parsed.clone_url);
+                match parsed.tree_spec {
+                    Some(spec) => request.tree_spec(spec),
+                    None => request,
+                }
+            }
+            None => Self::new(source),
+        })
+    }
+
+    /// 指定引用（分支、标签、提交 SHA 或完整引用名），未指定时使用远程仓库的默认分支
+    pub fn reference(mut self, reference: impl Into<String>) -> Self {
+        self.reference = Some(reference.into());
+        self
+    }
+
+    /// 指定仓库内的路径（子目录或单个文件），未指定时抓取整个仓库
+    pub fn path(mut self, path: impl Into<String>) -> Self {
+        let path = path.into().trim_matches('/').to_string();
+        self.path = (!path.is_empty()).then_some(path);
+        self
+    }
+
+    /// 指定 URL 中 /tree/ 之后尚未拆分的 "<ref>/<path>"
+    ///
+    /// 分支名可能包含斜杠，拆分位置在抓取时根据远程公布的分支与标签决定；
+    /// 显式指定的 [`reference`](Self::reference) 与 [`path`](Self::path) 优先。
+    pub fn tree_spec(mut self, spec: impl Into<String>) -> Self {
+        let spec = spec.into().trim_matches('/').to_string();
+        self.tree_spec = (!spec.is_empty()).then_some(spec);
+        self
+    }
+
+    /// 指定本地目标路径，未指定时使用 path 的最后一段或仓库名
+    ///
+    /// 抓取单个文件时，若 dest 是已存在的目录则把文件写入该目录。
+    pub fn dest(mut self, dest: impl Into<PathBuf>) -> Self {
+        self.dest = Some(dest.into());
+        self
+    }
+
+    /// 只抓取匹配该 glob 的文件（可多次调用，匹配任一即可），仅对目录生效
+    ///
+    /// 模式相对抓取的目录，语法见 [`Filter`]。
+    pub fn include(mut self, pattern: impl Into<String>) -> Self {
+        self.include.push(pattern.into());
+        self
+    }
+
+    /// 跳过匹配该 glob 的文件或目录（可多次调用），仅对目录生效
+    pub fn exclude(mut self, pattern: impl Into<String>) -> Self {
+        self.exclude.push(pattern.into());
+        self
+    }
+
+    /// 要求路径对应的对象 ID 与锁文件记录一致，不一致时在写入前报错
+    pub(crate) fn expect_tree(mut self, tree: impl Into<String>) -> Self {
+        self.expected_tree = Some(tree.into());
+        self
+    }
+
+    /// 替换全部抓取选项
+    pub fn options(mut self, options: FetchOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// 指定访问 token
+    pub fn token(mut self, token: impl Into<String>) -> Self {
+        self.options.token = Some(token.into());
+        self
+    }
+
+    /// 指定访问 SSH 地址时使用的私钥
+    pub fn ssh_key(mut self, key: impl Into<PathBuf>) -> Self {
+        self.options.ssh_key = Some(key.into());
+        self
+    }
+
+    /// 指定抓取后端
+    pub fn backend(mut self, backend: BackendKind) -> Self {
+        self.options.backend = backend;
+        self
+    }
+
+    /// 指定进度回调
+    pub fn progress(mut self, progress: Progress) -> Self {
+        self.options.progress = progress;
+        self
+    }
+
+    /// 抓取目录时是否写入来源记录（.git-get.json），默认不写入
+    pub fn metadata(mut self, enabled: bool) -> Self {
+        self.options.metadata = enabled;
+        self
+    }
+
+    /// 指定目标路径已有内容时的处理策略，默认报错
+    pub fn on_conflict(mut self, policy: OnConflict) -> Self {
+        self.options.on_conflict = policy;
+        self
+    }
+
+    /// 指定目标路径已有内容时询问处理策略的回调
+    pub fn conflict_prompt(mut self, prompt: ConflictPrompt) -> Self {
+        self.options.conflict_prompt = Some(prompt);
+        self
+    }
+
+    /// git 后端是否使用本地缓存仓库，默认不使用
+    pub fn cache(mut self, enabled: bool) -> Self {
+        self.options.cache = enabled;
+        self
+    }
+
+    /// 离线模式：只使用本地缓存，缓存中没有所需的仓库、引用或提交时报错，默认关闭
+    pub fn offline(mut self, enabled: bool) -> Self {
+        self.options.offline = enabled;
+        self
+    }
+
+    /// 仓库标识
+    pub fn repo(&self) -> &str {
+        &self.repo
+    }
+
+    /// 请求的引用
+    pub fn requested_reference(&self) -> Option<&str> {
+        self.reference.as_deref()
+    }
+
+    /// 仓库内的路径
+    pub fn subpath(&self) -> Option<&str> {
+        self.path.as_deref()
+    }
+
+    /// include 过滤模式
+    pub fn includes(&self) -> &[String] {
+        &self.include
+    }
+
+    /// exclude 过滤模式
+    pub fn excludes(&self) -> &[String] {
+        &self.exclude
+    }
+
+    /// 指定的目标路径
+    pub fn destination(&self) -> Option<&Path> {
+        self.dest.as_deref()
+    }
+
+    /// 抓取选项
+    pub(crate) fn fetch_options(&self) -> &FetchOptions {
+        &self.options
+    }
+
+    /// 完整的 Git 仓库 URL
+    pub fn repo_url(&self) -> Result<String> {
+        build_repo_url(&self.repo)
+    }
+
+    /// 未指定目标路径时的默认值：path 的最后一段或仓库名
+    fn default_dest(&self, path: Option<&str>) -> PathBuf {
+        let url;
+        let name = if let Some(path) = path {
+            path.split('/').next_back()
+        } else {
+            // 按解析后的地址取仓库名，使 "." 这类本地路径也能得到目录名
+            url = self.repo_url().unwrap_or_else(|_| self.repo.clone());
+            url.trim_end_matches('/')
+                .rsplit(['/', ':'])
+                .next()
+                .map(|name| name.trim_end_matches(".git"))
+        };
+        PathBuf::from(name.unwrap_or("download"))
+    }
+
+    /// 抓取单个文件时的目标路径
+    ///
+    /// 未指定 dest 时使用文件名；dest 是已存在的目录（或以 / 结尾）时写入其中；
+    /// 否则 dest 即文件路径。
+    fn file_dest(&self, path: &str) -> PathBuf {
+        let file_name = path.split('/').next_back().unwrap_or(path);
+        match &self.dest {
+            None => PathBuf::from(file_name),
+            Some(dest) if dest.is_dir() || dest.to_string_lossy().ends_with('/') => {
+                dest.join(file_name)
+            }
+            Some(dest) => dest.clone(),
+        }
+    }
+
+    /// 执行抓取，等价于 [`fetch`]
+    pub fn fetch(&self) -> Result<FetchOutcome> {
+        fetch(self)
+    }
+
+    /// 共用一次拉取抓取多个路径，等价于 [`fetch_many`]
+    pub fn fetch_many(&self, targets: &[FetchTarget]) -> Result<Vec<FetchOutcome>> {
+        fetch_many(self, targets)
+    }
+
+    /// 原地更新已抓取的目标目录，等价于 [`update`](crate::update::update)
+    pub fn update(&self) -> Result<UpdateOutcome> {
+        update(self)
+    }
+
+    /// 只解析引用与路径而不落盘，等价于 [`resolve`]
+    pub fn resolve(&self) -> Result<Resolution> {
+        resolve(self)
+    }
+
+    /// 解析引用并把路径的内容下载到本地缓存，等价于 [`prefetch`]
+    pub fn prefetch(&self) -> Result<Resolution> {
+        prefetch(self)
+    }
+}
+
+/// 一次成功抓取的结果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FetchOutcome {
+    /// 实际使用的 Git 仓库 URL
+    pub repo_url: String,
+    /// 使用的后端名称
+    pub backend: String,
+    /// 实际拉取的引用
+    pub reference: String,
+    /// 实际抓取的仓库内路径，None 表示整个仓库
+    pub path: Option<String>,
+    /// 抓取的是目录（Tree）还是单个文件（Blob）
+    pub kind: EntryKind,
+    /// 检出的提交 SHA
+    pub commit: String,
+    /// 抓取路径对应的 git 对象 ID（目录为 tree 哈希，文件为 blob 哈希）
+    pub tree: String,
+    /// 写入的目标路径
+    pub dest: PathBuf,
+    /// 写入的文件数
+    pub files_written: usize,
+    /// 写入的总字节数
+    pub bytes_written: u64,
+    /// 被 include/exclude 规则过滤掉的文件数
+    pub files_filtered: usize,
+    /// 来源记录的路径，未写入时为 None
+    pub metadata: Option<PathBuf>,
+    /// 与目标路径中已有文件同名的文件及其处理结果
+    pub collisions: Vec<Collision>,
+    /// 使用 backup 策略时，原有内容移到的路径
+    pub backup: Option<PathBuf>,
+}
+
+/// 只解析不落盘的结果，用于判断远程是否有更新
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Resolution {
+    /// 实际使用的 Git 仓库 URL
+    pub repo_url: String,
+    /// 实际解析的引用
+    pub reference: String,
+    /// 仓库内路径，None 表示整个仓库
+    pub path: Option<String>,
+    /// 引用指向的提交 SHA
+    pub commit: String,
+    /// 路径对应的 git 对象 ID
+    pub tree: String,
+}
+
+/// 已打开后端、拆分好引用与路径的抓取会话
+struct Session {
+    repo_url: String,
+    backend: Box<dyn Backend>,
+    reference: Option<String>,
+    resolved: ResolvedRef,
+    path: Option<String>,
+}
+
+impl Session {
+    /// 打开后端、拆分 URL 中的 <ref>/<path> 并解析引用
+    fn open(request: &FetchRequest) -> Result<Session> {
+        let progress = &request.options.progress;
+        let repo_url = request.repo_url()?;
+
+        // 决定鉴权 token（显式传入优先，其次环境变量）
+        let token = resolve_token(request.options.token.as_deref(), &repo_url);
+        if token.is_some() {
+            progress.emit("🔑 已启用 token 鉴权");
+        }
+
+        if let Some(key) = request.options.ssh_key.as_deref().filter(|key| !key.is_file()) {
+            bail!("SSH 私钥文件不存在: {}", key.display());
+        }
+
+        let remote = Remote {
+            url: repo_url.clone(),
+            token,
+            ssh_key: request.options.ssh_key.clone(),
+        };
+        let mut backend = open_backend(
+            request.options.backend,
+            remote,
+            progress.clone(),
+            request.options.cache,
+            request.options.offline,
+        )?;
+        progress.emit(format!("⚙️  后端: {}", backend.name()));
+
+        // 拆分 URL 中的 <ref>/<path>
+        let (reference, path) = split_tree_spec(request, backend.as_mut())?;
+        progress.emit(format!("📁 路径: {}", path.as_deref().unwrap_or("<整个仓库>")));
+
+        let resolved = match backend.resolve_ref(reference.as_deref()) {
+            Ok(resolved) => resolved,
+            Err(e) => return Err(with_ref_hint(e, reference.as_deref(), backend.as_mut())),
+        };
+        if reference.is_none() {
+            progress.emit(format!("🌿 使用远程默认分支: {}", resolved.name));
+        }
+
+        Ok(Session {
+            repo_url,
+            backend,
+            reference,
+            resolved,
+            path,
+        })
+    }
+
+    /// 路径对应的 git 对象 ID，路径不存在时报错
+    fn tree(&mut self, path: Option<&str>) -> Result<String> {
+        match self.backend.path_id(&self.resolved, path)? {
+            Some(id) => Ok(id),
+            None => Err(self.missing_path(path.unwrap_or(""))),
+        }
+    }
+
+    /// 路径不存在的错误，附上仓库中名称相近的路径
+    ///
+    /// 此时只拉取了目录结构（或只调用了 API），列出整个仓库树不会下载文件内容。
+    fn missing_path(&mut self, path: &str) -> anyhow::Error {
+        let entries = self
+            .backend
+            .list_tree(&self.resolved, None)
+            .unwrap_or_default();
+        let suggestions = closest(path, entries.iter().map(|entry| entry.path.as_str()));
+        anyhow!("远程仓库中未找到指定路径: {}{}", path, hint(&suggestions))
+    }
+}
+
+/// 引用解析失败且远程确实没有该引用时，在错误后附上名称相近的分支与标签
+fn with_ref_hint(
+    error: anyhow::Error,
+    reference: Option<&str>,
+    backend: &mut dyn Backend,
+) -> anyhow::Error {
+    let Some(name) = reference else {
+        return error;
+    };
+    // 无法列出引用（如网络错误）或引用其实存在时，保留原始错误
+    let refs = match backend.list_refs() {
+        Ok(refs) if find_ref(name, &refs).is_none() => refs,
+        _ => return error,
+    };
+    let names = refs
+        .iter()
+        .filter(|r| r.name.starts_with("refs/heads/") || r.name.starts_with("refs/tags/"))
+        .map(|r| short_name(&r.name));
+    let suggestions = closest(short_name(name), names);
+    if suggestions.is_empty() {
+        return error;
+    }
+    anyhow!("{:#}{}", error, hint(&suggestions))
+}
+
+/// 只解析引用、路径与对应的对象 ID，不写入任何文件
+pub fn resolve(request: &FetchRequest) -> Result<Resolution> {
+    let mut session = Session::open(request)?;
+    let path = session.path.clone();
+    let tree = session.tree(path.as_deref())?;
+    Ok(Resolution {
+        repo_url: session.repo_url,
+        reference: session.resolved.name,
+        path: session.path,
+        commit: session.resolved.commit,
+        tree,
+    })
+}
+
+/// 解析引用并把路径的内容下载到本地缓存，不写入任何文件，供之后离线抓取使用
+pub fn prefetch(request: &FetchRequest) -> Result<Resolution> {
+    let mut session = Session::open(request)?;
+    let path = session.path.clone();
+    let tree = session.tree(path.as_deref())?;
+    session
+        .backend
+        .prefetch(&session.resolved, &[path.as_deref()])?;
+    Ok(Resolution {
+        repo_url: session.repo_url,
+        reference: session.resolved.name,
+        path: session.path,
+        commit: session.resolved.commit,
+        tree,
+    })
+}
+
+/// 执行一次抓取：由后端解析引用，再把子目录或文件落盘到目标路径
+///
+/// 目标路径已有内容（非空目录或已存在的文件）时按 [`OnConflict`] 策略处理，
+/// 默认报错；后端使用的临时文件在返回前自动清理。
+pub fn fetch(request: &FetchRequest) -> Result<FetchOutcome> {
+    let filter = Filter::new(&request.include, &request.exclude)?;
+    check_dest_file(request, request.dest.as_deref())?;
+
+    let mut session = Session::open(request)?;
+    let path = session.path.clone();
+    let pending = prepare(&mut session, request, path, &filter)?;
+    let staged = stage(&pending)?;
+    let stats = session.backend.materialize(
+        &session.resolved,
+        pending.path.as_deref(),
+        &pending.filter,
+        &staged.upstream,
+    )?;
+    finish(&session, request, pending, staged, stats)
+}
+
+/// 抓取多个路径时的一个目标
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FetchTarget {
+    /// 仓库内的子目录或文件
+    pub path: String,
+    /// 本地目标路径，None 时与单路径抓取的默认值相同
+    pub dest: Option<PathBuf>,
+}
+
+/// 从同一仓库、同一提交抓取多个路径，所有路径共用一次拉取与检出
+///
+/// request 中的仓库、引用、过滤规则与选项对全部目标生效，其自身的 path 与 dest
+/// 被各目标取代。结果与 targets 一一对应；任一目标在写入前出错时不会写入任何目标。
+pub fn fetch_many(request: &FetchRequest, targets: &[FetchTarget]) -> Result<Vec<FetchOutcome>> {
+    let Some(first) = targets.first() else {
+        bail!("没有要抓取的路径");
+    };
+    let filter = Filter::new(&request.include, &request.exclude)?;
+    let requests: Vec<FetchRequest> = targets
+        .iter()
+        .map(|target| {
+            let request = request.clone().path(&target.path);
+            match &target.dest {
+                Some(dest) => request.dest(dest),
+                None => FetchRequest { dest: None, ..request },
+            }
+        })
+        .collect();
+    for request in &requests {
+        check_dest_file(request, request.dest.as_deref())?;
+    }
+
+    let mut session = Session::open(&request.clone().path(&first.path))?;
+    let mut pending: Vec<Pending> = Vec::with_capacity(targets.len());
+    for (index, request) in requests.iter().enumerate() {
+        if index > 0 {
+            request.options.progress.emit(format!("📁 路径: {}", target_path(request)));
+        }
+        let next = prepare(&mut session, request, request.path.clone(), &filter)?;
+        if let Some(other) = pending
+            .iter()
+            .find(|other| other.dest.starts_with(&next.dest) || next.dest.starts_with(&other.dest))
+        {
+            bail!(
+                "多个路径的目标路径重叠: {} 与 {}\n提示: 用 --path-dest 为每个路径指定不同的目标路径",
+                other.dest.display(),
+                next.dest.display()
+            );
+        }
+        pending.push(next);
+    }
+
+    let staged = pending.iter().map(stage).collect::<Result<Vec<_>>>()?;
+    let placements: Vec<Placement<'_>> = pending
+        .iter()
+        .zip(&staged)
+        .map(|(pending, staged)| Placement {
+            path: pending.path.as_deref(),
+            filter: &pending.filter,
+            target: &staged.upstream,
+        })
+        .collect();
+    let stats = session.backend.materialize_many(&session.resolved, &placements)?;
+
+    pending
+        .into_iter()
+        .zip(staged)
+        .zip(stats)
+        .zip(&requests)
+        .map(|(((pending, staged), stats), request)| {
+            finish(&session, request, pending, staged, stats)
+        })
+        .collect()
+}
+
+/// 仓库内路径的显示名称
+fn target_path(request: &FetchRequest) -> &str {
+    request.path.as_deref().unwrap_or("<整个仓库>")
+}
+
+/// 目标路径已是普通文件且不允许处理时，无论抓取目录还是文件都无法写入，尽早报错
+fn check_dest_file(request: &FetchRequest, dest: Option<&Path>) -> Result<()> {
+    let strict = request.options.on_conflict == OnConflict::Error
+        && request.options.conflict_prompt.is_none();
+    match dest.filter(|dest| strict && dest.is_file()) {
+        Some(dest) => check_file_dest_safety(dest),
+        None => Ok(()),
+    }
+}
+
+/// 已确定目标路径与处理策略、等待后端写入的一个路径
+struct Pending {
+    path: Option<String>,
+    kind: EntryKind,
+    tree: String,
+    dest: PathBuf,
+    policy: OnConflict,
+    backup: Option<PathBuf>,
+    filter: Filter,
+    files_filtered: usize,
+}
+
+/// 一个路径的暂存区
+struct Staged {
+    staging: Staging,
+    /// 后端写入上游内容的位置
+    upstream: PathBuf,
+}
+
+/// 确定路径类型与目标路径并检查安全性，此时尚未创建或写入任何内容
+fn prepare(
+    session: &mut Session,
+    request: &FetchRequest,
+    path: Option<String>,
+    filter: &Filter,
+) -> Result<Pending> {
+    let progress = &request.options.progress;
+    let resolved = session.resolved.clone();
+
+    // 判断路径是目录还是文件，再决定目标路径并检查安全性
+    let kind = match path.as_deref() {
+        Some(path) => match session.backend.path_kind(&resolved, path)? {
+            Some(kind) => kind,
+            None => return Err(session.missing_path(path)),
+        },
+        None => EntryKind::Tree,
+    };
+    let tree = session.tree(path.as_deref())?;
+    if let Some(expected) = request.expected_tree.as_deref().filter(|expected| *expected != tree) {
+        bail!(
+            "远程内容与锁文件不一致: {} 的对象 ID 为 {}，锁文件记录为 {}",
+            path.as_deref().unwrap_or("<整个仓库>"),
+            tree,
+            expected
+        );
+    }
+    let dest = match (kind, path.as_deref()) {
+        (EntryKind::Blob, Some(path)) => request.file_dest(path),
+        _ => request
+            .dest
+            .clone()
+            .unwrap_or_else(|| request.default_dest(path.as_deref())),
+    };
+    progress.emit(format!("📍 目标路径: {}", dest.display()));
+
+    // 过滤规则只作用于目录；按仓库树统计被过滤的文件，与后端是否提前跳过无关
+    let filter = match kind {
+        EntryKind::Tree => filter.clone(),
+        _ => Filter::default(),
+    };
+    let files_filtered = if filter.is_empty() {
+        0
+    } else {
+        let root = path.as_deref().map(|path| format!("{}/", path));
+        let files: Vec<String> = session
+            .backend
+            .list_tree(&resolved, path.as_deref())?
+            .into_iter()
+            .filter(|entry| entry.kind == EntryKind::Blob)
+            .filter_map(|entry| match &root {
+                Some(root) => entry.path.strip_prefix(root.as_str()).map(str::to_string),
+                None => Some(entry.path),
+            })
+            .collect();
+        let filtered = files.iter().filter(|relative| !filter.matches(relative)).count();
+        if filtered > 0 && filtered == files.len() {
+            bail!("过滤规则排除了全部 {} 个文件，请检查 --include/--exclude", filtered);
+        }
+        progress.emit(format!("🔎 按过滤规则跳过 {} 个文件（共 {} 个）", filtered, files.len()));
+        filtered
+    };
+
+    // 目标路径已有内容时决定处理策略
+    let policy = if is_occupied(&dest) {
+        match &request.options.conflict_prompt {
+            Some(prompt) => prompt.ask(&dest)?,
+            None => request.options.on_conflict,
+        }
+    } else {
+        OnConflict::Error
+    };
+    let mut backup = None;
+    match policy {
+        OnConflict::Error if kind == EntryKind::Blob => check_file_dest_safety(&dest)?,
+        OnConflict::Error => check_dest_path_safety(&dest)?,
+        OnConflict::Backup => backup = Some(backup_path(&dest)?),
+        _ if kind == EntryKind::Tree && !dest.is_dir() => {
+            bail!("目标路径已存在且不是目录: {}", dest.display())
+        }
+        policy => progress.emit(format!("⚠️  目标路径已有内容，按 {} 策略写入", policy)),
+    }
+
+    Ok(Pending {
+        path,
+        kind,
+        tree,
+        dest,
+        policy,
+        backup,
+        filter,
+        files_filtered,
+    })
+}
+
+/// 创建暂存区：内容先完整写入与目标路径同目录的暂存区，最后再改名替换目标路径，
+/// 中途失败或被中断时目标路径保持原样
+fn stage(pending: &Pending) -> Result<Staged> {
+    let staging = Staging::new(&pending.dest)?;
+    // 需要写入已有内容时，上游内容单独抓取，再逐个文件按策略写入目标路径的副本
+    let upstream = if is_staged(pending.policy) {
+        staging.join("upstream")
+    } else {
+        staging.content()
+    };
+    Ok(Staged { staging, upstream })
+}
+
+/// 是否需要把上游内容逐个文件写入已有内容
+fn is_staged(policy: OnConflict) -> bool {
+    matches!(
+        policy,
+        OnConflict::Overwrite | OnConflict::Merge | OnConflict::SkipExisting
+    )
+}
+
+/// 后端写入上游内容之后：按策略合入已有内容、写入来源记录，再替换目标路径
+fn finish(
+    session: &Session,
+    request: &FetchRequest,
+    pending: Pending,
+    staged: Staged,
+    mut stats: CopyStats,
+) -> Result<FetchOutcome> {
+    let progress = &request.options.progress;
+    let Pending {
+        path,
+        kind,
+        tree,
+        dest,
+        policy,
+        backup,
+        files_filtered,
+        ..
+    } = pending;
+    let Staged { staging, upstream } = staged;
+    let content = staging.content();
+
+    // 来源记录只描述上游内容，在写入已有目录之前统计
+    let records = if request.options.metadata && kind == EntryKind::Tree {
+        if upstream.join(PROVENANCE_FILE).exists() {
+            progress.emit(format!("⚠️  抓取的内容中已有 {}，未写入来源记录", PROVENANCE_FILE));
+            None
+        } else {
+            Some(collect_files(&upstream)?)
+        }
+    } else {
+        None
+    };
+
+    let mut collisions = Vec::new();
+    if is_staged(policy) {
+        staging.seed(&dest)?;
+        let file_name = dest.file_name().unwrap_or_default().to_string_lossy();
+        (stats, collisions) = install(&upstream, &content, &file_name, policy)?;
+    }
+
+    let resolved = &session.resolved;
+    let metadata = match records {
+        Some(files) => {
+            let provenance = Provenance {
+                tool_version: env!("CARGO_PKG_VERSION").to_string(),
+                source: session.repo_url.clone(),
+                reference: session.reference.clone(),
+                resolved: resolved.name.clone(),
+                commit: resolved.commit.clone(),
+                path: path.clone(),
+                tree: tree.clone(),
+                include: request.include.clone(),
+                exclude: request.exclude.clone(),
+                fetched_at: now_rfc3339(),
+                files,
+            };
+            provenance.save(&content)?;
+            Some(dest.join(PROVENANCE_FILE))
+        }
+        None => None,
+    };
+
+    staging.commit(&dest, backup.as_deref())?;
+    if let Some(backup) = &backup {
+        progress.emit(format!("📦 已将原有内容移到: {}", backup.display()));
+    }
+
+    Ok(FetchOutcome {
+        repo_url: session.repo_url.clone(),
+        backend: session.backend.name().to_string(),
+        reference: resolved.name.clone(),
+        path,
+        kind,
+        commit: resolved.commit.clone(),
+        tree,
+        dest,
+        files_written: stats.files,
+        bytes_written: stats.bytes,
+        files_filtered,
+        metadata,
+        collisions,
+        backup,
+    })
+}
+
+/// 确定最终使用的引用与仓库内路径
+///
+/// 请求中带有未拆分的 tree_spec 时，按远程公布的分支与标签拆分（最长匹配优先）；
+/// 显式指定的引用与路径始终优先。
+fn split_tree_spec(
+    request: &FetchRequest,
+    backend: &mut dyn Backend,
+) -> Result<(Option<String>, Option<String>)> {
+    let explicit = (request.reference.clone(), request.path.clone());
+    let Some(spec) = request.tree_spec.as_deref() else {
+        return Ok(explicit);
+    };
+
+    if let Some(reference) = request.reference.as_deref() {
+        // 显式引用正好是 URL 的前缀时无需查询远程
+        if let Some(rest) = spec
+            .strip_prefix(reference)
+            .filter(|rest| rest.is_empty() || rest.starts_with('/'))
+        {
+            let rest = rest.trim_matches('/');
+            let path = (!rest.is_empty()).then(|| rest.to_string());
+            return Ok((explicit.0, explicit.1.or(path)));
+        }
+        if explicit.1.is_some() {
+            return Ok(explicit);
+        }
+    }
+
+    let refs = backend.list_refs()?;
+    let (reference, path) = split_ref_and_path(spec, &refs).with_context(|| {
+        format!("无法在远程仓库的分支与标签中找到与 '{}' 匹配的引用", spec)
+    })?;
+    Ok((explicit.0.or(Some(reference)), explicit.1.or(path)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::FixtureRepo;
+    use tempfile::TempDir;
+
+    fn target(path: &str, dest: &Path) -> FetchTarget {
+        FetchTarget {
+            path: path.to_string(),
+            dest: Some(dest.to_path_buf()),
+        }
+    }
+
+    #[test]
+    fn fetch_many_writes_each_path_from_one_commit() {
+        let repo = FixtureRepo::new();
+        repo.write("examples/a.txt", "a\n")
+            .write("examples/sub/b.txt", "b\n")
+            .write("docs/guide.md", "guide\n")
+            .write("other.txt", "other\n");
+        let commit = repo.commit("init");
+        let out = TempDir::new().unwrap();
+        let request = FetchRequest::new(repo.url()).metadata(true);
+
+        let outcomes = fetch_many(
+            &request,
+            &[
+                target("examples", &out.path().join("vendor/examples")),
+                target("docs/guide.md", &out.path().join("GUIDE.md")),
+            ],
+        )
+        .unwrap();
+        assert_eq!(outcomes.len(), 2);
+        assert!(outcomes.iter().all(|outcome| outcome.commit == commit));
+        assert_eq!(outcomes[0].kind, EntryKind::Tree);
+        assert_eq!(outcomes[0].files_written, 2);
+        assert!(outcomes[0].metadata.is_some());
+        assert_eq!(outcomes[1].kind, EntryKind::Blob);
+        assert_eq!(outcomes[1].path.as_deref(), Some("docs/guide.md"));
+
+        let read = |path: &str| std::fs::read_to_string(out.path().join(path)).unwrap();
+        assert_eq!(read("vendor/examples/a.txt"), "a\n");
+        assert_eq!(read("vendor/examples/sub/b.txt"), "b\n");
+        assert_eq!(read("GUIDE.md"), "guide\n");
+        assert!(!out.path().join("other.txt").exists());
+    }
+
+    #[test]
+    fn fetch_many_rejects_overlapping_or_invalid_targets_before_writing() {
+        let repo = FixtureRepo::new();
+        repo.write("examples/a.txt", "a\n").write("docs/guide.md", "guide\n");
+        repo.commit("init");
+        let out = TempDir::new().unwrap();
+        let request = FetchRequest::new(repo.url());
+        let dest = out.path().join("vendor");
+
+        for targets in [
+            // 相同的目标路径
+            vec![target("examples", &dest), target("docs", &dest)],
+            // 一个目标路径位于另一个之中
+            vec![target("examples", &dest), target("docs", &dest.join("docs"))],
+            // 后面的路径不存在
+            vec![target("examples", &dest), target("missing", &out.path().join("missing"))],
+        ] {
+            assert!(fetch_many(&request, &targets).is_err(), "{:?}", targets);
+            assert_eq!(std::fs::read_dir(out.path()).unwrap().count(), 0, "{:?}", targets);
+        }
+        assert!(fetch_many(&request, &[]).is_err());
+    }
+}
@@ -0,0 +1,284 @@
+//! 抓取内容的 include/exclude 过滤
+//!
+//! 模式是相对抓取目录的 glob：`*` 不跨越 `/`，`**` 匹配任意层目录。不含 `/` 的模式
+//! （如 `*.md`、`target`）在任意层级匹配文件名或目录名；含 `/` 的模式从抓取目录根部
+//! 匹配，开头的 `/` 可省略。模式匹配到目录时作用于其中的全部文件；以 `/` 结尾的模式
+//! （如 `target/`）只匹配目录，不匹配同名文件。
+//!
+//! 指定了 include 时只保留匹配任一 include 的文件，再去掉匹配任一 exclude 的文件。
+
+use anyhow::{bail, Context, Result};
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+
+/// 编译后的过滤规则
+#[derive(Debug, Clone, Default)]
+pub struct Filter {
+    include: Option<PatternSet>,
+    exclude: Option<PatternSet>,
+    /// 规范化后的 include 模式（相对抓取目录根部，只匹配目录的模式保留结尾的 /）
+    include_patterns: Vec<String>,
+    /// 规范化后的 exclude 模式
+    exclude_patterns: Vec<String>,
+}
+
+impl Filter {
+    /// 编译 include 与 exclude 模式，模式无效时报错
+    pub fn new(include: &[String], exclude: &[String]) -> Result<Filter> {
+        let include_patterns = normalize_all(include)?;
+        let exclude_patterns = normalize_all(exclude)?;
+        Ok(Filter {
+            include: build_set(&include_patterns)?,
+            exclude: build_set(&exclude_patterns)?,
+            include_patterns,
+            exclude_patterns,
+        })
+    }
+
+    /// 是否没有任何规则（保留全部文件）
+    pub fn is_empty(&self) -> bool {
+        self.include.is_none() && self.exclude.is_none()
+    }
+
+    /// 相对抓取目录的文件路径（以 / 分隔）是否保留
+    pub fn matches(&self, path: &str) -> bool {
+        let hit = |set: &PatternSet| set.matches(path);
+        self.include.as_ref().is_none_or(hit) && !self.exclude.as_ref().is_some_and(hit)
+    }
+
+    /// 转换为 sparse-checkout（非 cone 模式）的模式行，root 为仓库内的抓取路径
+    ///
+    /// 结果只用于减少检出的文件，可能比过滤规则宽松，复制时仍会逐个文件过滤；
+    /// 含有 sparse-checkout 无法表达的语法（如 `{a,b}`）时返回 None。
+    pub(crate) fn sparse_patterns(&self, root: Option<&str>) -> Option<Vec<String>> {
+        let all = self.include_patterns.iter().chain(&self.exclude_patterns);
+        if all.clone().any(|pattern| pattern.contains(['{', '}', '\\'])) {
+            return None;
+        }
+
+        let prefix = match root {
+            Some(root) => format!("{}/", anchored_pattern(root)?),
+            None => "/".to_string(),
+        };
+        let mut lines = Vec::new();
+        if self.include_patterns.is_empty() {
+            lines.push(if root.is_some() { prefix.clone() } else { "/*".to_string() });
+        }
+        for pattern in &self.include_patterns {
+            lines.push(format!("{}{}", prefix, pattern));
+        }
+        for pattern in &self.exclude_patterns {
+            lines.push(format!("!{}{}", prefix, pattern));
+        }
+        Some(lines)
+    }
+}
+
+/// 把仓库内的路径转换为只匹配该路径本身的 sparse-checkout 模式行
+///
+/// 开头加 / 锚定到仓库根目录，否则 `examples` 会匹配任意层级的同名目录；路径中的通配符
+/// （`*`、`?`、`[`、`\`）与行尾空格用反斜杠转义。锚定后 `!`、`#`、`-` 不会出现在行首，无需转义。
+/// 路径含换行符时无法表示，返回 None。
+pub(crate) fn anchored_pattern(path: &str) -> Option<String> {
+    if path.contains(['\n', '\r']) {
+        return None;
+    }
+    let trailing = path.len() - path.trim_end_matches(' ').len();
+    let mut pattern = String::from("/");
+    for c in path[..path.len() - trailing].chars() {
+        if matches!(c, '*' | '?' | '[' | '\\') {
+            pattern.push('\\');
+        }
+        pattern.push(c);
+    }
+    pattern.push_str(&"\\ ".repeat(trailing));
+    Some(pattern)
+}
+
+/// 一组编译后的模式
+#[derive(Debug, Clone)]
+struct PatternSet {
+    set: GlobSet,
+    /// 与 set 中的模式一一对应：是否只匹配目录
+    dir_only: Vec<bool>,
+}
+
+impl PatternSet {
+    /// 文件路径本身或其某级父目录是否匹配；只匹配目录的模式不与路径本身比较
+    fn matches(&self, path: &str) -> bool {
+        ancestors(path).any(|(candidate, is_dir)| {
+            self.set
+                .matches(candidate)
+                .into_iter()
+                .any(|index| is_dir || !self.dir_only[index])
+        })
+    }
+}
+
+/// 规范化模式：去掉开头的 /，结尾的 / 合并为一个，不含 / 的模式改为在任意层级匹配
+fn normalize_all(patterns: &[String]) -> Result<Vec<String>> {
+    patterns
+        .iter()
+        .map(|pattern| {
+            let pattern = pattern.trim();
+            let trimmed = pattern.trim_end_matches('/');
+            if trimmed.trim_start_matches('/').is_empty() {
+                bail!("过滤模式不能为空: '{}'", pattern);
+            }
+            let normalized = match trimmed.strip_prefix('/') {
+                Some(anchored) => anchored.to_string(),
+                None if trimmed.contains('/') => trimmed.to_string(),
+                None => format!("**/{}", trimmed),
+            };
+            let suffix = if trimmed.len() < pattern.len() { "/" } else { "" };
+            Ok(normalized + suffix)
+        })
+        .collect()
+}
+
+fn build_set(patterns: &[String]) -> Result<Option<PatternSet>> {
+    if patterns.is_empty() {
+        return Ok(None);
+    }
+    let mut builder = GlobSetBuilder::new();
+    let mut dir_only = Vec::new();
+    for pattern in patterns {
+        let glob = pattern.strip_suffix('/').unwrap_or(pattern);
+        let glob = GlobBuilder::new(glob)
+            .literal_separator(true)
+            .build()
+            .with_context(|| format!("无效的过滤模式: {}", pattern.trim_start_matches("**/")))?;
+        builder.add(glob);
+        dir_only.push(pattern.ends_with('/'));
+    }
+    Ok(Some(PatternSet {
+        set: builder.build().context("无法编译过滤模式")?,
+        dir_only,
+    }))
+}
+
+/// 路径的各级父目录及路径本身，并标明是否为目录，如 a/b/c 依次为 a、a/b、a/b/c（文件）
+fn ancestors(path: &str) -> impl Iterator<Item = (&str, bool)> {
+    path.match_indices('/')
+        .map(move |(index, _)| (&path[..index], true))
+        .chain(std::iter::once((path, false)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter(include: &[&str], exclude: &[&str]) -> Filter {
+        let owned = |patterns: &[&str]| patterns.iter().map(|p| p.to_string()).collect::<Vec<_>>();
+        Filter::new(&owned(include), &owned(exclude)).unwrap()
+    }
+
+    #[test]
+    fn anchored_pattern_anchors_to_root() {
+        // 不加 / 时 `examples` 也会匹配 foo/examples
+        assert_eq!(anchored_pattern("examples").unwrap(), "/examples");
+        assert_eq!(anchored_pattern("foo/examples").unwrap(), "/foo/examples");
+    }
+
+    #[test]
+    fn anchored_pattern_escapes_wildcards() {
+        assert_eq!(anchored_pattern("a*b").unwrap(), "/a\\*b");
+        assert_eq!(anchored_pattern("a?b").unwrap(), "/a\\?b");
+        assert_eq!(anchored_pattern("[ab]/c").unwrap(), "/\\[ab]/c");
+        assert_eq!(anchored_pattern("a\\b").unwrap(), "/a\\\\b");
+    }
+
+    #[test]
+    fn anchored_pattern_keeps_trailing_spaces() {
+        assert_eq!(anchored_pattern("dir  ").unwrap(), "/dir\\ \\ ");
+        assert_eq!(anchored_pattern(" dir").unwrap(), "/ dir");
+        assert_eq!(anchored_pattern("a b/c").unwrap(), "/a b/c");
+    }
+
+    #[test]
+    fn anchored_pattern_leading_specials_need_no_escape() {
+        assert_eq!(anchored_pattern("!important").unwrap(), "/!important");
+        assert_eq!(anchored_pattern("#notes").unwrap(), "/#notes");
+        assert_eq!(anchored_pattern("-rf").unwrap(), "/-rf");
+    }
+
+    #[test]
+    fn anchored_pattern_rejects_newlines() {
+        assert_eq!(anchored_pattern("a\nb"), None);
+        assert_eq!(anchored_pattern("a\rb"), None);
+    }
+
+    #[test]
+    fn sparse_patterns_are_anchored_under_root() {
+        let f = filter(&["*.md", "/docs/"], &["draft"]);
+        assert_eq!(
+            f.sparse_patterns(Some("examples")).unwrap(),
+            ["/examples/**/*.md", "/examples/docs/", "!/examples/**/draft"]
+        );
+        assert_eq!(
+            f.sparse_patterns(None).unwrap(),
+            ["/**/*.md", "/docs/", "!/**/draft"]
+        );
+    }
+
+    #[test]
+    fn sparse_patterns_escape_root_but_not_filters() {
+        let f = filter(&["*.txt"], &[]);
+        assert_eq!(
+            f.sparse_patterns(Some("a*[b] ")).unwrap(),
+            ["/a\\*\\[b]\\ /**/*.txt"]
+        );
+        assert_eq!(f.sparse_patterns(Some("bad\nroot")), None);
+    }
+
+    #[test]
+    fn sparse_patterns_without_include_keep_whole_root() {
+        let f = filter(&[], &["*.log"]);
+        assert_eq!(f.sparse_patterns(Some("src")).unwrap(), ["/src/", "!/src/**/*.log"]);
+        assert_eq!(f.sparse_patterns(None).unwrap(), ["/*", "!/**/*.log"]);
+    }
+
+    #[test]
+    fn sparse_patterns_give_up_on_unsupported_syntax() {
+        assert_eq!(filter(&["*.{md,txt}"], &[]).sparse_patterns(None), None);
+        assert_eq!(filter(&[], &["a\\*"]).sparse_patterns(None), None);
+    }
+
+    #[test]
+    fn matches_applies_include_then_exclude() {
+        let f = filter(&["*.md"], &["draft"]);
+        assert!(f.matches("README.md"));
+        assert!(f.matches("docs/guide.md"));
+        assert!(!f.matches("src/main.rs"));
+        assert!(!f.matches("draft/notes.md"));
+        assert!(!f.matches("docs/draft/notes.md"));
+    }
+
+    #[test]
+    fn dir_only_patterns_skip_files_with_the_same_name() {
+        let f = filter(&[], &["target/"]);
+        assert!(!f.matches("target/debug/app"));
+        assert!(!f.matches("crates/a/target/out.txt"));
+        assert!(f.matches("target"));
+        assert!(f.matches("crates/target"));
+
+        let f = filter(&["/docs//"], &[]);
+        assert!(f.matches("docs/guide.md"));
+        assert!(!f.matches("docs"));
+        assert!(!f.matches("sub/docs/guide.md"));
+    }
+
+    #[test]
+    fn sparse_patterns_keep_dir_only_suffix() {
+        let f = filter(&["/docs/"], &["target/"]);
+        assert_eq!(
+            f.sparse_patterns(Some("examples")).unwrap(),
+            ["/examples/docs/", "!/examples/**/target/"]
+        );
+    }
+
+    #[test]
+    fn empty_patterns_are_rejected() {
+        assert!(Filter::new(&["/".to_string()], &[]).is_err());
+        assert!(Filter::new(&[], &[" ".to_string()]).is_err());
+    }
+}
@@ -0,0 +1,369 @@
+//! 系统 git 命令的调用封装与 token 处理
+//!
+//! 所有命令都在调用方提供的临时目录中执行，不会触碰当前工作目录的 .git。
+
+use crate::backend::Remote;
+use crate::host::{Host, HostKind};
+use crate::refs::{parse_ls_remote, RemoteRef};
+use anyhow::{bail, Context, Result};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use std::path::Path;
+use std::process::Command;
+
+/// 查找访问 token：显式传入优先，其次是仓库所在平台（或配置中 token_env）对应的环境变量
+///
+/// 无法识别平台的仓库不读取环境变量，避免把 token 发给无关主机。
+pub fn resolve_token(explicit: Option<&str>, repo_url: &str) -> Option<String> {
+    let host = Host::from_url(repo_url);
+    let env_vars = host.as_ref().map(Host::token_env_vars).unwrap_or_default();
+    explicit
+        .map(str::to_string)
+        .or_else(|| env_vars.iter().find_map(|var| std::env::var(var).ok()))
+        .map(|token| token.trim().to_string())
+        .filter(|token| !token.is_empty())
+}
+
+/// 列出远程 origin 公布的所有引用
+pub(crate) fn ls_remote(working_dir: &Path, remote: &Remote) -> Result<Vec<RemoteRef>> {
+    let output = run_git_fetch(working_dir, &["ls-remote", "origin"], remote)
+        .context("无法访问远程仓库，请检查仓库地址是否正确")?;
+    Ok(parse_ls_remote(&output))
+}
+
+/// 通过 `git ls-remote --symref` 询问远程 HEAD 指向的分支
+///
+/// 需要在已添加 origin 的仓库中执行。
+pub(crate) fn remote_default_branch(working_dir: &Path, remote: &Remote) -> Result<String> {
+    let output = run_git_fetch(working_dir, &["ls-remote", "--symref", "origin", "HEAD"], remote)
+        .context("无法访问远程仓库，请检查仓库地址是否正确")?;
+    parse_symref_head(&output)
+        .context("远程仓库未公布默认分支，请使用 --ref 明确指定分支")
+}
+
+/// 从 `ls-remote --symref` 的输出中提取 HEAD 指向的分支名
+/// 输出格式: "ref: refs/heads/main\tHEAD"
+fn parse_symref_head(output: &str) -> Option<String> {
+    output.lines().find_map(|line| {
+        let (target, name) = line.strip_prefix("ref: ")?.split_once('\t')?;
+        (name == "HEAD").then(|| {
+            target
+                .strip_prefix("refs/heads/")
+                .unwrap_or(target)
+                .to_string()
+        })
+    })
+}
+
+/// 执行 git 命令并检查结果
+pub(crate) fn run_git_command(working_dir: &Path, args: &[&str]) -> Result<()> {
+    run_git(working_dir, args, None, None).map(|_| ())
+}
+
+/// 执行 git 命令并返回去除首尾空白的标准输出
+pub(crate) fn git_output(working_dir: &Path, args: &[&str]) -> Result<String> {
+    run_git(working_dir, args, None, None)
+}
+
+/// 执行访问远程的 git 命令，按需附带 token 鉴权与 SSH 私钥，返回标准输出
+///
+/// token 通过 GIT_CONFIG_* 环境变量注入为仅对本次命令生效的
+/// `http.extraHeader`，既不会写入 `.git/config`，也不会出现在进程参数中；
+/// SSH 私钥通过 GIT_SSH_COMMAND 指定。
+pub(crate) fn run_git_fetch(working_dir: &Path, args: &[&str], remote: &Remote) -> Result<String> {
+    run_git(working_dir, args, Some(remote), None)
+}
+
+/// 同 [`run_git_fetch`]，额外返回标准错误中的提示信息（已去除 token），
+/// 用于发现命令成功但 git 给出警告的情况
+pub(crate) fn run_git_fetch_with_stderr(
+    working_dir: &Path,
+    args: &[&str],
+    remote: &Remote,
+) -> Result<(String, String)> {
+    run_git_full(working_dir, args, Some(remote), None)
+}
+
+/// 在工作区中执行检出等命令，部分克隆时 git 会按需从 remote 拉取缺少的文件内容
+///
+/// objects 指定使用的对象目录（GIT_OBJECT_DIRECTORY），按需拉取的对象也写入其中；
+/// remote 为 None 时不附带鉴权信息。返回标准输出。
+pub(crate) fn run_git_checkout(
+    working_dir: &Path,
+    args: &[&str],
+    remote: Option<&Remote>,
+    objects: Option<&Path>,
+) -> Result<String> {
+    run_git(working_dir, args, remote, objects)
+}
+
+fn run_git(
+    working_dir: &Path,
+    args: &[&str],
+    remote: Option<&Remote>,
+    objects: Option<&Path>,
+) -> Result<String> {
+    run_git_full(working_dir, args, remote, objects).map(|(stdout, _)| stdout)
+}
+
+/// 执行 git 命令，返回去除首尾空白的标准输出与标准错误
+fn run_git_full(
+    working_dir: &Path,
+    args: &[&str],
+    remote: Option<&Remote>,
+    objects: Option<&Path>,
+) -> Result<(String, String)> {
+    let token = remote.and_then(|remote| remote.token.as_deref());
+    let mut command = Command::new("git");
+    command
+        .current_dir(working_dir)
+        .args(args)
+        // 鉴权失败时直接报错，而不是等待终端输入用户名密码
+        .env("GIT_TERMINAL_PROMPT", "0");
+
+    if let Some((remote, token)) = remote.zip(token) {
+        let existing = std::env::var("GIT_CONFIG_COUNT").ok();
+        let header = auth_header(&remote.url, token);
+        command.envs(config_env(existing.as_deref(), "http.extraHeader", &header));
+    }
+    if let Some(key) = remote.and_then(|remote| remote.ssh_key.as_deref()) {
+        command.env("GIT_SSH_COMMAND", ssh_command(key));
+    }
+    if let Some(objects) = objects {
+        command.env("GIT_OBJECT_DIRECTORY", objects);
+    }
+
+    let output = command
+        .output()
+        .with_context(|| format!("无法执行 git 命令: git {}", args.join(" ")))?;
+
+    let stderr = redact_token(String::from_utf8_lossy(&output.stderr).trim(), token);
+    if !output.status.success() {
+        bail!("git {} 执行失败: {}", args.join(" "), stderr);
+    }
+
+    Ok((String::from_utf8_lossy(&output.stdout).trim().to_string(), stderr))
+}
+
+/// 以 GIT_CONFIG_* 环境变量追加一项配置
+///
+/// 追加在调用方已导出的配置（existing 为其 GIT_CONFIG_COUNT，如 CI 设置的 safe.directory）之后，
+/// 不会覆盖它们。
+fn config_env(existing: Option<&str>, key: &str, value: &str) -> [(String, String); 3] {
+    let index: usize = existing
+        .and_then(|count| count.trim().parse().ok())
+        .unwrap_or(0);
+    [
+        ("GIT_CONFIG_COUNT".to_string(), (index + 1).to_string()),
+        (format!("GIT_CONFIG_KEY_{}", index), key.to_string()),
+        (format!("GIT_CONFIG_VALUE_{}", index), value.to_string()),
+    ]
+}
+
+/// 三方合并单个文本文件的结果
+pub(crate) struct MergedFile {
+    /// 合并后的内容，有冲突时包含冲突标记
+    pub content: Vec<u8>,
+    /// 是否有冲突
+    pub conflicted: bool,
+}
+
+/// 用 `git merge-file` 三方合并文本文件：以 base 为共同祖先，把 theirs 的改动合入 ours
+///
+/// labels 依次是 ours、base、theirs 在冲突标记中显示的名称。
+pub(crate) fn merge_file(
+    ours: &Path,
+    base: &Path,
+    theirs: &Path,
+    labels: [&str; 3],
+) -> Result<MergedFile> {
+    let output = Command::new("git")
+        .args(["merge-file", "-p", "-L", labels[0], "-L", labels[1], "-L", labels[2]])
+        .args([ours, base, theirs])
+        .output()
+        .context("无法执行 git merge-file，合并本地修改需要系统 git")?;
+
+    // 退出码为冲突数量，负数（截断为 255）表示出错
+    match output.status.code() {
+        Some(code) if (0..255).contains(&code) => Ok(MergedFile {
+            content: output.stdout,
+            conflicted: code > 0,
+        }),
+        _ => bail!(
+            "git merge-file 执行失败: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ),
+    }
+}
+
+/// 生成携带 token 的 HTTP 鉴权头（`Authorization: Basic ...`）
+///
+/// 用户名按仓库所在平台选择，无法识别平台时按 GitHub 处理。
+pub(crate) fn auth_header(url: &str, token: &str) -> String {
+    let kind = Host::from_url(url).map_or(HostKind::GitHub, |host| host.kind);
+    format!("Authorization: Basic {}", basic_credentials(kind, token))
+}
+
+/// Basic 鉴权中 base64 编码的 "用户名:token"
+fn basic_credentials(kind: HostKind, token: &str) -> String {
+    BASE64.encode(format!("{}:{}", kind.token_username(), token))
+}
+
+/// 使用指定私钥的 ssh 命令，供 GIT_SSH_COMMAND / core.sshCommand 使用
+pub(crate) fn ssh_command(key: &Path) -> String {
+    // 按 shell 规则用单引号包裹路径
+    let key = key.to_string_lossy().replace('\'', "'\\''");
+    format!("ssh -i '{}' -o IdentitiesOnly=yes", key)
+}
+
+/// 从输出文本中抹去 token 及其 Basic 鉴权编码，防止其出现在错误信息中
+pub(crate) fn redact_token(text: &str, token: Option<&str>) -> String {
+    match token {
+        Some(token) => HostKind::ALL
+            .iter()
+            .fold(text.to_string(), |text, kind| {
+                text.replace(&basic_credentials(*kind, token), "***")
+            })
+            .replace(token, "***"),
+        None => text.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::{Backend, GitCliBackend};
+    use crate::progress::Progress;
+    use crate::test_support::{bare_clone, git, git_http_backend, serve, FixtureRepo, Response};
+    use std::path::PathBuf;
+    use std::sync::{Arc, Mutex};
+    use tempfile::TempDir;
+
+    const TOKEN: &str = "ghp_secret123";
+
+    /// 服务端收到的请求：(请求路径, 鉴权头)
+    type RequestLog = Arc<Mutex<Vec<(String, Option<String>)>>>;
+
+    /// 只接受 TOKEN 鉴权的 git HTTP 服务，记录收到的请求路径与鉴权头
+    fn authenticated_server(root: PathBuf) -> (String, RequestLog) {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let log = Arc::clone(&seen);
+        let expected = format!("Basic {}", basic_credentials(HostKind::GitHub, TOKEN));
+        let url = serve(move |request| {
+            let authorization = request.header("Authorization").map(str::to_string);
+            log.lock()
+                .unwrap()
+                .push((request.target.clone(), authorization.clone()));
+            if authorization.as_deref() != Some(expected.as_str()) {
+                return Response::new(401, "unauthorized")
+                    .header("WWW-Authenticate", "Basic realm=\"git\"");
+            }
+            git_http_backend(&root, request)
+        });
+        (url, seen)
+    }
+
+    fn remote(url: String, token: Option<&str>) -> Remote {
+        Remote {
+            url,
+            token: token.map(str::to_string),
+            ssh_key: None,
+        }
+    }
+
+    #[test]
+    fn config_env_appends_after_existing() {
+        let env = config_env(Some("2"), "http.extraHeader", "X: y");
+        assert_eq!(env[0], ("GIT_CONFIG_COUNT".to_string(), "3".to_string()));
+        assert_eq!(env[1], ("GIT_CONFIG_KEY_2".to_string(), "http.extraHeader".to_string()));
+        assert_eq!(env[2], ("GIT_CONFIG_VALUE_2".to_string(), "X: y".to_string()));
+
+        let env = config_env(None, "http.extraHeader", "X: y");
+        assert_eq!(env[0].1, "1");
+        assert_eq!(env[1].0, "GIT_CONFIG_KEY_0");
+    }
+
+    #[test]
+    fn auth_header_uses_platform_username() {
+        let username = |url: &str| {
+            let header = auth_header(url, TOKEN);
+            let encoded = header.strip_prefix("Authorization: Basic ").unwrap();
+            let decoded = String::from_utf8(BASE64.decode(encoded).unwrap()).unwrap();
+            assert!(decoded.ends_with(&format!(":{}", TOKEN)));
+            decoded.split(':').next().unwrap().to_string()
+        };
+        assert_eq!(username("https://github.com/owner/repo.git"), "x-access-token");
+        assert_eq!(username("https://gitlab.com/group/sub/proj.git"), "oauth2");
+        assert_eq!(username("https://bitbucket.org/owner/repo.git"), "x-token-auth");
+        assert_eq!(username("https://codeberg.org/owner/repo.git"), "x-access-token");
+        assert_eq!(username("http://127.0.0.1:8080/repo.git"), "x-access-token");
+    }
+
+    #[test]
+    fn redact_token_removes_raw_and_encoded_token() {
+        let header = auth_header("https://gitlab.com/group/proj.git", TOKEN);
+        let text = format!("url https://{}@host/ failed; sent {}", TOKEN, header);
+        let redacted = redact_token(&text, Some(TOKEN));
+        assert!(!redacted.contains(TOKEN));
+        assert!(!redacted.contains(&basic_credentials(HostKind::GitLab, TOKEN)));
+        assert_eq!(redact_token("plain", None), "plain");
+    }
+
+    #[test]
+    fn token_is_sent_as_header_and_never_persisted() {
+        let repo = FixtureRepo::new();
+        repo.write("examples/a.txt", "a\n");
+        repo.commit("init");
+        let root = TempDir::new().unwrap();
+        bare_clone(&repo, root.path(), "repo.git");
+        let (url, seen) = authenticated_server(root.path().to_path_buf());
+        let url = format!("{}/repo.git", url);
+
+        let mut backend =
+            GitCliBackend::new(remote(url.clone(), Some(TOKEN)), Progress::silent(), false)
+                .unwrap();
+        let resolved = backend.resolve_ref(None).unwrap();
+        assert_eq!(resolved.name, "main");
+        let entries = backend.list_tree(&resolved, Some("examples")).unwrap();
+        assert!(entries.iter().any(|entry| entry.path == "examples/a.txt"));
+
+        let requests = seen.lock().unwrap().clone();
+        assert!(!requests.is_empty());
+        for (target, authorization) in &requests {
+            assert!(!target.contains(TOKEN), "token 出现在请求地址中: {}", target);
+            assert!(authorization.is_some(), "请求未携带鉴权头: {}", target);
+        }
+
+        // token 不能写入任何 git 配置
+        let workdir = TempDir::new().unwrap();
+        git(workdir.path(), &["init", "--quiet"]);
+        git(workdir.path(), &["remote", "add", "origin", &url]);
+        ls_remote(workdir.path(), &remote(url.clone(), Some(TOKEN))).unwrap();
+        let config = std::fs::read_to_string(workdir.path().join(".git/config")).unwrap();
+        assert!(!config.contains(TOKEN));
+        assert!(!config.contains(&basic_credentials(HostKind::GitHub, TOKEN)));
+    }
+
+    #[test]
+    fn rejected_token_is_not_leaked_in_errors() {
+        let repo = FixtureRepo::new();
+        repo.write("a.txt", "a\n");
+        repo.commit("init");
+        let root = TempDir::new().unwrap();
+        bare_clone(&repo, root.path(), "repo.git");
+        let (url, _) = authenticated_server(root.path().to_path_buf());
+        let url = format!("{}/repo.git", url);
+
+        let workdir = TempDir::new().unwrap();
+        git(workdir.path(), &["init", "--quiet"]);
+        git(workdir.path(), &["remote", "add", "origin", &url]);
+
+        let wrong = "ghp_wrong456";
+        let error = ls_remote(workdir.path(), &remote(url.clone(), Some(wrong))).unwrap_err();
+        let message = format!("{:#}", error);
+        assert!(!message.contains(wrong));
+        assert!(!message.contains(&basic_credentials(HostKind::GitHub, wrong)));
+
+        assert!(ls_remote(workdir.path(), &remote(url, None)).is_err());
+    }
+}
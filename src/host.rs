@@ -0,0 +1,531 @@
+//! 代码托管平台：识别 GitHub、GitLab、Gitea/Forgejo、Bitbucket 的地址格式
+//!
+//! 各平台网页 URL 中 "<ref>/<path>" 的位置不同，这里统一解析为
+//! 克隆地址与尚未拆分的 tree_spec，交给抓取流程按远程引用拆分。
+
+use crate::config::{config, HostEntry};
+use anyhow::{anyhow, bail, Result};
+use serde::Deserialize;
+use std::str::FromStr;
+
+/// 托管平台类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HostKind {
+    /// github.com：/owner/repo/tree|blob/<ref>/<path>
+    GitHub,
+    /// gitlab.com 或自建 GitLab：/group/sub/proj/-/tree|blob|raw/<ref>/<path>
+    GitLab,
+    /// Gitea/Forgejo（含 codeberg.org）：/owner/repo/src|raw/branch|tag|commit/<ref>/<path>
+    #[serde(alias = "forgejo")]
+    Gitea,
+    /// bitbucket.org：/owner/repo/src|raw/<ref>/<path>
+    Bitbucket,
+}
+
+impl HostKind {
+    /// 平台名称，用于输出
+    pub fn name(self) -> &'static str {
+        match self {
+            HostKind::GitHub => "GitHub",
+            HostKind::GitLab => "GitLab",
+            HostKind::Gitea => "Gitea",
+            HostKind::Bitbucket => "Bitbucket",
+        }
+    }
+
+    /// 全部平台类型
+    pub const ALL: [HostKind; 4] = [
+        HostKind::GitHub,
+        HostKind::GitLab,
+        HostKind::Gitea,
+        HostKind::Bitbucket,
+    ];
+
+    /// 以 HTTP Basic 鉴权发送 token 时使用的用户名
+    ///
+    /// GitLab 的 OAuth token 要求 oauth2，Bitbucket 的访问 token 要求 x-token-auth；
+    /// Gitea 只校验密码位置上的 token，用户名可任意。
+    pub fn token_username(self) -> &'static str {
+        match self {
+            HostKind::GitHub | HostKind::Gitea => "x-access-token",
+            HostKind::GitLab => "oauth2",
+            HostKind::Bitbucket => "x-token-auth",
+        }
+    }
+
+    /// 未显式传入 token 时读取的环境变量（按顺序）
+    ///
+    /// 按平台区分，避免把 GitHub 的 token 发给其他主机。
+    pub fn token_env_vars(self) -> &'static [&'static str] {
+        match self {
+            HostKind::GitHub => &["GITHUB_TOKEN", "GH_TOKEN"],
+            HostKind::GitLab => &["GITLAB_TOKEN"],
+            HostKind::Gitea => &["GITEA_TOKEN"],
+            HostKind::Bitbucket => &["BITBUCKET_TOKEN"],
+        }
+    }
+}
+
+impl FromStr for HostKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "github" => Ok(HostKind::GitHub),
+            "gitlab" => Ok(HostKind::GitLab),
+            "gitea" | "forgejo" => Ok(HostKind::Gitea),
+            "bitbucket" => Ok(HostKind::Bitbucket),
+            other => bail!(
+                "未知的平台类型: {}（可选: github、gitlab、gitea、forgejo、bitbucket）",
+                other
+            ),
+        }
+    }
+}
+
+/// 内置的公共托管平台域名
+const KNOWN_HOSTS: &[(&str, HostKind)] = &[
+    ("github.com", HostKind::GitHub),
+    ("gitlab.com", HostKind::GitLab),
+    ("codeberg.org", HostKind::Gitea),
+    ("gitea.com", HostKind::Gitea),
+    ("bitbucket.org", HostKind::Bitbucket),
+];
+
+/// 简写前缀（如 gitlab:group/proj）对应的域名
+const SHORTHANDS: &[(&str, &str)] = &[
+    ("github", "github.com"),
+    ("gitlab", "gitlab.com"),
+    ("bitbucket", "bitbucket.org"),
+    ("codeberg", "codeberg.org"),
+];
+
+/// GitHub raw 文件下载域名
+const GITHUB_RAW_HOST: &str = "raw.githubusercontent.com";
+
+/// 一个托管平台实例：平台类型 + 域名，以及配置文件中的可选设置
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Host {
+    pub kind: HostKind,
+    /// 域名，可带端口（如 gitea.local:3000）
+    pub domain: String,
+    /// 克隆地址模板，`{repo}` 替换为仓库路径
+    pub clone_template: Option<String>,
+    /// REST API 地址
+    pub api_url: Option<String>,
+    /// 读取 token 的环境变量，替代平台默认的变量
+    pub token_env: Option<String>,
+}
+
+/// 从网页 URL 解析出的信息
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParsedWebUrl {
+    /// 仓库所在平台
+    pub host: Host,
+    /// 仓库在平台上的路径，例如 owner/repo、group/sub/proj
+    pub repo_path: String,
+    /// 克隆地址（SSH 输入保持 SSH；否则未配置模板时沿用网页 URL 的 http/https 协议）
+    pub clone_url: String,
+    /// 目录/文件标记之后尚未拆分的 "<ref>/<path>"，需结合远程引用列表拆分
+    pub tree_spec: Option<String>,
+}
+
+impl Host {
+    /// 仅由平台类型与域名构成的主机
+    pub fn new(kind: HostKind, domain: impl Into<String>) -> Host {
+        Host {
+            kind,
+            domain: domain.into(),
+            clone_template: None,
+            api_url: None,
+            token_env: None,
+        }
+    }
+
+    /// 按域名查找配置文件中声明的主机与内置平台（配置优先）
+    ///
+    /// 带端口的域名（如 SSH 地址中的 host:7999）也会与不带端口的配置匹配。
+    pub fn known(domain: &str) -> Option<Host> {
+        Host::known_in(&config().hosts, domain)
+    }
+
+    /// 在给定的主机配置与内置平台中按域名查找
+    fn known_in(hosts: &[HostEntry], domain: &str) -> Option<Host> {
+        let domain = domain.to_ascii_lowercase();
+        let bare = domain.split(':').next().unwrap_or(&domain);
+        let matches = |known: &str| {
+            let known = known.to_ascii_lowercase();
+            known == domain || known == bare
+        };
+
+        if let Some(entry) = hosts.iter().find(|entry| matches(&entry.domain)) {
+            return Some(Host {
+                kind: entry.kind,
+                domain: entry.domain.to_ascii_lowercase(),
+                clone_template: entry.clone_url.clone(),
+                api_url: entry.api_url.clone(),
+                token_env: entry.token_env.clone(),
+            });
+        }
+        if domain == GITHUB_RAW_HOST {
+            return Some(Host::new(HostKind::GitHub, "github.com"));
+        }
+        KNOWN_HOSTS
+            .iter()
+            .find(|(known, _)| matches(known))
+            .map(|(known, kind)| Host::new(*kind, *known))
+    }
+
+    /// `owner/repo` 简写使用的主机：配置中的 default_host，默认 github.com
+    ///
+    /// default_host 未在配置中声明时按 GitHub（Enterprise）处理。
+    pub fn default_host() -> Host {
+        match config().default_host.as_deref() {
+            Some(domain) => {
+                Host::known(domain).unwrap_or_else(|| Host::new(HostKind::GitHub, domain))
+            }
+            None => Host::new(HostKind::GitHub, "github.com"),
+        }
+    }
+
+    /// 识别仓库地址（网页 URL、HTTPS/SSH 克隆地址）所在的平台
+    ///
+    /// 先按内置域名匹配；自建实例则根据网页 URL 中的平台特征路径推断
+    /// （GitLab 的 `/-/tree/`，Gitea 的 `/src/branch/` 等），无法判断时返回 None。
+    pub fn from_url(url: &str) -> Option<Host> {
+        Host::from_url_in(&config().hosts, url)
+    }
+
+    /// 按给定的主机配置识别仓库地址所在的平台
+    fn from_url_in(hosts: &[HostEntry], url: &str) -> Option<Host> {
+        let (domain, path) = split_host_and_path(url)?;
+        if let Some(mut host) = Host::known_in(hosts, domain) {
+            // 网页或 HTTP(S) 地址中的端口在匹配不带端口的配置后仍要保留，克隆地址与 API
+            // 地址都需要它；SSH 地址中的端口只属于 SSH，克隆时原样沿用 SSH 地址
+            if domain.contains(':') && (url.starts_with("https://") || url.starts_with("http://")) {
+                host.domain = domain.to_ascii_lowercase();
+            }
+            return Some(host);
+        }
+        let segments: Vec<&str> = path.split('/').collect();
+        let kind = if gitlab_marker(&segments).is_some() {
+            HostKind::GitLab
+        } else if gitea_marker(&segments).is_some() {
+            HostKind::Gitea
+        } else {
+            return None;
+        };
+        Some(Host::new(kind, domain.to_ascii_lowercase()))
+    }
+
+    /// 仓库的克隆地址：按配置的模板生成，默认 https://<domain>/<repo>.git
+    pub fn clone_url(&self, repo_path: &str) -> String {
+        match &self.clone_template {
+            Some(template) => template.replace("{repo}", repo_path),
+            None => format!("https://{}/{}.git", self.domain, repo_path),
+        }
+    }
+
+    /// 未显式传入 token 时读取的环境变量（按顺序）
+    pub fn token_env_vars(&self) -> Vec<&str> {
+        match &self.token_env {
+            Some(var) => vec![var.as_str()],
+            None => self.kind.token_env_vars().to_vec(),
+        }
+    }
+
+    /// GitHub REST API 地址：配置的 api_url，github.com 为 api.github.com，
+    /// GitHub Enterprise 为 https://<domain>/api/v3；其他平台返回 None
+    pub fn github_api_url(&self) -> Option<String> {
+        if self.kind != HostKind::GitHub {
+            return None;
+        }
+        Some(match &self.api_url {
+            Some(url) => url.clone(),
+            None if self.domain == "github.com" => "https://api.github.com".to_string(),
+            None => format!("https://{}/api/v3", self.domain),
+        })
+    }
+
+    /// 是否为公共的 github.com
+    pub fn is_github_com(&self) -> bool {
+        self.kind == HostKind::GitHub && self.domain == "github.com"
+    }
+
+    /// 按平台格式解析网页 URL（也接受在 SSH 地址后附加网页路径的写法）
+    pub fn parse_web_url(&self, url: &str) -> Result<ParsedWebUrl> {
+        let Some((domain, path)) = split_host_and_path(url) else {
+            bail!("无法解析 URL: {}", url);
+        };
+        let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+
+        let (repo_segments, spec_segments) = if domain.eq_ignore_ascii_case(GITHUB_RAW_HOST) {
+            // raw.githubusercontent.com/owner/repo/<ref>/<path>
+            (segments.get(..2), segments.get(2..))
+        } else {
+            match self.kind {
+                HostKind::GitHub => match segments.get(2) {
+                    Some(&"tree") | Some(&"blob") => (segments.get(..2), segments.get(3..)),
+                    _ => (segments.get(..2), None),
+                },
+                // 子组可以任意嵌套，仓库路径到 "-" 为止
+                HostKind::GitLab => match gitlab_marker(&segments) {
+                    Some(i) => (segments.get(..i), segments.get(i + 2..)),
+                    None => {
+                        let end = segments.iter().position(|s| *s == "-");
+                        (segments.get(..end.unwrap_or(segments.len())), None)
+                    }
+                },
+                HostKind::Gitea => match gitea_marker(&segments) {
+                    Some(i) => (segments.get(..i), segments.get(i + 2..)),
+                    None => (segments.get(..2), None),
+                },
+                HostKind::Bitbucket => match segments.get(2) {
+                    Some(&"src") | Some(&"raw") => (segments.get(..2), segments.get(3..)),
+                    _ => (segments.get(..2), None),
+                },
+            }
+        };
+
+        let repo_segments = repo_segments
+            .filter(|s| s.len() >= 2)
+            .ok_or_else(|| anyhow!("URL 格式错误，无法提取仓库信息: {}", url))?;
+        let repo_path = repo_segments.join("/");
+        let repo_path = repo_path.trim_end_matches(".git").to_string();
+
+        let tree_spec = spec_segments
+            .filter(|s| !s.is_empty())
+            .map(|s| s.join("/"))
+            .map(|spec| {
+                // raw 链接中的引用可能写成完整的 refs/heads/<branch> 或 refs/tags/<tag>
+                spec.strip_prefix("refs/heads/")
+                    .or_else(|| spec.strip_prefix("refs/tags/"))
+                    .map(str::to_string)
+                    .unwrap_or(spec)
+            });
+
+        // SSH 地址保留原有的用户、主机与端口；未配置克隆模板时沿用网页 URL 的协议
+        let clone_url = match (ssh_prefix(url), &self.clone_template) {
+            (Some(prefix), _) => format!("{}{}.git", prefix, repo_path),
+            (None, Some(_)) => self.clone_url(&repo_path),
+            (None, None) if url.starts_with("http://") => {
+                format!("http://{}/{}.git", self.domain, repo_path)
+            }
+            (None, None) => self.clone_url(&repo_path),
+        };
+        Ok(ParsedWebUrl {
+            host: self.clone(),
+            clone_url,
+            repo_path,
+            tree_spec,
+        })
+    }
+}
+
+/// 展开 `gitlab:group/proj` 形式的简写为 HTTPS 克隆地址，非简写返回 None
+pub fn expand_shorthand(input: &str) -> Option<String> {
+    let (prefix, repo_path) = input.split_once(':')?;
+    let (_, domain) = SHORTHANDS.iter().find(|(name, _)| *name == prefix)?;
+    let repo_path = repo_path.trim_matches('/').trim_end_matches(".git");
+    if repo_path.split('/').filter(|s| !s.is_empty()).count() < 2 {
+        return None;
+    }
+    let host = Host::known(domain)?;
+    Some(host.clone_url(repo_path))
+}
+
+/// 拆分 scp 风格的地址 `[user@]host:path`，返回 (`[user@]host`, path)
+///
+/// 冒号前不能含 `/`，且主机部分至少两个字符（排除 Windows 盘符）。
+pub(crate) fn split_scp(url: &str) -> Option<(&str, &str)> {
+    if url.contains("://") {
+        return None;
+    }
+    let (user_host, path) = url.split_once(':')?;
+    let host = user_host.rsplit('@').next().unwrap_or(user_host);
+    (host.len() > 1 && !user_host.contains('/') && !path.is_empty()).then_some((user_host, path))
+}
+
+/// SSH 地址中仓库路径之前的部分（`ssh://user@host:port/` 或 `user@host:`），非 SSH 地址返回 None
+fn ssh_prefix(url: &str) -> Option<&str> {
+    if let Some(rest) = url.strip_prefix("ssh://") {
+        let end = "ssh://".len() + rest.find('/')? + 1;
+        return Some(&url[..end]);
+    }
+    let (user_host, _) = split_scp(url)?;
+    Some(&url[..user_host.len() + 1])
+}
+
+/// 拆出地址中的域名与路径，支持 http(s)://、ssh:// 与 scp 风格的 [user@]host:path；
+/// 路径中的查询串与锚点会被丢弃
+fn split_host_and_path(url: &str) -> Option<(&str, &str)> {
+    let rest = if let Some(rest) = url
+        .strip_prefix("https://")
+        .or_else(|| url.strip_prefix("http://"))
+        .or_else(|| url.strip_prefix("ssh://"))
+    {
+        rest
+    } else {
+        // scp 风格: git@host:owner/repo.git
+        let (user_host, path) = split_scp(url)?;
+        let domain = user_host.rsplit('@').next().unwrap_or(user_host);
+        return Some((domain, path.trim_end_matches('/')));
+    };
+
+    let rest = rest.split(['?', '#']).next().unwrap_or(rest);
+    let (authority, path) = rest.split_once('/').unwrap_or((rest, ""));
+    // 去掉用户信息，保留端口（自建实例的克隆地址需要）
+    let domain = authority.rsplit('@').next().unwrap_or(authority);
+    (!domain.is_empty()).then_some((domain, path.trim_end_matches('/')))
+}
+
+/// GitLab 网页 URL 中 `-/tree|blob|raw` 标记的位置
+fn gitlab_marker(segments: &[&str]) -> Option<usize> {
+    segments.windows(2).position(|w| {
+        w[0] == "-" && matches!(w[1], "tree" | "blob" | "raw")
+    })
+}
+
+/// Gitea 网页 URL 中 `src|raw/branch|tag|commit` 标记的位置
+fn gitea_marker(segments: &[&str]) -> Option<usize> {
+    segments.windows(2).position(|w| {
+        matches!(w[0], "src" | "raw") && matches!(w[1], "branch" | "tag" | "commit")
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(domain: &str, kind: HostKind) -> HostEntry {
+        HostEntry {
+            domain: domain.to_string(),
+            kind,
+            clone_url: None,
+            api_url: None,
+            token_env: None,
+        }
+    }
+
+    #[test]
+    fn web_urls_are_split_per_platform() {
+        let cases = [
+            (
+                "https://github.com/owner/repo/tree/main/examples/servers",
+                "owner/repo",
+                "https://github.com/owner/repo.git",
+                Some("main/examples/servers"),
+            ),
+            (
+                "https://raw.githubusercontent.com/owner/repo/refs/heads/main/README.md",
+                "owner/repo",
+                "https://github.com/owner/repo.git",
+                Some("main/README.md"),
+            ),
+            (
+                "https://gitlab.com/group/sub/proj/-/tree/feature/x/ci?ref_type=heads",
+                "group/sub/proj",
+                "https://gitlab.com/group/sub/proj.git",
+                Some("feature/x/ci"),
+            ),
+            (
+                "https://gitlab.com/group/proj/-/merge_requests/1",
+                "group/proj",
+                "https://gitlab.com/group/proj.git",
+                None,
+            ),
+            (
+                "https://codeberg.org/owner/repo/src/branch/main/docs",
+                "owner/repo",
+                "https://codeberg.org/owner/repo.git",
+                Some("main/docs"),
+            ),
+            (
+                "https://codeberg.org/owner/repo/src/commit/3f2a9c1d/docs/a.md",
+                "owner/repo",
+                "https://codeberg.org/owner/repo.git",
+                Some("3f2a9c1d/docs/a.md"),
+            ),
+            (
+                "https://bitbucket.org/owner/repo/src/v1.0/lib/#lines-3",
+                "owner/repo",
+                "https://bitbucket.org/owner/repo.git",
+                Some("v1.0/lib"),
+            ),
+            (
+                "http://gitea.local:3000/owner/repo/raw/tag/v2/a.txt",
+                "owner/repo",
+                "http://gitea.local:3000/owner/repo.git",
+                Some("v2/a.txt"),
+            ),
+            (
+                "https://git.corp/a/b/c/-/blob/main/x.rs",
+                "a/b/c",
+                "https://git.corp/a/b/c.git",
+                Some("main/x.rs"),
+            ),
+            (
+                "git@gitlab.com:group/proj.git/-/tree/main/ci",
+                "group/proj",
+                "git@gitlab.com:group/proj.git",
+                Some("main/ci"),
+            ),
+        ];
+        for (url, repo_path, clone_url, tree_spec) in cases {
+            let host = Host::from_url_in(&[], url).unwrap_or_else(|| panic!("{}", url));
+            let parsed = host.parse_web_url(url).unwrap();
+            assert_eq!(parsed.repo_path, repo_path, "{}", url);
+            assert_eq!(parsed.clone_url, clone_url, "{}", url);
+            assert_eq!(parsed.tree_spec.as_deref(), tree_spec, "{}", url);
+        }
+        assert_eq!(Host::from_url_in(&[], "https://example.com/owner/repo"), None);
+        assert!(Host::new(HostKind::GitHub, "github.com")
+            .parse_web_url("https://github.com/owner")
+            .is_err());
+    }
+
+    #[test]
+    fn http_ports_are_kept_for_configured_hosts() {
+        let hosts = [entry("ghe.corp", HostKind::GitHub)];
+
+        let url = "https://ghe.corp:8443/org/repo/tree/main/dir";
+        let host = Host::from_url_in(&hosts, url).unwrap();
+        assert_eq!(host.domain, "ghe.corp:8443");
+        assert_eq!(host.clone_url("org/repo"), "https://ghe.corp:8443/org/repo.git");
+        assert_eq!(host.github_api_url().unwrap(), "https://ghe.corp:8443/api/v3");
+        assert_eq!(
+            host.parse_web_url(url).unwrap().clone_url,
+            "https://ghe.corp:8443/org/repo.git"
+        );
+
+        // SSH 端口不属于 HTTPS 地址，克隆时沿用 SSH 地址本身
+        let url = "ssh://git@ghe.corp:2222/org/repo.git";
+        let host = Host::from_url_in(&hosts, url).unwrap();
+        assert_eq!(host.domain, "ghe.corp");
+        assert_eq!(host.parse_web_url(url).unwrap().clone_url, url);
+
+        let host = Host::from_url_in(&hosts, "https://ghe.corp/org/repo").unwrap();
+        assert_eq!(host.domain, "ghe.corp");
+        assert_eq!(host.kind, HostKind::GitHub);
+    }
+
+    #[test]
+    fn shorthands_expand_to_https_clone_urls() {
+        assert_eq!(
+            expand_shorthand("gitlab:group/sub/proj").as_deref(),
+            Some("https://gitlab.com/group/sub/proj.git")
+        );
+        assert_eq!(
+            expand_shorthand("codeberg:owner/repo.git").as_deref(),
+            Some("https://codeberg.org/owner/repo.git")
+        );
+        assert_eq!(
+            expand_shorthand("bitbucket:/owner/repo/").as_deref(),
+            Some("https://bitbucket.org/owner/repo.git")
+        );
+        assert_eq!(expand_shorthand("gitlab:proj"), None);
+        assert_eq!(expand_shorthand("svn:owner/repo"), None);
+        assert_eq!(expand_shorthand("owner/repo"), None);
+    }
+}
@@ -0,0 +1,78 @@
+//! 简单的 HTTP GET 封装，供 archive 后端访问 GitHub API 与 codeload
+
+use anyhow::{bail, Context, Result};
+use std::io::Read;
+
+/// 带可选 token 鉴权的 HTTP 客户端
+pub(crate) struct HttpClient {
+    agent: ureq::Agent,
+    token: Option<String>,
+}
+
+impl HttpClient {
+    pub(crate) fn new(token: Option<String>) -> Self {
+        let agent = ureq::Agent::config_builder()
+            .user_agent(concat!("git-get/", env!("CARGO_PKG_VERSION")))
+            .build()
+            .new_agent();
+        Self { agent, token }
+    }
+
+    /// GET 请求并以字符串形式读取完整响应体
+    pub(crate) fn get_string(&self, url: &str, accept: &str) -> Result<String> {
+        let mut response = self.get(url, accept)?;
+        response
+            .body_mut()
+            .with_config()
+            .limit(u64::MAX)
+            .read_to_string()
+            .with_context(|| format!("无法读取响应: {}", url))
+    }
+
+    /// 与 [`get_string`](Self::get_string) 相同，但 404 时返回 None
+    pub(crate) fn get_optional_string(&self, url: &str, accept: &str) -> Result<Option<String>> {
+        match self.request(url, accept) {
+            Err(ureq::Error::StatusCode(404)) => Ok(None),
+            result => {
+                let mut response = check(result, url)?;
+                response
+                    .body_mut()
+                    .with_config()
+                    .limit(u64::MAX)
+                    .read_to_string()
+                    .map(Some)
+                    .with_context(|| format!("无法读取响应: {}", url))
+            }
+        }
+    }
+
+    /// GET 请求并返回流式响应体
+    pub(crate) fn get_reader(&self, url: &str) -> Result<impl Read> {
+        Ok(self.get(url, "*/*")?.into_body().into_reader())
+    }
+
+    fn get(&self, url: &str, accept: &str) -> Result<Response> {
+        check(self.request(url, accept), url)
+    }
+
+    fn request(&self, url: &str, accept: &str) -> Result<Response, ureq::Error> {
+        let mut request = self.agent.get(url).header("Accept", accept);
+        if let Some(token) = &self.token {
+            request = request.header("Authorization", format!("token {}", token));
+        }
+        request.call()
+    }
+}
+
+type Response = ureq::http::Response<ureq::Body>;
+
+/// 把 HTTP 错误转换为带 URL 的错误信息
+fn check(result: Result<Response, ureq::Error>, url: &str) -> Result<Response> {
+    match result {
+        Ok(response) => Ok(response),
+        Err(ureq::Error::StatusCode(status)) => {
+            bail!("HTTP 请求失败 ({}): {}", status, url)
+        }
+        Err(e) => Err(e).with_context(|| format!("HTTP 请求失败: {}", url)),
+    }
+}
@@ -0,0 +1,39 @@
+//! git-get: 从 GitHub、GitLab 等托管平台的仓库下载指定子目录或整个仓库
+//!
+//! 主要功能：
+//! - 通过可替换的后端拉取仓库（系统 git + sparse-checkout，或 GitHub 源码包）
+//! - 将指定子目录或整个仓库复制到目标路径
+//! - 自动清理临时文件，不污染当前项目的 .git 结构
+//!
+//! 命令行工具 `git-get` 只是本库的一层薄封装，其他 Rust 工具可以直接
+//! 构造 [`FetchRequest`] 并调用 [`fetch`] 完成同样的抓取。
+
+pub mod backend;
+pub mod cache;
+pub mod cleanup;
+pub mod config;
+pub mod copy;
+pub mod fetch;
+pub mod filter;
+pub mod git;
+pub mod host;
+mod http;
+pub mod lock;
+pub mod manifest;
+pub mod progress;
+pub mod provenance;
+pub mod refs;
+pub mod repo;
+mod suggest;
+#[cfg(test)]
+mod test_support;
+pub mod sync;
+pub mod update;
+
+pub use backend::BackendKind;
+pub use copy::OnConflict;
+pub use fetch::{
+    fetch, fetch_many, prefetch, resolve, FetchOptions, FetchOutcome, FetchRequest, FetchTarget, Resolution,
+};
+pub use progress::Progress;
+pub use update::{update, UpdateOutcome};
@@ -0,0 +1,101 @@
+//! 锁文件 `git-get.lock`：记录清单中每个条目解析到的提交，使抓取结果可复现
+//!
+//! 锁文件与清单放在同一目录，由 `git-get sync` 生成和更新；之后的同步
+//! 固定抓取锁定的提交，除非使用 `--update`。
+
+use crate::manifest::ManifestEntry;
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// 锁文件格式版本
+const LOCK_VERSION: u32 = 1;
+
+/// 锁文件开头的说明
+const LOCK_HEADER: &str = "# 由 git-get sync 自动生成，请勿手动编辑\n\n";
+
+/// 锁文件
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Lockfile {
+    /// 格式版本
+    pub version: u32,
+    /// 各条目的锁定信息
+    #[serde(default, rename = "entry")]
+    pub entries: Vec<LockEntry>,
+}
+
+/// 一个条目的锁定信息
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LockEntry {
+    /// 清单中的目标路径（相对清单目录）
+    pub dest: PathBuf,
+    /// 清单中的 source
+    pub source: String,
+    /// 清单中请求的引用，未指定时为空
+    #[serde(default, rename = "ref", skip_serializing_if = "Option::is_none")]
+    pub reference: Option<String>,
+    /// 实际使用的 Git 仓库 URL
+    pub url: String,
+    /// 解析到的引用名
+    pub resolved: String,
+    /// 仓库内路径，为空表示整个仓库
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    /// 锁定的提交 SHA
+    pub commit: String,
+    /// 路径对应的 git 对象 ID（目录为 tree 哈希，文件为 blob 哈希）
+    pub tree: String,
+}
+
+impl LockEntry {
+    /// 锁定信息是否仍对应清单中的条目（source、ref、path 未被修改）
+    pub fn matches(&self, entry: &ManifestEntry) -> bool {
+        let path = entry.path.as_deref().map(|path| path.trim_matches('/'));
+        self.dest == entry.dest
+            && self.source == entry.source
+            && self.reference == entry.reference
+            && (path.is_none() || path == self.path.as_deref())
+    }
+}
+
+impl Lockfile {
+    /// 清单对应的锁文件路径（同目录下的 git-get.lock）
+    pub fn path_for(manifest_path: &Path) -> PathBuf {
+        manifest_path.with_extension("lock")
+    }
+
+    /// 读取锁文件，不存在时返回空的锁文件
+    pub fn load(path: &Path) -> Result<Lockfile> {
+        if !path.exists() {
+            return Ok(Lockfile {
+                version: LOCK_VERSION,
+                entries: Vec::new(),
+            });
+        }
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("无法读取锁文件: {}", path.display()))?;
+        let lock: Lockfile = toml::from_str(&content)
+            .with_context(|| format!("锁文件格式错误: {}", path.display()))?;
+        if lock.version != LOCK_VERSION {
+            bail!(
+                "不支持的锁文件版本 {}（当前版本 {}）: {}",
+                lock.version,
+                LOCK_VERSION,
+                path.display()
+            );
+        }
+        Ok(lock)
+    }
+
+    /// 写入锁文件
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let body = toml::to_string_pretty(self).context("无法生成锁文件")?;
+        std::fs::write(path, format!("{}{}", LOCK_HEADER, body))
+            .with_context(|| format!("无法写入锁文件: {}", path.display()))
+    }
+
+    /// 查找仍与清单条目对应的锁定信息
+    pub fn find(&self, entry: &ManifestEntry) -> Option<&LockEntry> {
+        self.entries.iter().find(|locked| locked.matches(entry))
+    }
+}
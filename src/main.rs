@@ -1,59 +1,249 @@
 //! git-get: 从 GitHub 仓库下载指定子目录或整个仓库的命令行工具
 //!
-//! 主要功能：
-//! - 在临时目录中克隆仓库（子目录模式使用 sparse-checkout 优化）
-//! - 将指定子目录或整个仓库复制到目标路径
-//! - 自动清理临时文件，不污染当前项目的 .git 结构
-
-use anyhow::{anyhow, bail, Context, Result};
-use clap::Parser;
+//! 抓取逻辑全部位于 `git_get` 库中，这里只负责解析命令行参数、
+//! 输出进度以及更新当前目录的 .gitignore。子命令:
+//! - `git-get sync`：按项目清单批量抓取
+//! - `git-get update`：原地更新已抓取的目录
+//! - `git-get cache`：管理本地对象缓存
+//! - `git-get prefetch`：为离线抓取预热缓存
+
+use anyhow::{bail, Context, Result};
+use clap::{Parser, Subcommand};
+use git_get::backend::EntryKind;
+use git_get::cache;
+use git_get::config::Config;
+use git_get::copy::{CollisionAction, ConflictPrompt};
+use git_get::lock::Lockfile;
+use git_get::manifest::{Manifest, MANIFEST_FILE};
+use git_get::sync::{sync, LockMode, SyncStatus};
+use git_get::update::update_request;
+use git_get::{
+    fetch, fetch_many, update, BackendKind, FetchOptions, FetchOutcome, FetchRequest, FetchTarget,
+    OnConflict, Progress, UpdateOutcome,
+};
+use std::io::{IsTerminal, Write};
 use std::path::{Path, PathBuf};
-use std::process::Command;
-use tempfile::TempDir;
+use std::time::{Duration, SystemTime};
 
 /// 从 GitHub 仓库下载指定子目录或整个仓库到本地
 #[derive(Parser, Debug)]
 #[command(name = "git-get")]
 #[command(author, version, about, long_about = None)]
-struct Args {
-    /// GitHub URL 或仓库标识
+#[command(args_conflicts_with_subcommands = true)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    #[command(flatten)]
+    fetch: FetchArgs,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// 按项目清单（git-get.toml）抓取全部条目，并把解析到的提交记录到 git-get.lock
+    Sync(SyncArgs),
+    /// 按目标目录中的来源记录（.git-get.json）原地更新之前抓取的目录
+    Update(UpdateArgs),
+    /// 管理 git 后端的本地对象缓存
+    #[command(subcommand)]
+    Cache(CacheCommand),
+    /// 把仓库拉取到本地缓存，供之后的 --offline 使用
+    Prefetch(PrefetchArgs),
+}
+
+/// `git-get prefetch` 的参数
+#[derive(clap::Args, Debug)]
+struct PrefetchArgs {
+    /// 要预热的仓库，格式与单次抓取的位置参数相同；未指定时预热清单中的全部条目
+    #[arg(value_name = "SOURCE")]
+    sources: Vec<String>,
+
+    /// 清单文件路径；条目在锁文件中有记录时预热锁定的提交
+    #[arg(short, long, value_name = "PATH", default_value = MANIFEST_FILE)]
+    manifest: PathBuf,
+
+    /// 预热指定引用（只作用于 SOURCE），默认为 URL 中的引用或远程默认分支
+    #[arg(short = 'b', long = "ref", visible_aliases = ["rev", "branch"], value_name = "REF")]
+    reference: Option<String>,
+
+    /// 只预热仓库内的这些路径（可重复指定，只作用于 SOURCE），默认为 URL 中的路径或整个仓库
+    #[arg(short, long, value_name = "PATH")]
+    path: Vec<String>,
+
+    /// 访问 token，用于拉取私有仓库
+    #[arg(long)]
+    token: Option<String>,
+
+    /// 访问 SSH 地址时使用的私钥文件
+    #[arg(long, value_name = "PATH")]
+    ssh_key: Option<PathBuf>,
+}
+
+/// `git-get cache` 的子命令
+#[derive(Subcommand, Debug)]
+enum CacheCommand {
+    /// 列出缓存的仓库及其大小、最近使用时间
+    List,
+    /// 按最近使用时间删除旧仓库，直到缓存不超过大小上限
+    Gc {
+        /// 大小上限，如 500M、5GiB；默认使用配置中的 cache_max_size（未配置时为 5GiB）
+        #[arg(long, value_name = "SIZE")]
+        max_size: Option<String>,
+    },
+    /// 删除全部缓存
+    Clear,
+}
+
+/// `git-get update` 的参数
+#[derive(clap::Args, Debug)]
+struct UpdateArgs {
+    /// 要更新的目录（之前由 git-get 抓取）
+    #[arg(value_name = "DEST")]
+    dest: PathBuf,
+
+    /// 更新到指定引用，默认沿用来源记录中的引用（未记录时为远程默认分支）
+    #[arg(short = 'b', long = "ref", visible_aliases = ["rev", "branch"], value_name = "REF")]
+    reference: Option<String>,
+
+    /// 访问 token，用于拉取私有仓库
+    #[arg(long)]
+    token: Option<String>,
+
+    /// 访问 SSH 地址时使用的私钥文件
+    #[arg(long, value_name = "PATH")]
+    ssh_key: Option<PathBuf>,
+
+    /// 抓取后端
+    #[arg(long, value_name = "BACKEND", default_value = "auto")]
+    backend: BackendKind,
+
+    /// 不使用本地对象缓存，在临时目录中重新拉取
+    #[arg(long)]
+    no_cache: bool,
+
+    /// 离线模式：只使用本地缓存中的仓库、引用与提交，不访问网络
+    #[arg(long, conflicts_with = "no_cache")]
+    offline: bool,
+}
+
+/// `git-get sync` 的参数
+#[derive(clap::Args, Debug)]
+struct SyncArgs {
+    /// 清单文件路径
+    #[arg(short, long, value_name = "PATH", default_value = MANIFEST_FILE)]
+    manifest: PathBuf,
+
+    /// 默认访问 token（条目中的 token_env 优先）
+    #[arg(long)]
+    token: Option<String>,
+
+    /// 默认抓取后端（条目中的 backend 优先）
+    #[arg(long, value_name = "BACKEND", default_value = "auto")]
+    backend: BackendKind,
+
+    /// 忽略锁文件中的提交，重新解析各条目的引用并更新锁文件
+    #[arg(long, conflicts_with = "frozen")]
+    update: bool,
+
+    /// 锁文件必须覆盖全部条目且不会被修改，否则失败（用于 CI）
+    #[arg(long)]
+    frozen: bool,
+
+    /// 不在抓取的目录中写入来源记录（.git-get.json）
+    #[arg(long)]
+    no_metadata: bool,
+
+    /// 不使用本地对象缓存，在临时目录中重新拉取
+    #[arg(long)]
+    no_cache: bool,
+
+    /// 离线模式：只使用本地缓存中的仓库、引用与提交，不访问网络
+    #[arg(long, conflicts_with = "no_cache")]
+    offline: bool,
+}
+
+/// 单次抓取的参数
+#[derive(clap::Args, Debug)]
+struct FetchArgs {
+    /// 仓库网页 URL 或仓库标识
     /// 支持以下格式:
-    /// 1. 完整 GitHub URL: https://github.com/owner/repo/tree/branch/path/to/dir
-    /// 2. 简写: owner/repo
-    /// 3. 完整 Git URL: https://github.com/owner/repo.git
+    /// 1. 网页 URL: https://github.com/owner/repo/tree/branch/path/to/dir
+    ///    （也支持 /blob/ 文件 URL、raw.githubusercontent.com 文件 URL，
+    ///    以及 GitLab、Gitea/Forgejo/Codeberg、Bitbucket 的目录与文件 URL）
+    /// 2. 简写: owner/repo（默认 GitHub，可在配置中改为其他主机）、
+    ///    gitlab:group/sub/proj、bitbucket:owner/repo、codeberg:owner/repo
+    /// 3. 完整 Git URL: https://github.com/owner/repo.git、git@github.com:owner/repo.git、ssh://host/repo.git
+    /// 4. 本地仓库: file:///path/to/repo 或 ./path/to/repo（工作区或裸仓库）
     #[arg(short, long)]
     repo: Option<String>,
 
-    /// 分支名（当使用简写格式时可指定，URL 格式时会自动提取）
-    #[arg(short, long)]
-    branch: Option<String>,
+    /// 要抓取的引用：分支、标签、完整或缩写的提交 SHA，或任意引用（如 refs/pull/123/head）
+    /// URL 格式时会自动提取；均未提供时使用远程仓库的默认分支
+    #[arg(short = 'b', long = "ref", visible_aliases = ["rev", "branch"], value_name = "REF")]
+    reference: Option<String>,
 
-    /// 仓库内的子目录路径（可选，URL 格式时会自动提取）
-    #[arg(short, long)]
-    path: Option<String>,
+    /// 仓库内的子目录或文件路径（可选，URL 格式时会自动提取）
+    /// 可重复指定以共用一次拉取抓取多个路径
+    #[arg(short, long, value_name = "PATH")]
+    path: Vec<String>,
 
-    /// 本地目标目录路径（可选，默认使用 path 的最后一段或仓库名）
+    /// 抓取 PATH 并写入 DEST（可重复指定），与 --path 一起共用一次拉取
+    #[arg(long, num_args = 2, value_names = ["PATH", "DEST"])]
+    path_dest: Vec<String>,
+
+    /// 本地目标路径（可选，默认使用 path 的最后一段或仓库名；
+    /// 抓取单个文件且目标为已存在的目录时，文件写入该目录；
+    /// 抓取多个路径时作为共同的根目录，各路径按仓库中的相对结构写入其中）
     #[arg(short, long)]
     dest: Option<String>,
 
-    /// GitHub 访问 token（预留，用于私有仓库）
+    /// 只抓取匹配该 glob 的文件（可重复指定），如 '**/*.rs'；模式相对抓取的目录
+    #[arg(long, value_name = "GLOB")]
+    include: Vec<String>,
+
+    /// 跳过匹配该 glob 的文件或目录（可重复指定），如 '*.md'、'target/'
+    #[arg(long, value_name = "GLOB")]
+    exclude: Vec<String>,
+
+    /// 访问 token，用于拉取私有仓库
+    /// 未指定时按平台读取环境变量：GitHub 为 GITHUB_TOKEN、GH_TOKEN，
+    /// GitLab 为 GITLAB_TOKEN，Gitea 为 GITEA_TOKEN，Bitbucket 为 BITBUCKET_TOKEN
     #[arg(long)]
     token: Option<String>,
 
-    /// GitHub URL（位置参数，可直接传入 URL 而不用 --repo）
+    /// 访问 SSH 地址时使用的私钥文件
+    #[arg(long, value_name = "PATH")]
+    ssh_key: Option<PathBuf>,
+
+    /// 抓取后端: git（系统 git + sparse-checkout）、archive（GitHub 源码包）、
+    /// auto（有 git 时用 git，否则对 GitHub 仓库用 archive）
+    #[arg(long, value_name = "BACKEND", default_value = "auto")]
+    backend: BackendKind,
+
+    /// 不在抓取的目录中写入来源记录（.git-get.json）
+    #[arg(long)]
+    no_metadata: bool,
+
+    /// 不使用本地对象缓存（git 后端默认把对象缓存在用户缓存目录中，重复抓取同一仓库时只增量拉取）
+    #[arg(long)]
+    no_cache: bool,
+
+    /// 离线模式：只使用本地缓存中的仓库、引用与提交，不访问网络（需要先用 git-get prefetch 或联网抓取预热缓存）
+    #[arg(long, conflicts_with = "no_cache")]
+    offline: bool,
+
+    /// 目标路径已有内容时的处理方式: error（报错）、overwrite（只替换同名文件）、
+    /// merge（写入已有目录，同名文件保留原样、上游版本另存为 .rej）、backup（原有内容加时间戳移到一旁）、
+    /// skip-existing（跳过已存在的文件）。未指定且在终端中运行时会询问
+    #[arg(long, value_name = "POLICY")]
+    on_conflict: Option<OnConflict>,
+
+    /// 仓库网页 URL（位置参数，可直接传入 URL 而不用 --repo）
     /// 例如: git-get https://github.com/owner/repo/tree/main/examples/servers
     #[arg(value_name = "URL")]
     url: Option<String>,
 }
 
-/// 从 GitHub URL 解析出的信息
-#[derive(Debug)]
-struct ParsedGitHubUrl {
-    repo: String,
-    branch: Option<String>,
-    path: Option<String>,
-}
-
 fn main() {
     if let Err(e) = run() {
         eprintln!("❌ 错误: {:#}", e);
@@ -62,329 +252,468 @@ fn main() {
 }
 
 fn run() -> Result<()> {
-    let args = Args::parse();
-
-    // 解析输入，获取 repo、branch、path
-    let (repo, branch, path) = parse_input(&args)?;
-
-    // 决定目标路径（如果未提供，使用 path 的最后一段或仓库名）
-    let dest = args.dest.unwrap_or_else(|| {
-        if let Some(path) = path.as_deref() {
-            path.split('/')
-                .last()
-                .unwrap_or("download")
-                .to_string()
-        } else {
-            repo.split('/')
-                .last()
-                .unwrap_or("download")
-                .trim_end_matches(".git")
-                .to_string()
-        }
-    });
-
-    // 验证并构建仓库 URL
-    let repo_url = build_repo_url(&repo)?;
-    println!("📦 仓库: {}", repo_url);
-    println!("🌿 分支: {}", branch);
-    if let Some(path) = path.as_deref() {
-        println!("📁 子目录: {}", path);
-    } else {
-        println!("📁 子目录: <整个仓库>");
+    let cli = Cli::parse();
+
+    // 加载用户配置（自定义主机等），配置有误时直接报错
+    Config::init()?;
+    // Ctrl-C 中断时删除临时克隆与暂存目录
+    git_get::cleanup::install_signal_handler()?;
+
+    match &cli.command {
+        Some(Command::Sync(args)) => run_sync(args),
+        Some(Command::Update(args)) => run_update(args),
+        Some(Command::Cache(command)) => run_cache(command),
+        Some(Command::Prefetch(args)) => run_prefetch(args),
+        None => run_fetch(&cli.fetch),
     }
-    println!("📍 目标路径: {}", dest);
-
-    // 检查目标路径安全性
-    let dest_path = PathBuf::from(&dest);
-    check_dest_path_safety(&dest_path, &dest)?;
+}
 
-    // 创建临时目录（作用域结束自动清理）
-    let temp_dir = TempDir::new().context("无法创建临时目录")?;
-    let temp_path = temp_dir.path();
-    println!("🔧 临时目录: {}", temp_path.display());
+/// 单次抓取
+fn run_fetch(args: &FetchArgs) -> Result<()> {
+    // 解析输入，构造抓取请求
+    let (request, targets) = parse_input(args)?;
+    let request = request.progress(Progress::stdout());
 
-    // 在临时目录中克隆仓库：有 path 时仅拉取子目录；无 path 时拉取整个仓库
-    clone_repository(temp_path, &repo_url, &branch, path.as_deref(), args.token.as_deref())?;
+    println!("📦 仓库: {}", request.repo_url()?);
+    println!(
+        "🌿 引用: {}",
+        request.requested_reference().unwrap_or("<自动确定>")
+    );
 
-    // 确定源路径
-    let source_path = if let Some(path) = path.as_deref() {
-        let source_path = temp_path.join(path);
-        if !source_path.exists() {
-            bail!(
-                "远程仓库中未找到指定子目录: {}",
-                path
-            );
-        }
-        source_path
+    let outcomes = if targets.is_empty() {
+        vec![fetch(&request)?]
     } else {
-        temp_path.to_path_buf()
+        let outcomes = fetch_many(&request, &targets)?;
+        println!();
+        outcomes
     };
+    for outcome in &outcomes {
+        report_fetch(outcome)?;
+    }
+
+    let conflicts = outcomes
+        .iter()
+        .flat_map(|outcome| &outcome.collisions)
+        .filter(|collision| collision.action == CollisionAction::SavedAsRej)
+        .count();
+    if conflicts > 0 {
+        bail!("{} 个文件与已有文件内容不同，上游版本已另存为 .rej，请手动处理", conflicts);
+    }
+    Ok(())
+}
 
-    // 复制子目录到目标路径
-    copy_directory(&source_path, &dest_path)?;
+/// 输出一个路径的抓取结果，并把目标路径加入 .gitignore
+fn report_fetch(outcome: &FetchOutcome) -> Result<()> {
+    println!(
+        "📌 提交: {} @ {} ({} 个文件, {} 字节)",
+        outcome.commit, outcome.reference, outcome.files_written, outcome.bytes_written
+    );
+
+    for collision in &outcome.collisions {
+        match &collision.saved_as {
+            Some(saved_as) => {
+                println!("  ! {}（{} {}）", collision.path, collision.action, saved_as)
+            }
+            None => println!("  ! {}（{}）", collision.path, collision.action),
+        }
+    }
+    if let Some(metadata) = &outcome.metadata {
+        println!("🧾 来源记录: {}", metadata.display());
+    }
 
-    if path.is_some() {
-        println!("✅ 完成! 子目录已复制到: {}", dest);
+    if outcome.kind == EntryKind::Blob {
+        println!("✅ 完成! 文件已写入: {}", outcome.dest.display());
+    } else if outcome.path.is_some() {
+        println!("✅ 完成! 子目录已复制到: {}", outcome.dest.display());
     } else {
-        println!("✅ 完成! 仓库已复制到: {}", dest);
+        println!("✅ 完成! 仓库已复制到: {}", outcome.dest.display());
     }
 
     // 尝试添加到 .gitignore
-    add_to_gitignore(&dest)?;
+    add_to_gitignore(&outcome.dest.to_string_lossy())?;
 
-    // temp_dir 在此处被 drop，自动清理
     Ok(())
 }
 
 /// 解析用户输入，支持两种模式：
 /// 1. URL 模式：从完整的 GitHub URL 中提取信息
-/// 2. 分散参数模式：使用 --repo, --branch, --path 参数
-fn parse_input(args: &Args) -> Result<(String, String, Option<String>)> {
+/// 2. 分散参数模式：使用 --repo, --ref, --path 参数
+///
+/// 指定了多个 --path（或 --path-dest）时返回各个目标，否则目标列表为空。
+fn parse_input(args: &FetchArgs) -> Result<(FetchRequest, Vec<FetchTarget>)> {
     // 优先使用位置参数 URL
-    let input_url = args.url.as_ref().or(args.repo.as_ref());
-
-    if let Some(url) = input_url {
-        // 尝试解析 GitHub URL
-        if url.contains("github.com") && url.contains("/tree/") {
-            let parsed = parse_github_url(url)?;
-            
-            let repo = parsed.repo;
-            let branch = args.branch.clone()
-                .or(parsed.branch)
-                .unwrap_or_else(|| "main".to_string());
-            let path = args.path.clone().or(parsed.path);
-            
-            return Ok((repo, branch, path));
+    let Some(url) = args.url.as_ref().or(args.repo.as_ref()) else {
+        // 如果没有提供任何输入
+        bail!("缺少输入！请提供仓库 URL 或使用 --repo 参数\n\n使用示例:\n  git-get https://github.com/owner/repo/tree/main/path/to/dir\n  git-get --repo owner/repo --path path/to/dir");
+    };
+
+    // 尝试按托管平台解析网页 URL，否则作为 repo 参数处理；
+    // URL 中的 "<ref>/<path>" 留待抓取时根据远程引用拆分
+    let mut request = FetchRequest::from_source(url)?
+        .backend(args.backend)
+        .metadata(!args.no_metadata)
+        .cache(!args.no_cache)
+        .offline(args.offline);
+
+    // 未指定处理方式时，在终端中询问；非交互环境保持报错
+    match args.on_conflict {
+        Some(policy) => request = request.on_conflict(policy),
+        None if std::io::stdin().is_terminal() => {
+            request = request.conflict_prompt(ConflictPrompt::new(prompt_on_conflict));
         }
-        
-        // 否则作为 repo 参数处理
-        let repo = url.clone();
-        let branch = args.branch.clone().unwrap_or_else(|| "main".to_string());
-        let path = args.path.clone();
-        
-        return Ok((repo, branch, path));
+        None => {}
     }
 
-    // 如果没有提供任何输入
-    bail!("缺少输入！请提供 GitHub URL 或使用 --repo 参数\n\n使用示例:\n  git-get https://github.com/owner/repo/tree/main/path/to/dir\n  git-get --repo owner/repo --path path/to/dir");
-}
-
-/// 解析 GitHub URL，提取 repo、branch 和 path
-/// 支持格式: https://github.com/owner/repo/tree/branch/path/to/dir
-fn parse_github_url(url: &str) -> Result<ParsedGitHubUrl> {
-    // 移除末尾的斜杠
-    let url = url.trim_end_matches('/');
-    
-    // 检查是否包含 github.com
-    if !url.contains("github.com") {
-        bail!("不是有效的 GitHub URL: {}", url);
+    if let Some(reference) = &args.reference {
+        request = request.reference(reference);
     }
-
-    // 提取 github.com 后面的部分
-    let parts: Vec<&str> = url.split("github.com/").collect();
-    if parts.len() != 2 {
-        bail!("无法解析 GitHub URL: {}", url);
+    // 路径与目标路径分成两个参数，其中的 `:` 不会被误当作分隔符
+    let mut targets: Vec<FetchTarget> = args
+        .path
+        .iter()
+        .map(|path| FetchTarget {
+            path: path.clone(),
+            dest: None,
+        })
+        .chain(args.path_dest.chunks(2).map(|pair| FetchTarget {
+            path: pair[0].clone(),
+            dest: Some(PathBuf::from(&pair[1])),
+        }))
+        .collect();
+    if targets.len() == 1 {
+        let target = targets.remove(0);
+        request = request.path(target.path);
+        match (target.dest, &args.dest) {
+            (Some(_), Some(_)) => bail!("--path-dest 已指定目标路径，不能再使用 --dest"),
+            (Some(dest), None) => request = request.dest(dest),
+            (None, Some(dest)) => request = request.dest(dest),
+            (None, None) => {}
+        }
+    } else if let Some(root) = &args.dest {
+        // 多个路径共用一个根目录，按仓库中的相对结构写入
+        for target in targets.iter_mut().filter(|target| target.dest.is_none()) {
+            target.dest = Some(Path::new(root).join(target.path.trim_matches('/')));
+        }
     }
-
-    let path_part = parts[1];
-    let segments: Vec<&str> = path_part.split('/').collect();
-
-    // 至少需要 owner/repo
-    if segments.len() < 2 {
-        bail!("URL 格式错误，无法提取仓库信息: {}", url);
+    for pattern in &args.include {
+        request = request.include(pattern);
     }
+    for pattern in &args.exclude {
+        request = request.exclude(pattern);
+    }
+    if let Some(token) = &args.token {
+        request = request.token(token);
+    }
+    if let Some(key) = &args.ssh_key {
+        request = request.ssh_key(key);
+    }
+    Ok((request, targets))
+}
 
-    let owner = segments[0];
-    let repo_name = segments[1].trim_end_matches(".git");
-    let repo = format!("{}/{}", owner, repo_name);
-
-    // 检查是否包含 /tree/ 或 /blob/
-    let mut branch = None;
-    let mut path = None;
+/// 在终端中询问目标路径已有内容时的处理方式
+fn prompt_on_conflict(dest: &Path) -> Result<OnConflict> {
+    println!("⚠️  目标路径已有内容: {}", dest.display());
+    println!("   [e] 放弃（error）  [o] 只替换同名文件（overwrite）  [m] 写入新文件（merge）");
+    println!("   [b] 备份后重新写入（backup）  [s] 跳过已存在的文件（skip-existing）");
+    loop {
+        print!("请选择 [e/o/m/b/s]（默认 e）: ");
+        std::io::stdout().flush()?;
+        let mut answer = String::new();
+        if std::io::stdin().read_line(&mut answer)? == 0 {
+            return Ok(OnConflict::Error);
+        }
+        let policy = match answer.trim() {
+            "" | "e" => Ok(OnConflict::Error),
+            "o" => Ok(OnConflict::Overwrite),
+            "m" => Ok(OnConflict::Merge),
+            "b" => Ok(OnConflict::Backup),
+            "s" => Ok(OnConflict::SkipExisting),
+            other => other.parse(),
+        };
+        match policy {
+            Ok(policy) => return Ok(policy),
+            Err(e) => println!("{}", e),
+        }
+    }
+}
 
-    if segments.len() > 2 {
-        if segments[2] == "tree" || segments[2] == "blob" {
-            if segments.len() > 3 {
-                branch = Some(segments[3].to_string());
-                
-                // 如果有更多段，组合成路径
-                if segments.len() > 4 {
-                    path = Some(segments[4..].join("/"));
+/// 按清单同步全部条目，有条目失败时返回错误
+fn run_sync(args: &SyncArgs) -> Result<()> {
+    let manifest = Manifest::load(&args.manifest)?;
+    println!(
+        "📋 清单: {} ({} 个条目)",
+        args.manifest.display(),
+        manifest.entries.len()
+    );
+
+    let options = FetchOptions {
+        token: args.token.clone(),
+        backend: args.backend,
+        progress: Progress::stdout(),
+        metadata: !args.no_metadata,
+        cache: !args.no_cache,
+        offline: args.offline,
+        ..FetchOptions::default()
+    };
+    let mode = if args.frozen {
+        LockMode::Frozen
+    } else if args.update {
+        LockMode::Update
+    } else {
+        LockMode::Locked
+    };
+    let report = sync(&manifest, &options, mode)?;
+
+    let mut fetched = 0;
+    let mut updated = 0;
+    let mut up_to_date = 0;
+    let mut failed = Vec::new();
+    let mut conflicted = Vec::new();
+    for result in &report.results {
+        match &result.status {
+            SyncStatus::Fetched(outcome) => {
+                fetched += 1;
+                println!(
+                    "📌 {}: {} @ {}",
+                    result.dest.display(),
+                    outcome.commit,
+                    outcome.reference
+                );
+            }
+            SyncStatus::Updated(outcome) => {
+                updated += 1;
+                if !outcome.conflicts.is_empty() {
+                    conflicted.push(result.dest.display().to_string());
                 }
+                println!(
+                    "📌 {}: {} → {}（新增 {}、修改 {}、删除 {}、合并 {}、冲突 {}）",
+                    result.dest.display(),
+                    short_commit(&outcome.previous_commit),
+                    outcome.commit,
+                    outcome.added.len(),
+                    outcome.changed.len(),
+                    outcome.removed.len(),
+                    outcome.merged.len(),
+                    outcome.conflicts.len()
+                );
             }
+            SyncStatus::UpToDate => up_to_date += 1,
+            SyncStatus::Failed(_) => failed.push(result.dest.display().to_string()),
         }
     }
 
-    Ok(ParsedGitHubUrl {
-        repo,
-        branch,
-        path,
-    })
-}
-
-/// 检查目标路径的安全性
-/// 只允许不存在的路径或空目录，防止覆盖已有文件造成数据损失
-fn check_dest_path_safety(dest_path: &Path, dest_str: &str) -> Result<()> {
-    // 如果路径不存在，直接返回（安全）
-    if !dest_path.exists() {
-        return Ok(());
+    println!(
+        "\n✅ 同步完成: {} 个已抓取, {} 个已更新, {} 个已是最新, {} 个失败",
+        fetched,
+        updated,
+        up_to_date,
+        failed.len()
+    );
+    if !failed.is_empty() {
+        bail!("以下条目同步失败: {}", failed.join(", "));
     }
-
-    // 如果存在但不是目录，报错
-    if !dest_path.is_dir() {
-        bail!(
-            "目标路径已存在且不是目录: {}",
-            dest_str
-        );
+    if !conflicted.is_empty() {
+        bail!("以下条目合并本地修改时存在冲突，请手动处理: {}", conflicted.join(", "));
     }
-
-    // 检查目录是否为空
-    let entries = std::fs::read_dir(dest_path)
-        .with_context(|| format!("无法读取目标目录: {}", dest_str))?;
-
-    // 如果目录包含任何内容，报错
-    if entries.count() > 0 {
-        bail!(
-            "目标目录已存在且不为空: {}\n提示: 为了安全起见，git-get 只能写入空目录或不存在的目录",
-            dest_str
-        );
-    }
-
-    // 目录存在但为空，安全
     Ok(())
 }
 
-/// 将 repo 参数转换为完整的 Git URL
-fn build_repo_url(repo: &str) -> Result<String> {
-    // 已经是完整 URL
-    if repo.starts_with("https://") || repo.starts_with("git@") {
-        return Ok(repo.to_string());
+/// 原地更新之前抓取的目录
+fn run_update(args: &UpdateArgs) -> Result<()> {
+    let mut request = update_request(&args.dest)?
+        .backend(args.backend)
+        .cache(!args.no_cache)
+        .offline(args.offline)
+        .progress(Progress::stdout());
+    if let Some(reference) = &args.reference {
+        request = request.reference(reference);
     }
-
-    // owner/repo 格式
-    let parts: Vec<&str> = repo.split('/').collect();
-    if parts.len() == 2 && !parts[0].is_empty() && !parts[1].is_empty() {
-        return Ok(format!("https://github.com/{}.git", repo));
+    if let Some(token) = &args.token {
+        request = request.token(token);
     }
+    if let Some(key) = &args.ssh_key {
+        request = request.ssh_key(key);
+    }
+    println!("📦 仓库: {}", request.repo_url()?);
 
-    Err(anyhow!(
-        "无效的仓库格式: {}。支持格式: owner/repo 或 https://github.com/owner/repo.git",
-        repo
-    ))
+    let outcome = update(&request)?;
+    print_update_summary(&outcome);
+    if !outcome.conflicts.is_empty() {
+        bail!("{} 个文件存在冲突，请手动处理后再继续", outcome.conflicts.len());
+    }
+    Ok(())
 }
 
-/// 在临时目录中克隆仓库
-/// - subdir 为 Some 时：使用 sparse-checkout 仅拉取指定子目录
-/// - subdir 为 None 时：拉取整个仓库
-fn clone_repository(
-    temp_dir: &Path,
-    repo_url: &str,
-    branch: &str,
-    subdir: Option<&str>,
-    _token: Option<&str>,
-) -> Result<()> {
-    println!("📥 正在初始化仓库...");
-
-    // 1. git init
-    run_git_command(temp_dir, &["init"])?;
-
-    // 2. git remote add origin <url>
-    run_git_command(temp_dir, &["remote", "add", "origin", repo_url])?;
-
-    if let Some(subdir) = subdir {
-        // 3. 启用 sparse-checkout
-        run_git_command(temp_dir, &["config", "core.sparseCheckout", "true"])?;
-
-        // 4. 配置 sparse-checkout 路径
-        let sparse_checkout_path = temp_dir.join(".git/info/sparse-checkout");
-        std::fs::create_dir_all(sparse_checkout_path.parent().unwrap())?;
-        std::fs::write(&sparse_checkout_path, format!("{}\n", subdir))
-            .context("无法写入 sparse-checkout 配置")?;
-
-        println!("📥 正在拉取仓库（仅获取指定子目录）...");
-    } else {
-        println!("📥 正在拉取仓库（完整仓库）...");
-    }
+/// 把指定仓库（或清单中的全部条目）拉取到本地缓存，有仓库失败时返回错误
+fn run_prefetch(args: &PrefetchArgs) -> Result<()> {
+    let options = FetchOptions {
+        token: args.token.clone(),
+        ssh_key: args.ssh_key.clone(),
+        progress: Progress::stdout(),
+        cache: true,
+        ..FetchOptions::default()
+    };
 
-    // 5. git fetch --depth=1 origin <branch>
-    let fetch_result = run_git_command(temp_dir, &["fetch", "--depth=1", "origin", branch]);
-    
-    // 如果指定分支失败，尝试 master
-    if fetch_result.is_err() && branch == "main" {
-        println!("⚠️  分支 'main' 不存在，尝试 'master'...");
-        run_git_command(temp_dir, &["fetch", "--depth=1", "origin", "master"])
-            .context("无法拉取仓库，请检查仓库地址和分支名是否正确")?;
-        run_git_command(temp_dir, &["checkout", "FETCH_HEAD"])?;
+    // 每个请求都固定使用 git 后端，只有它会写入缓存
+    let mut requests = Vec::new();
+    if args.sources.is_empty() {
+        let manifest = Manifest::load(&args.manifest)?;
+        let lock = Lockfile::load(&Lockfile::path_for(&manifest.path))?;
+        println!(
+            "📋 清单: {} ({} 个条目)",
+            args.manifest.display(),
+            manifest.entries.len()
+        );
+        for entry in &manifest.entries {
+            let mut request = manifest.request_for(entry, &options)?;
+            if let Some(locked) = lock.find(entry) {
+                request = request.reference(&locked.commit);
+                if let Some(path) = &locked.path {
+                    request = request.path(path);
+                }
+            }
+            requests.push(request.backend(BackendKind::Git));
+        }
     } else {
-        fetch_result.context("无法拉取仓库，请检查仓库地址和分支名是否正确")?;
-        // 6. git checkout FETCH_HEAD
-        run_git_command(temp_dir, &["checkout", "FETCH_HEAD"])?;
+        for source in &args.sources {
+            let mut request = FetchRequest::from_source(source)?
+                .options(options.clone())
+                .backend(BackendKind::Git);
+            if let Some(reference) = &args.reference {
+                request = request.reference(reference);
+            }
+            if args.path.is_empty() {
+                requests.push(request);
+            } else {
+                requests.extend(args.path.iter().map(|path| request.clone().path(path)));
+            }
+        }
     }
 
-    println!("📥 拉取完成");
-    Ok(())
-}
-
-/// 执行 git 命令并检查结果
-fn run_git_command(working_dir: &Path, args: &[&str]) -> Result<()> {
-    let output = Command::new("git")
-        .current_dir(working_dir)
-        .args(args)
-        .output()
-        .with_context(|| format!("无法执行 git 命令: git {}", args.join(" ")))?;
-
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        bail!(
-            "git {} 执行失败: {}",
-            args.join(" "),
-            stderr.trim()
+    let mut failed = Vec::new();
+    for request in &requests {
+        println!(
+            "\n📦 仓库: {} 路径: {}",
+            request.repo_url()?,
+            request.subpath().unwrap_or("<URL 中的路径或整个仓库>")
         );
+        match request.prefetch() {
+            Ok(resolution) => println!(
+                "✅ 已缓存: {} @ {}",
+                resolution.reference,
+                short_commit(&resolution.commit)
+            ),
+            Err(e) => {
+                println!("❌ 预热失败: {:#}", e);
+                failed.push(request.repo().to_string());
+            }
+        }
     }
 
+    println!(
+        "\n✅ 预热完成: {} 个成功, {} 个失败",
+        requests.len() - failed.len(),
+        failed.len()
+    );
+    if !failed.is_empty() {
+        bail!("以下仓库预热失败: {}", failed.join(", "));
+    }
     Ok(())
 }
 
-/// 递归复制目录，排除 .git 目录
-fn copy_directory(src: &Path, dest: &Path) -> Result<()> {
-    println!("📋 正在复制文件...");
-
-    // 创建目标目录
-    std::fs::create_dir_all(dest)
-        .with_context(|| format!("无法创建目标目录: {}", dest.display()))?;
-
-    copy_dir_recursive(src, dest)?;
-
+/// 查看或清理本地对象缓存
+fn run_cache(command: &CacheCommand) -> Result<()> {
+    match command {
+        CacheCommand::List => {
+            let entries = cache::list()?;
+            println!("🗃️  缓存目录: {}", cache::cache_dir()?.display());
+            let now = SystemTime::now();
+            for entry in &entries {
+                let idle = now.duration_since(entry.last_used).unwrap_or_default();
+                let idle = Duration::from_secs(idle.as_secs());
+                println!(
+                    "  {}  {}（{} 前使用）",
+                    entry.url,
+                    cache::format_size(entry.size),
+                    humantime::format_duration(idle)
+                );
+            }
+            let total: u64 = entries.iter().map(|entry| entry.size).sum();
+            println!(
+                "📊 共 {} 个仓库，{}（上限 {}）",
+                entries.len(),
+                cache::format_size(total),
+                cache::format_size(cache::max_size()?)
+            );
+        }
+        CacheCommand::Gc { max_size } => {
+            let max_size = match max_size {
+                Some(size) => cache::parse_size(size)?,
+                None => cache::max_size()?,
+            };
+            let removed = cache::gc(max_size, None)?;
+            print_removed(&removed);
+        }
+        CacheCommand::Clear => {
+            let removed = cache::clear()?;
+            print_removed(&removed);
+        }
+    }
     Ok(())
 }
 
-/// 递归复制目录内容，跳过 .git 目录
-fn copy_dir_recursive(src: &Path, dest: &Path) -> Result<()> {
-    for entry in std::fs::read_dir(src)
-        .with_context(|| format!("无法读取目录: {}", src.display()))?
-    {
-        let entry = entry?;
-        let file_name = entry.file_name();
-        let file_name_str = file_name.to_string_lossy();
-
-        // 跳过 .git 目录
-        if file_name_str == ".git" {
-            continue;
-        }
-
-        let src_path = entry.path();
-        let dest_path = dest.join(&file_name);
+/// 输出被删除的缓存仓库
+fn print_removed(removed: &[cache::CacheEntry]) {
+    for entry in removed {
+        println!("  - {}（{}）", entry.url, cache::format_size(entry.size));
+    }
+    let total: u64 = removed.iter().map(|entry| entry.size).sum();
+    println!(
+        "✅ 已删除 {} 个缓存仓库，释放 {}",
+        removed.len(),
+        cache::format_size(total)
+    );
+}
 
-        if src_path.is_dir() {
-            std::fs::create_dir_all(&dest_path)?;
-            copy_dir_recursive(&src_path, &dest_path)?;
-        } else {
-            std::fs::copy(&src_path, &dest_path)
-                .with_context(|| format!("无法复制文件: {}", src_path.display()))?;
+/// 输出原地更新的文件变化
+fn print_update_summary(outcome: &UpdateOutcome) {
+    for path in &outcome.added {
+        println!("  + {}", path);
+    }
+    for path in &outcome.changed {
+        println!("  ~ {}", path);
+    }
+    for path in &outcome.removed {
+        println!("  - {}", path);
+    }
+    for path in &outcome.merged {
+        println!("  M {}（已合并本地修改）", path);
+    }
+    for conflict in &outcome.conflicts {
+        match &conflict.saved_as {
+            Some(saved_as) => println!("  ! {}（{} {}）", conflict.path, conflict.kind, saved_as),
+            None => println!("  ! {}（{}）", conflict.path, conflict.kind),
         }
     }
+    if outcome.is_unchanged() {
+        println!("✅ 已是最新: {} @ {}", outcome.dest.display(), outcome.commit);
+    } else {
+        println!(
+            "✅ 更新完成: {} → {}，新增 {} 个、修改 {} 个、删除 {} 个、合并 {} 个文件，{} 个冲突",
+            short_commit(&outcome.previous_commit),
+            short_commit(&outcome.commit),
+            outcome.added.len(),
+            outcome.changed.len(),
+            outcome.removed.len(),
+            outcome.merged.len(),
+            outcome.conflicts.len()
+        );
+    }
+}
 
-    Ok(())
+/// 提交 SHA 的缩写
+fn short_commit(commit: &str) -> &str {
+    &commit[..commit.len().min(12)]
 }
 
 /// 添加目标路径到 .gitignore 文件
@@ -441,3 +770,73 @@ fn add_to_gitignore(dest_path: &str) -> Result<()> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<(FetchRequest, Vec<FetchTarget>)> {
+        let cli = Cli::try_parse_from([&["git-get", "owner/repo"], args].concat())?;
+        parse_input(&cli.fetch)
+    }
+
+    fn target(path: &str, dest: &str) -> FetchTarget {
+        FetchTarget {
+            path: path.to_string(),
+            dest: Some(PathBuf::from(dest)),
+        }
+    }
+
+    #[test]
+    fn single_path_sets_the_request_path() {
+        let (request, targets) = parse(&["-p", "examples/a:b", "-d", "out"]).unwrap();
+        assert!(targets.is_empty());
+        assert_eq!(request.subpath(), Some("examples/a:b"));
+        assert_eq!(request.destination(), Some(Path::new("out")));
+
+        // 路径与目标路径中的 `:` 都原样保留
+        let (request, targets) = parse(&["--path-dest", "docs/a:b.md", "C:\\out\\a.md"]).unwrap();
+        assert!(targets.is_empty());
+        assert_eq!(request.subpath(), Some("docs/a:b.md"));
+        assert_eq!(request.destination(), Some(Path::new("C:\\out\\a.md")));
+
+        assert!(parse(&["--path-dest", "docs", "out", "-d", "other"]).is_err());
+        assert!(parse(&["--path-dest", "docs"]).is_err());
+    }
+
+    #[test]
+    fn multiple_paths_become_targets() {
+        let (_, targets) = parse(&["-p", "examples/servers", "-p", "/docs/", "-d", "vendor"]).unwrap();
+        assert_eq!(
+            targets,
+            [target("examples/servers", "vendor/examples/servers"), target("/docs/", "vendor/docs")]
+        );
+
+        let (_, targets) = parse(&[
+            "-p",
+            "examples",
+            "--path-dest",
+            "a:b",
+            "C:\\x",
+            "--path-dest",
+            "docs/guide.md",
+            "vendor/GUIDE.md",
+        ])
+        .unwrap();
+        assert_eq!(
+            targets,
+            [
+                FetchTarget {
+                    path: "examples".to_string(),
+                    dest: None,
+                },
+                target("a:b", "C:\\x"),
+                target("docs/guide.md", "vendor/GUIDE.md"),
+            ]
+        );
+
+        // 有单独目标路径的条目不受 --dest 影响
+        let (_, targets) = parse(&["-p", "examples", "--path-dest", "docs", "out", "-d", "vendor"]).unwrap();
+        assert_eq!(targets, [target("examples", "vendor/examples"), target("docs", "out")]);
+    }
+}
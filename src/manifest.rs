@@ -0,0 +1,149 @@
+//! 项目级清单 `git-get.toml`：声明需要抓取的条目，由 `git-get sync` 统一落盘
+//!
+//! ```toml
+//! [[entry]]
+//! source = "https://github.com/owner/repo/tree/main/examples/servers"
+//! dest = "vendor/servers"
+//!
+//! [[entry]]
+//! source = "gitlab:group/templates"
+//! ref = "v1.2.0"
+//! path = "ci"
+//! dest = "vendor/ci"
+//! exclude = ["*.md", "fixtures/"]
+//! backend = "git"
+//! token_env = "CORP_GITLAB_TOKEN"
+//! ```
+//!
+//! 清单中的相对路径（dest、ssh_key 与本地仓库 source）均相对清单所在目录。
+
+use crate::backend::BackendKind;
+use crate::fetch::{FetchOptions, FetchRequest};
+use crate::repo::expand_home;
+use anyhow::{anyhow, bail, Context, Result};
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+/// 默认的清单文件名
+pub const MANIFEST_FILE: &str = "git-get.toml";
+
+/// 清单文件
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Manifest {
+    /// 清单中的条目
+    #[serde(default, rename = "entry")]
+    pub entries: Vec<ManifestEntry>,
+    /// 清单文件路径
+    #[serde(skip)]
+    pub path: PathBuf,
+    /// 清单所在目录，相对路径以此为基准
+    #[serde(skip)]
+    pub base_dir: PathBuf,
+}
+
+/// 清单中的一个条目
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ManifestEntry {
+    /// 仓库参数或网页 URL，与命令行的位置参数相同
+    pub source: String,
+    /// 引用（分支、标签或提交 SHA），未指定时使用 URL 中的引用或远程默认分支
+    #[serde(default, rename = "ref")]
+    pub reference: Option<String>,
+    /// 仓库内的子目录或文件
+    #[serde(default)]
+    pub path: Option<String>,
+    /// 本地目标路径
+    pub dest: PathBuf,
+    /// 只抓取匹配这些 glob 的文件
+    #[serde(default)]
+    pub include: Vec<String>,
+    /// 跳过匹配这些 glob 的文件或目录
+    #[serde(default)]
+    pub exclude: Vec<String>,
+    /// 抓取后端（auto、git、archive、gix）
+    #[serde(default)]
+    pub backend: Option<String>,
+    /// 从哪个环境变量读取 token（不要把 token 直接写进清单）
+    #[serde(default)]
+    pub token_env: Option<String>,
+    /// 访问 SSH 地址时使用的私钥
+    #[serde(default)]
+    pub ssh_key: Option<PathBuf>,
+}
+
+impl Manifest {
+    /// 读取并校验清单文件
+    pub fn load(path: &Path) -> Result<Manifest> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("无法读取清单文件: {}", path.display()))?;
+        let mut manifest: Manifest = toml::from_str(&content)
+            .with_context(|| format!("清单文件格式错误: {}", path.display()))?;
+        manifest.path = path.to_path_buf();
+        manifest.base_dir = path
+            .parent()
+            .filter(|dir| !dir.as_os_str().is_empty())
+            .unwrap_or(Path::new("."))
+            .to_path_buf();
+
+        let mut seen = HashSet::new();
+        for entry in &manifest.entries {
+            if entry.dest.as_os_str().is_empty() {
+                bail!("清单条目缺少 dest: {}", entry.source);
+            }
+            if !seen.insert(&entry.dest) {
+                bail!("清单中有多个条目写入同一目标路径: {}", entry.dest.display());
+            }
+        }
+        Ok(manifest)
+    }
+
+    /// 条目目标路径（相对清单目录解析）
+    pub fn dest_of(&self, entry: &ManifestEntry) -> PathBuf {
+        self.base_dir.join(&entry.dest)
+    }
+
+    /// 把条目转换为抓取请求，条目中的设置覆盖 base 中的同名选项
+    pub fn request_for(&self, entry: &ManifestEntry, base: &FetchOptions) -> Result<FetchRequest> {
+        // 以 ./ 或 ../ 开头的本地仓库相对清单目录
+        let source = if entry.source.starts_with("./") || entry.source.starts_with("../") {
+            self.base_dir.join(&entry.source).to_string_lossy().into_owned()
+        } else {
+            entry.source.clone()
+        };
+
+        let mut request = FetchRequest::from_source(&source)?
+            .options(base.clone())
+            .dest(self.dest_of(entry));
+        if let Some(reference) = &entry.reference {
+            request = request.reference(reference);
+        }
+        if let Some(path) = &entry.path {
+            request = request.path(path);
+        }
+        for pattern in &entry.include {
+            request = request.include(pattern);
+        }
+        for pattern in &entry.exclude {
+            request = request.exclude(pattern);
+        }
+        if let Some(backend) = &entry.backend {
+            let backend: BackendKind = backend
+                .parse()
+                .map_err(|e| anyhow!("清单条目 {} 的 backend 无效: {}", entry.dest.display(), e))?;
+            request = request.backend(backend);
+        }
+        if let Some(var) = &entry.token_env {
+            let token = std::env::var(var).with_context(|| {
+                format!("清单条目 {} 需要的环境变量 {} 未设置", entry.dest.display(), var)
+            })?;
+            request = request.token(token);
+        }
+        if let Some(key) = &entry.ssh_key {
+            request = request.ssh_key(self.base_dir.join(expand_home(&key.to_string_lossy())));
+        }
+        Ok(request)
+    }
+}
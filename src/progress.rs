@@ -0,0 +1,47 @@
+//! 进度输出
+//!
+//! 库本身不直接打印任何内容，而是把关键步骤的描述交给调用方，
+//! 由调用方决定输出到终端、日志还是直接丢弃。
+
+use std::fmt;
+use std::sync::Arc;
+
+/// 进度回调函数类型
+type Callback = dyn Fn(&str) + Send + Sync;
+
+/// 进度回调，每次接收一行人类可读的进度信息
+///
+/// 默认值会丢弃所有信息。
+#[derive(Clone, Default)]
+pub struct Progress(Option<Arc<Callback>>);
+
+impl Progress {
+    /// 使用自定义回调接收进度信息
+    pub fn new(callback: impl Fn(&str) + Send + Sync + 'static) -> Self {
+        Self(Some(Arc::new(callback)))
+    }
+
+    /// 将进度信息逐行打印到标准输出
+    pub fn stdout() -> Self {
+        Self::new(|message| println!("{}", message))
+    }
+
+    /// 丢弃所有进度信息
+    pub fn silent() -> Self {
+        Self(None)
+    }
+
+    pub(crate) fn emit(&self, message: impl AsRef<str>) {
+        if let Some(callback) = &self.0 {
+            callback(message.as_ref());
+        }
+    }
+}
+
+impl fmt::Debug for Progress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Progress")
+            .field(&if self.0.is_some() { "<callback>" } else { "<silent>" })
+            .finish()
+    }
+}
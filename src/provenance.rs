@@ -0,0 +1,203 @@
+//! 抓取来源记录 `.git-get.json`：写在抓取的目录中，说明文件来自哪个仓库的哪个提交
+//!
+//! 记录包含仓库 URL、请求与解析到的引用、提交 SHA、仓库内路径、抓取时间、
+//! git-get 版本，以及写入的每个文件的 SHA-256，供其他子命令和审计脚本使用。
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::Path;
+
+/// 来源记录的文件名
+pub const PROVENANCE_FILE: &str = ".git-get.json";
+
+/// 抓取来源记录
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Provenance {
+    /// 写入记录的 git-get 版本
+    pub tool_version: String,
+    /// Git 仓库 URL
+    pub source: String,
+    /// 请求的引用，未指定时为 null（使用远程默认分支）
+    pub reference: Option<String>,
+    /// 解析到的引用名
+    pub resolved: String,
+    /// 提交 SHA
+    pub commit: String,
+    /// 仓库内路径，null 表示整个仓库
+    pub path: Option<String>,
+    /// 路径对应的 tree 哈希
+    pub tree: String,
+    /// 抓取时使用的 include 过滤模式
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub include: Vec<String>,
+    /// 抓取时使用的 exclude 过滤模式
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub exclude: Vec<String>,
+    /// 抓取时间（UTC，RFC 3339）
+    pub fetched_at: String,
+    /// 写入的文件，按路径排序
+    pub files: Vec<FileRecord>,
+}
+
+/// 一个写入的文件
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileRecord {
+    /// 相对目标目录的路径，以 / 分隔
+    pub path: String,
+    /// 文件大小（字节）
+    pub size: u64,
+    /// 文件内容的 SHA-256（十六进制）
+    pub sha256: String,
+}
+
+impl Provenance {
+    /// 读取目录中的来源记录，不存在时返回 None
+    pub fn load(dir: &Path) -> Result<Option<Provenance>> {
+        let path = dir.join(PROVENANCE_FILE);
+        if !path.is_file() {
+            return Ok(None);
+        }
+        let content = std::fs::read_to_string(&path)
+            .with_context(|| format!("无法读取来源记录: {}", path.display()))?;
+        let provenance = serde_json::from_str(&content)
+            .with_context(|| format!("来源记录格式错误: {}", path.display()))?;
+        Ok(Some(provenance))
+    }
+
+    /// 把来源记录写入目录
+    pub fn save(&self, dir: &Path) -> Result<()> {
+        let path = dir.join(PROVENANCE_FILE);
+        let mut content = serde_json::to_string_pretty(self).context("无法生成来源记录")?;
+        content.push('\n');
+        std::fs::write(&path, content)
+            .with_context(|| format!("无法写入来源记录: {}", path.display()))
+    }
+}
+
+/// 当前时间（UTC，RFC 3339，精确到秒）
+pub(crate) fn now_rfc3339() -> String {
+    humantime::format_rfc3339_seconds(std::time::SystemTime::now()).to_string()
+}
+
+/// 计算文件内容的 SHA-256；符号链接计算链接目标路径本身（与 git 保存的内容一致）
+pub fn hash_file(path: &Path) -> Result<String> {
+    if path.is_symlink() {
+        let target = std::fs::read_link(path)
+            .with_context(|| format!("无法读取符号链接: {}", path.display()))?;
+        return Ok(format!("{:x}", Sha256::digest(link_bytes(&target))));
+    }
+    let mut file = std::fs::File::open(path)
+        .with_context(|| format!("无法读取文件: {}", path.display()))?;
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut file, &mut hasher)
+        .with_context(|| format!("无法读取文件: {}", path.display()))?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// 列出目录下的全部文件及其哈希（不含来源记录本身），按路径排序
+pub fn collect_files(dir: &Path) -> Result<Vec<FileRecord>> {
+    let mut files = Vec::new();
+    collect_recursive(dir, "", &mut files)?;
+    files.retain(|file| file.path != PROVENANCE_FILE);
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(files)
+}
+
+fn collect_recursive(dir: &Path, prefix: &str, files: &mut Vec<FileRecord>) -> Result<()> {
+    for entry in std::fs::read_dir(dir)
+        .with_context(|| format!("无法读取目录: {}", dir.display()))?
+    {
+        let entry = entry?;
+        let path = entry.path();
+        let relative = format!("{}{}", prefix, entry.file_name().to_string_lossy());
+        let metadata = std::fs::symlink_metadata(&path)
+            .with_context(|| format!("无法读取文件: {}", path.display()))?;
+        if metadata.is_dir() {
+            collect_recursive(&path, &format!("{}/", relative), files)?;
+        } else {
+            // 符号链接记录链接本身，不跟随到仓库之外
+            let size = if metadata.is_symlink() {
+                link_bytes(&std::fs::read_link(&path)?).len() as u64
+            } else {
+                metadata.len()
+            };
+            files.push(FileRecord {
+                path: relative,
+                size,
+                sha256: hash_file(&path)?,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// 符号链接目标路径的原始字节
+fn link_bytes(target: &Path) -> Vec<u8> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStrExt;
+        target.as_os_str().as_bytes().to_vec()
+    }
+    #[cfg(not(unix))]
+    {
+        target.to_string_lossy().into_owned().into_bytes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fetch::FetchRequest;
+    use crate::test_support::{git, FixtureRepo};
+    use tempfile::TempDir;
+
+    #[test]
+    fn fetched_directories_record_source_and_file_hashes() {
+        let repo = FixtureRepo::new();
+        repo.write("docs/guide.md", "guide\n")
+            .write("docs/img/logo.svg", "<svg/>\n")
+            .write("docs/debug.log", "noise\n")
+            .write("README.md", "readme\n");
+        let commit = repo.commit("init");
+        git(repo.path(), &["tag", "v1"]);
+        let tree = git(repo.path(), &["rev-parse", "HEAD:docs"]);
+        let out = TempDir::new().unwrap();
+        let dest = out.path().join("docs");
+
+        FetchRequest::new(repo.url())
+            .reference("v1")
+            .path("docs")
+            .dest(&dest)
+            .exclude("*.log")
+            .metadata(true)
+            .fetch()
+            .unwrap();
+
+        let provenance = Provenance::load(&dest).unwrap().unwrap();
+        assert_eq!(provenance.tool_version, env!("CARGO_PKG_VERSION"));
+        assert_eq!(provenance.source, repo.url());
+        assert_eq!(provenance.reference.as_deref(), Some("v1"));
+        assert_eq!(provenance.resolved, "v1");
+        assert_eq!(provenance.commit, commit);
+        assert_eq!(provenance.path.as_deref(), Some("docs"));
+        assert_eq!(provenance.tree, tree);
+        assert!(provenance.include.is_empty());
+        assert_eq!(provenance.exclude, vec!["*.log"]);
+        assert!(humantime::parse_rfc3339(&provenance.fetched_at).is_ok());
+
+        let paths: Vec<&str> = provenance.files.iter().map(|f| f.path.as_str()).collect();
+        assert_eq!(paths, vec!["guide.md", "img/logo.svg"]);
+        let guide = &provenance.files[0];
+        assert_eq!(guide.size, 6);
+        assert_eq!(guide.sha256, format!("{:x}", Sha256::digest(b"guide\n")));
+        assert_eq!(collect_files(&dest).unwrap(), provenance.files);
+
+        // 保存后再读取得到同样的记录，空的过滤模式不写入文件
+        let json = std::fs::read_to_string(dest.join(PROVENANCE_FILE)).unwrap();
+        assert!(!json.contains("\"include\""));
+        provenance.save(out.path()).unwrap();
+        assert_eq!(Provenance::load(out.path()).unwrap(), Some(provenance));
+        assert_eq!(Provenance::load(&dest.join("img")).unwrap(), None);
+    }
+}
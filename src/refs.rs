@@ -0,0 +1,187 @@
+//! 远程引用的匹配：把用户给出的 ref/rev 与远程公布的引用列表对应起来
+
+use anyhow::{bail, Result};
+
+/// 远程公布的一个引用
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RemoteRef {
+    /// 完整引用名，例如 refs/heads/main、refs/tags/v1.0
+    pub name: String,
+    /// 引用指向的提交（附注标签已剥离到提交）
+    pub commit: String,
+}
+
+/// 解析 `git ls-remote` 风格的输出（"<sha>\t<refname>"）
+///
+/// 附注标签的 `^{}` 剥离记录会被合并到对应标签上，使 commit 始终指向提交。
+pub fn parse_ls_remote(output: &str) -> Vec<RemoteRef> {
+    let mut refs: Vec<RemoteRef> = Vec::new();
+    for line in output.lines() {
+        let Some((commit, name)) = line.split_once('\t') else {
+            continue;
+        };
+        if let Some(tag) = name.strip_suffix("^{}") {
+            if let Some(existing) = refs.iter_mut().find(|r| r.name == tag) {
+                existing.commit = commit.to_string();
+            }
+            continue;
+        }
+        refs.push(RemoteRef {
+            name: name.to_string(),
+            commit: commit.to_string(),
+        });
+    }
+    refs
+}
+
+/// 判断输入是否形如（可能缩写的）提交 SHA
+pub fn is_hex_sha(rev: &str) -> bool {
+    (4..=40).contains(&rev.len()) && rev.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// 按 git 的查找顺序在远程引用中查找 rev：
+/// 完整引用名、refs/heads/、refs/tags/、refs/
+pub fn find_ref<'a>(rev: &str, refs: &'a [RemoteRef]) -> Option<&'a RemoteRef> {
+    let candidates = [
+        rev.to_string(),
+        format!("refs/heads/{}", rev),
+        format!("refs/tags/{}", rev),
+        format!("refs/{}", rev),
+    ];
+    candidates
+        .iter()
+        .filter(|name| name.starts_with("refs/"))
+        .find_map(|name| refs.iter().find(|r| &r.name == name))
+}
+
+/// 用（缩写的）SHA 匹配远程引用指向的提交，返回完整 SHA
+///
+/// 没有引用指向该提交时返回 None；缩写对应多个不同提交时报错。
+pub fn find_commit_by_prefix(prefix: &str, refs: &[RemoteRef]) -> Result<Option<String>> {
+    let prefix = prefix.to_ascii_lowercase();
+    let mut matches: Vec<&str> = refs
+        .iter()
+        .map(|r| r.commit.as_str())
+        .filter(|commit| commit.starts_with(&prefix))
+        .collect();
+    matches.sort_unstable();
+    matches.dedup();
+
+    match matches.as_slice() {
+        [] => Ok(None),
+        [commit] => Ok(Some(commit.to_string())),
+        _ => bail!(
+            "缩写的提交 {} 有歧义，可能是: {}",
+            prefix,
+            matches.join(", ")
+        ),
+    }
+}
+
+/// 去掉 refs/heads/、refs/tags/ 前缀，得到便于展示的短名
+pub fn short_name(name: &str) -> &str {
+    name.strip_prefix("refs/heads/")
+        .or_else(|| name.strip_prefix("refs/tags/"))
+        .unwrap_or(name)
+}
+
+/// 把 /tree/ 之后的 "<ref>/<path>" 拆分为引用与仓库内路径
+///
+/// 分支名与标签名可能包含斜杠（如 release/1.2），因此依次尝试越来越短的前缀，
+/// 与远程公布的分支、标签匹配，最长的匹配获胜；都不匹配时，首段若形如提交 SHA 则作为提交处理。
+pub fn split_ref_and_path(spec: &str, refs: &[RemoteRef]) -> Option<(String, Option<String>)> {
+    let segments: Vec<&str> = spec.split('/').filter(|s| !s.is_empty()).collect();
+
+    let split_at = (1..=segments.len())
+        .rev()
+        .find(|&n| {
+            let candidate = segments[..n].join("/");
+            refs.iter().any(|r| {
+                r.name == format!("refs/heads/{}", candidate)
+                    || r.name == format!("refs/tags/{}", candidate)
+            })
+        })
+        .or_else(|| segments.first().filter(|s| is_hex_sha(s)).map(|_| 1))?;
+
+    let path = (split_at < segments.len()).then(|| segments[split_at..].join("/"));
+    Some((segments[..split_at].join("/"), path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAIN: &str = "1111111111111111111111111111111111111111";
+    const TAG: &str = "2222222222222222222222222222222222222222";
+    const FEATURE: &str = "abcd000000000000000000000000000000000000";
+    const FEATURE_X: &str = "abce000000000000000000000000000000000000";
+
+    fn remote_refs() -> Vec<RemoteRef> {
+        parse_ls_remote(&format!(
+            "{MAIN}\tHEAD\n\
+             {MAIN}\trefs/heads/main\n\
+             {FEATURE}\trefs/heads/feature\n\
+             {FEATURE_X}\trefs/heads/feature/x\n\
+             {MAIN}\trefs/heads/v1\n\
+             3333333333333333333333333333333333333333\trefs/tags/v1\n\
+             {TAG}\trefs/tags/v1^{{}}\n\
+             {MAIN}\trefs/pull/7/head\n"
+        ))
+    }
+
+    #[test]
+    fn annotated_tags_are_peeled_to_commits() {
+        let refs = remote_refs();
+        let tag = refs.iter().find(|r| r.name == "refs/tags/v1").unwrap();
+        assert_eq!(tag.commit, TAG);
+        assert!(!refs.iter().any(|r| r.name.ends_with("^{}")));
+    }
+
+    #[test]
+    fn refs_are_looked_up_in_git_order() {
+        let refs = remote_refs();
+        let name = |rev| find_ref(rev, &refs).map(|r| r.name.as_str());
+
+        // 分支与标签同名时分支优先，完整引用名可指定标签
+        assert_eq!(name("v1"), Some("refs/heads/v1"));
+        assert_eq!(name("refs/tags/v1"), Some("refs/tags/v1"));
+        assert_eq!(name("tags/v1"), Some("refs/tags/v1"));
+        assert_eq!(name("pull/7/head"), Some("refs/pull/7/head"));
+        assert_eq!(name("feature/x"), Some("refs/heads/feature/x"));
+        assert_eq!(name("HEAD"), None);
+        assert_eq!(name("missing"), None);
+    }
+
+    #[test]
+    fn abbreviated_shas_resolve_to_unique_commits() {
+        let refs = remote_refs();
+        assert_eq!(find_commit_by_prefix("1111", &refs).unwrap().as_deref(), Some(MAIN));
+        assert_eq!(find_commit_by_prefix("ABCD", &refs).unwrap().as_deref(), Some(FEATURE));
+        assert_eq!(find_commit_by_prefix("9999", &refs).unwrap(), None);
+
+        let err = find_commit_by_prefix("abc", &refs).unwrap_err().to_string();
+        assert!(err.contains("有歧义") && err.contains(FEATURE) && err.contains(FEATURE_X));
+
+        assert!(is_hex_sha("abcd"));
+        assert!(!is_hex_sha("abc"));
+        assert!(!is_hex_sha("main"));
+    }
+
+    #[test]
+    fn tree_specs_split_at_the_longest_matching_ref() {
+        let refs = remote_refs();
+        let split = |spec| split_ref_and_path(spec, &refs);
+        let owned = |r: &str, p: Option<&str>| Some((r.to_string(), p.map(str::to_string)));
+
+        assert_eq!(split("main/src/lib.rs"), owned("main", Some("src/lib.rs")));
+        assert_eq!(split("main"), owned("main", None));
+        assert_eq!(split("feature/x/docs"), owned("feature/x", Some("docs")));
+        assert_eq!(split("feature/y/docs"), owned("feature", Some("y/docs")));
+        assert_eq!(split("feature/x"), owned("feature/x", None));
+        // 同名的分支与标签都算匹配，交给 find_ref 决定优先级
+        assert_eq!(split("v1/README.md"), owned("v1", Some("README.md")));
+        assert_eq!(split("abc1234/docs"), owned("abc1234", Some("docs")));
+        assert_eq!(split("unknown/docs"), None);
+        assert_eq!(split(""), None);
+    }
+}
@@ -0,0 +1,230 @@
+//! 仓库地址解析：各托管平台的网页 URL、SSH 与本地仓库、owner/repo 与 gitlab: 等简写
+
+use crate::host::{expand_shorthand, split_scp, Host, HostKind, ParsedWebUrl};
+use anyhow::{anyhow, bail, Context, Result};
+use std::path::{Path, PathBuf};
+
+/// 仓库来源
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RepoSpec {
+    /// http(s):// 地址（包括由 owner/repo、gitlab: 等简写展开的地址）
+    Http(String),
+    /// ssh:// 地址或 scp 风格的 [user@]host:path
+    Ssh(String),
+    /// 本地仓库（工作区目录或裸仓库），保存为绝对路径
+    Local(PathBuf),
+}
+
+impl RepoSpec {
+    /// 解析仓库参数
+    ///
+    /// 依次识别：file:// URL、http(s)://、ssh://、gitlab: 等简写、
+    /// 本地路径（以 `/`、`./`、`../`、`~/` 开头，或是已存在的 git 仓库目录）、
+    /// scp 风格地址，最后是 owner/repo 简写。
+    pub fn parse(input: &str) -> Result<RepoSpec> {
+        if let Some(path) = input.strip_prefix("file://") {
+            return local_repo(Path::new(path));
+        }
+        if input.starts_with("https://") || input.starts_with("http://") {
+            return Ok(RepoSpec::Http(input.to_string()));
+        }
+        if input.starts_with("ssh://") {
+            return Ok(RepoSpec::Ssh(input.to_string()));
+        }
+
+        // gitlab:group/proj、bitbucket:owner/repo 等简写
+        if let Some(url) = expand_shorthand(input) {
+            return Ok(RepoSpec::Http(url));
+        }
+
+        // 明确的本地路径，或恰好是已存在的 git 仓库目录
+        let explicit_local = ["/", "./", "../", "~/"]
+            .iter()
+            .any(|prefix| input.starts_with(prefix))
+            || matches!(input, "." | ".." | "~");
+        if explicit_local {
+            return local_repo(&expand_home(input));
+        }
+        if is_git_repo(Path::new(input)) {
+            return local_repo(Path::new(input));
+        }
+
+        if split_scp(input).is_some() {
+            return Ok(RepoSpec::Ssh(input.to_string()));
+        }
+
+        // owner/repo 格式，使用默认主机（github.com 或配置的 default_host）
+        let parts: Vec<&str> = input.split('/').collect();
+        if parts.len() == 2 && !parts[0].is_empty() && !parts[1].is_empty() {
+            return Ok(RepoSpec::Http(Host::default_host().clone_url(input)));
+        }
+
+        Err(anyhow!(
+            "无效的仓库格式: {}。支持格式: owner/repo、gitlab:group/proj、\
+             https://github.com/owner/repo.git、git@github.com:owner/repo.git、\
+             ssh://host/path/repo.git、file:///path/to/repo 或本地仓库路径",
+            input
+        ))
+    }
+
+    /// 交给 git 使用的地址；本地仓库转为 file:// URL，使浅拉取生效
+    pub fn url(&self) -> String {
+        match self {
+            RepoSpec::Http(url) | RepoSpec::Ssh(url) => url.clone(),
+            RepoSpec::Local(path) => format!("file://{}", path.display()),
+        }
+    }
+}
+
+/// 若输入是可识别平台上的网页 URL，按平台格式解析
+///
+/// 支持的格式:
+/// - GitHub: https://github.com/owner/repo/tree|blob/<ref>/<path>，
+///   以及 https://raw.githubusercontent.com/owner/repo/<ref>/<path>
+/// - GitLab: https://gitlab.com/group/sub/proj/-/tree|blob|raw/<ref>/<path>
+/// - Gitea/Forgejo: https://codeberg.org/owner/repo/src/branch|tag|commit/<ref>/<path>
+/// - Bitbucket: https://bitbucket.org/owner/repo/src/<ref>/<path>
+///
+/// SSH 地址后附加同样的网页路径（如 git@github.com:owner/repo/tree/main/dir）
+/// 也会拆出 "<ref>/<path>"，克隆地址保持 SSH。
+/// 其他输入（不带网页路径的 SSH 地址、简写、本地路径等）返回 None。
+pub fn parse_web_url(input: &str) -> Result<Option<ParsedWebUrl>> {
+    let is_ssh = match RepoSpec::parse(input)? {
+        RepoSpec::Http(_) if input.starts_with("https://") || input.starts_with("http://") => false,
+        RepoSpec::Ssh(_) => true,
+        _ => return Ok(None),
+    };
+    let Some(host) = Host::from_url(input) else {
+        return Ok(None);
+    };
+    let parsed = host.parse_web_url(input)?;
+    if is_ssh && parsed.tree_spec.is_none() {
+        return Ok(None);
+    }
+    Ok(Some(parsed))
+}
+
+/// 将 repo 参数转换为完整的 Git URL
+pub fn build_repo_url(repo: &str) -> Result<String> {
+    RepoSpec::parse(repo).map(|spec| spec.url())
+}
+
+/// 从 GitHub（含已配置的 GitHub Enterprise）仓库 URL 中提取 (owner, repo)，
+/// 非 GitHub 地址返回 None
+pub fn github_repo_slug(repo_url: &str) -> Option<(String, String)> {
+    let host = Host::from_url(repo_url).filter(|host| host.kind == HostKind::GitHub)?;
+    let parsed = host.parse_web_url(repo_url).ok()?;
+    let (owner, repo) = parsed.repo_path.split_once('/')?;
+    Some((owner.to_string(), repo.to_string()))
+}
+
+/// 校验本地仓库路径并转为绝对路径
+fn local_repo(path: &Path) -> Result<RepoSpec> {
+    if !path.exists() {
+        bail!("本地仓库不存在: {}", path.display());
+    }
+    if !is_git_repo(path) {
+        bail!("不是 git 仓库: {}", path.display());
+    }
+    let path = path
+        .canonicalize()
+        .with_context(|| format!("无法访问本地仓库: {}", path.display()))?;
+    Ok(RepoSpec::Local(path))
+}
+
+/// 目录是工作区（含 .git）或裸仓库（含 HEAD 与 objects）
+fn is_git_repo(path: &Path) -> bool {
+    path.join(".git").exists() || (path.join("HEAD").is_file() && path.join("objects").is_dir())
+}
+
+/// 展开开头的 `~`
+pub(crate) fn expand_home(input: &str) -> PathBuf {
+    let rest = match input.strip_prefix('~') {
+        Some(rest) if rest.is_empty() || rest.starts_with('/') => rest.trim_start_matches('/'),
+        _ => return PathBuf::from(input),
+    };
+    match dirs::home_dir() {
+        Some(home) => home.join(rest),
+        None => PathBuf::from(input),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::git;
+
+    #[test]
+    fn ssh_addresses_are_kept_verbatim() {
+        for input in [
+            "git@github.com:owner/repo.git",
+            "git@gitlab.corp:group/sub/proj",
+            "ssh://git@ghe.corp:2222/org/repo.git",
+        ] {
+            let spec = RepoSpec::parse(input).unwrap();
+            assert_eq!(spec, RepoSpec::Ssh(input.to_string()));
+            assert_eq!(spec.url(), input);
+        }
+    }
+
+    #[test]
+    fn ssh_addresses_with_web_paths_are_split() {
+        let parsed = parse_web_url("git@github.com:owner/repo/tree/main/examples")
+            .unwrap()
+            .unwrap();
+        assert_eq!(parsed.clone_url, "git@github.com:owner/repo.git");
+        assert_eq!(parsed.tree_spec.as_deref(), Some("main/examples"));
+
+        let parsed = parse_web_url("ssh://git@gitlab.com/group/proj/-/blob/v1/a.txt")
+            .unwrap()
+            .unwrap();
+        assert_eq!(parsed.clone_url, "ssh://git@gitlab.com/group/proj.git");
+        assert_eq!(parsed.tree_spec.as_deref(), Some("v1/a.txt"));
+
+        // 没有网页路径的 SSH 地址不是网页 URL
+        assert!(parse_web_url("git@github.com:owner/repo.git").unwrap().is_none());
+        assert!(parse_web_url("ssh://git@github.com:22/owner/repo.git").unwrap().is_none());
+    }
+
+    #[test]
+    fn local_repositories_shadow_owner_repo_shorthand() {
+        std::fs::create_dir_all("target").unwrap();
+        let dir = tempfile::Builder::new().tempdir_in("target").unwrap();
+        git(dir.path(), &["init", "-q"]);
+        let name = dir.path().file_name().unwrap().to_str().unwrap();
+
+        let spec = RepoSpec::parse(&format!("target/{}", name)).unwrap();
+        assert_eq!(spec, RepoSpec::Local(dir.path().canonicalize().unwrap()));
+        assert!(spec.url().starts_with("file:///"));
+
+        // 不是 git 仓库的同名目录仍按 owner/repo 简写处理
+        std::fs::remove_dir_all(dir.path().join(".git")).unwrap();
+        assert!(matches!(
+            RepoSpec::parse(&format!("target/{}", name)).unwrap(),
+            RepoSpec::Http(_)
+        ));
+    }
+
+    #[test]
+    fn explicit_local_paths_must_be_repositories() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().to_str().unwrap();
+        assert!(RepoSpec::parse(path).unwrap_err().to_string().contains("不是 git 仓库"));
+        assert!(RepoSpec::parse(&format!("{}/missing", path))
+            .unwrap_err()
+            .to_string()
+            .contains("本地仓库不存在"));
+
+        git(dir.path(), &["init", "-q"]);
+        let expected = RepoSpec::Local(dir.path().canonicalize().unwrap());
+        assert_eq!(RepoSpec::parse(path).unwrap(), expected);
+        assert_eq!(RepoSpec::parse(&format!("file://{}", path)).unwrap(), expected);
+    }
+
+    #[test]
+    fn invalid_inputs_are_rejected() {
+        for input in ["owner", "a/b/c", "/owner/", "owner//repo"] {
+            assert!(RepoSpec::parse(input).is_err(), "{}", input);
+        }
+    }
+}
@@ -0,0 +1,126 @@
+//! 拼写提示：路径或引用不存在时，找出名称最接近的候选项
+
+/// 最多给出的候选项数量
+const MAX_SUGGESTIONS: usize = 3;
+
+/// 从 candidates 中找出与 wanted 最接近的几项，越接近越靠前
+///
+/// 忽略大小写后完全相同的最优先；其次比较编辑距离，整条路径与最后一段名称
+/// （目录被移动或层级写错时）取较小者，距离超过名称长度约三分之一的不算相近。
+pub(crate) fn closest<'a>(wanted: &str, candidates: impl IntoIterator<Item = &'a str>) -> Vec<&'a str> {
+    let wanted = wanted.trim_matches('/').to_lowercase();
+    let wanted_base = base_name(&wanted);
+    let limit = (wanted.chars().count() / 3).max(1);
+    let base_limit = wanted_base.chars().count() / 3;
+
+    let mut scored: Vec<(usize, &str)> = candidates
+        .into_iter()
+        .filter_map(|candidate| {
+            let lower = candidate.to_lowercase();
+            let full = bounded_distance(&wanted, &lower, limit);
+            // 只有最后一段名称相近时多记 1 的距离，表示所在目录不同
+            let base = bounded_distance(wanted_base, base_name(&lower), base_limit)
+                .map(|distance| distance + 1);
+            let score = full.into_iter().chain(base).min()?;
+            Some((score, candidate))
+        })
+        .collect();
+    scored.sort_unstable();
+    scored.dedup_by(|a, b| a.1 == b.1);
+    scored
+        .into_iter()
+        .take(MAX_SUGGESTIONS)
+        .map(|(_, candidate)| candidate)
+        .collect()
+}
+
+/// 把候选项格式化为追加在错误信息后的提示，没有候选项时为空字符串
+pub(crate) fn hint(suggestions: &[&str]) -> String {
+    if suggestions.is_empty() {
+        return String::new();
+    }
+    format!("\n提示: 是否是指 {}？", suggestions.join("、"))
+}
+
+/// 路径的最后一段
+fn base_name(path: &str) -> &str {
+    path.rsplit('/').next().unwrap_or(path)
+}
+
+/// 两个字符串的编辑距离（相邻字符互换记 1 次），超过 limit 时返回 None
+///
+/// 长度差已超过 limit 时直接跳过，在大仓库的完整文件列表中查找也不会太慢。
+fn bounded_distance(a: &str, b: &str, limit: usize) -> Option<usize> {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    if a.len().abs_diff(b.len()) > limit {
+        return None;
+    }
+
+    // 只保留最近三行：互换需要回看两行
+    let mut before: Vec<usize> = vec![0; b.len() + 1];
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+    for i in 0..a.len() {
+        current[0] = i + 1;
+        for j in 0..b.len() {
+            let mut distance = (previous[j] + usize::from(a[i] != b[j]))
+                .min(previous[j + 1] + 1)
+                .min(current[j] + 1);
+            if i > 0 && j > 0 && a[i] == b[j - 1] && a[i - 1] == b[j] {
+                distance = distance.min(before[j - 1] + 1);
+            }
+            current[j + 1] = distance;
+        }
+        // 连续两行都已超过上限，之后只会更大
+        if current.iter().chain(&previous).all(|&distance| distance > limit) {
+            return None;
+        }
+        std::mem::swap(&mut before, &mut previous);
+        std::mem::swap(&mut previous, &mut current);
+    }
+    Some(previous[b.len()]).filter(|&distance| distance <= limit)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn case_only_mismatches_come_first() {
+        let candidates = ["src/readme.rs", "README.md", "readme.txt"];
+        assert_eq!(closest("readme.md", candidates)[0], "README.md");
+    }
+
+    #[test]
+    fn transpositions_count_as_one_edit() {
+        assert_eq!(bounded_distance("exmaples", "examples", 1), Some(1));
+        assert_eq!(closest("exmaples", ["examples", "src"]), vec!["examples"]);
+    }
+
+    #[test]
+    fn files_under_another_parent_are_found_by_name() {
+        let candidates = ["website/content/guide.md", "src/lib.rs"];
+        assert_eq!(closest("docs/guide.md", candidates), vec!["website/content/guide.md"]);
+    }
+
+    #[test]
+    fn distant_names_are_cut_off() {
+        assert!(closest("main", ["master", "trunk"]).is_empty());
+        assert!(closest("dev", ["develop"]).is_empty());
+        assert_eq!(closest("ab", ["ax", "xy"]), vec!["ax"]);
+        assert_eq!(bounded_distance("kitten", "sitting", 2), None);
+        assert_eq!(bounded_distance("kitten", "sitting", 3), Some(3));
+    }
+
+    #[test]
+    fn suggestions_are_ordered_deduplicated_and_capped() {
+        let candidates = ["example", "exampels", "examples", "examples", "Examples"];
+        assert_eq!(
+            closest("examples/", candidates),
+            vec!["Examples", "examples", "exampels"]
+        );
+        assert_eq!(hint(&[]), "");
+        assert_eq!(hint(&["a", "b"]), "\n提示: 是否是指 a、b？");
+    }
+}
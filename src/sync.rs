@@ -0,0 +1,504 @@
+//! `git-get sync`：按清单逐个抓取条目，并维护锁文件
+
+use crate::copy::Staging;
+use crate::fetch::{FetchOptions, FetchOutcome, FetchRequest};
+use crate::lock::{LockEntry, Lockfile};
+use crate::manifest::{Manifest, ManifestEntry};
+use crate::progress::Progress;
+use crate::provenance::{collect_files, hash_file, Provenance};
+use crate::update::UpdateOutcome;
+use anyhow::{bail, Result};
+use std::path::{Path, PathBuf};
+
+/// 锁文件的使用方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LockMode {
+    /// 有锁定记录的条目抓取锁定的提交，其余条目解析后写入锁文件
+    #[default]
+    Locked,
+    /// 忽略锁定记录，重新解析全部条目并更新锁文件
+    Update,
+    /// 锁文件必须覆盖全部条目且不得变化（用于 CI）
+    Frozen,
+}
+
+/// 单个条目的同步结果
+#[derive(Debug)]
+pub enum SyncStatus {
+    /// 本次抓取并写入
+    Fetched(Box<FetchOutcome>),
+    /// 按来源记录原地更新了已有的目录
+    Updated(Box<UpdateOutcome>),
+    /// 目标路径已是锁定（或最新）的内容，跳过
+    UpToDate,
+    /// 同步失败
+    Failed(anyhow::Error),
+}
+
+/// 单个条目的同步记录
+#[derive(Debug)]
+pub struct SyncResult {
+    /// 条目的目标路径
+    pub dest: PathBuf,
+    /// 同步结果
+    pub status: SyncStatus,
+}
+
+/// 一次同步的汇总
+#[derive(Debug)]
+pub struct SyncReport {
+    /// 各条目的结果，顺序与清单一致
+    pub results: Vec<SyncResult>,
+    /// 锁文件是否被更新
+    pub lock_updated: bool,
+}
+
+/// 同步清单中的全部条目，并按 mode 读取、更新锁文件
+///
+/// 单个条目失败不会中断其余条目，失败信息记录在对应的结果中；
+/// options 中的 token、后端与进度回调作为各条目的默认设置。
+pub fn sync(manifest: &Manifest, options: &FetchOptions, mode: LockMode) -> Result<SyncReport> {
+    let progress = &options.progress;
+    let lock_path = Lockfile::path_for(&manifest.path);
+    let lock = Lockfile::load(&lock_path)?;
+
+    if mode == LockMode::Frozen {
+        if let Some(stale) = lock
+            .entries
+            .iter()
+            .find(|locked| !manifest.entries.iter().any(|entry| locked.matches(entry)))
+        {
+            bail!(
+                "锁文件中的条目 {} 与清单不一致，--frozen 模式下不能更新锁文件",
+                stale.dest.display()
+            );
+        }
+    }
+
+    let total = manifest.entries.len();
+    let mut results = Vec::with_capacity(total);
+    let mut new_lock = Lockfile {
+        version: lock.version,
+        entries: Vec::with_capacity(total),
+    };
+
+    for (index, entry) in manifest.entries.iter().enumerate() {
+        let dest = manifest.dest_of(entry);
+        progress.emit(format!("\n🔄 [{}/{}] {}", index + 1, total, dest.display()));
+
+        let locked = lock.find(entry);
+        let status = match sync_entry(manifest, entry, options, mode, locked) {
+            Ok((status, locked)) => {
+                new_lock.entries.push(locked);
+                status
+            }
+            Err(e) => {
+                progress.emit(format!("❌ 失败: {:#}", e));
+                // 失败时保留该目标路径原有的锁定记录
+                if let Some(previous) = lock.entries.iter().find(|locked| locked.dest == entry.dest) {
+                    new_lock.entries.push(previous.clone());
+                }
+                SyncStatus::Failed(e)
+            }
+        };
+        results.push(SyncResult { dest, status });
+    }
+
+    let lock_updated = new_lock != lock;
+    if lock_updated && mode == LockMode::Frozen {
+        bail!(
+            "锁文件与清单不一致（条目缺失或顺序不同），--frozen 模式下不能更新锁文件: {}",
+            lock_path.display()
+        );
+    }
+    if lock_updated {
+        new_lock.save(&lock_path)?;
+        progress.emit(format!("\n🔒 已更新锁文件: {}", lock_path.display()));
+    }
+
+    Ok(SyncReport {
+        results,
+        lock_updated,
+    })
+}
+
+/// 同步单个条目，返回结果与应写入锁文件的记录
+fn sync_entry(
+    manifest: &Manifest,
+    entry: &ManifestEntry,
+    options: &FetchOptions,
+    mode: LockMode,
+    locked: Option<&LockEntry>,
+) -> Result<(SyncStatus, LockEntry)> {
+    let progress = &options.progress;
+    let dest = manifest.dest_of(entry);
+    let request = manifest.request_for(entry, options)?;
+
+    match (mode, locked) {
+        (LockMode::Frozen, None) => {
+            bail!("锁文件中没有该条目的记录（或清单已修改），--frozen 模式下不能更新锁文件")
+        }
+        (LockMode::Locked | LockMode::Frozen, Some(locked)) => {
+            let mut request = request.reference(&locked.commit).expect_tree(&locked.tree);
+            if let Some(path) = &locked.path {
+                request = request.path(path);
+            }
+
+            if is_materialized(&dest) {
+                // 目录中的来源记录与锁文件不一致（例如锁文件被他人更新）
+                // 或清单中的过滤规则有变化时原地更新
+                match recorded(&dest)? {
+                    Some(provenance)
+                        if provenance.tree != locked.tree || !same_filter(&provenance, entry) =>
+                    {
+                        progress.emit(format!("🔒 更新到锁定的提交: {}", short(&locked.commit)));
+                        let outcome = request.update()?;
+                        return Ok((SyncStatus::Updated(Box::new(outcome)), locked.clone()));
+                    }
+                    Some(_) => {
+                        progress.emit(format!("⏭️  已是锁定的提交 {}，跳过", short(&locked.commit)));
+                        return Ok((SyncStatus::UpToDate, locked.clone()));
+                    }
+                    None => {
+                        verify_unrecorded(&request, &dest, &locked.commit, progress)?;
+                        return Ok((SyncStatus::UpToDate, locked.clone()));
+                    }
+                }
+            }
+
+            progress.emit(format!("🔒 使用锁定的提交: {}", short(&locked.commit)));
+            let outcome = request.fetch()?;
+            Ok((SyncStatus::Fetched(Box::new(outcome)), locked.clone()))
+        }
+        (LockMode::Locked | LockMode::Update, _) => {
+            if is_materialized(&dest) {
+                let resolution = request.resolve()?;
+                let recorded = recorded(&dest)?;
+                let known = locked
+                    .map(|locked| locked.tree.clone())
+                    .or(recorded.as_ref().map(|provenance| provenance.tree.clone()));
+                let filter_changed = recorded
+                    .as_ref()
+                    .is_some_and(|provenance| !same_filter(provenance, entry));
+                if known.as_deref() == Some(resolution.tree.as_str()) && !filter_changed {
+                    if recorded.is_none() {
+                        let pinned = request
+                            .clone()
+                            .reference(&resolution.commit)
+                            .expect_tree(&resolution.tree);
+                        verify_unrecorded(&pinned, &dest, &resolution.commit, progress)?;
+                    }
+                    progress.emit(format!("⏭️  内容未变化（{}），跳过", short(&resolution.commit)));
+                    let locked = lock_entry(
+                        entry,
+                        resolution.repo_url,
+                        resolution.reference,
+                        resolution.path,
+                        resolution.commit,
+                        resolution.tree,
+                    );
+                    return Ok((SyncStatus::UpToDate, locked));
+                }
+                if recorded.is_none() {
+                    match locked {
+                        Some(locked) => bail!(
+                            "远程有更新（{} → {}），但目标路径中没有来源记录，无法原地更新: {}\n提示: 删除该路径后重新同步",
+                            short(&locked.commit),
+                            short(&resolution.commit),
+                            dest.display()
+                        ),
+                        None => bail!(
+                            "目标路径已存在，但锁文件与来源记录中都没有对应信息，无法确认其版本: {}\n提示: 删除该路径后重新同步",
+                            dest.display()
+                        ),
+                    }
+                }
+
+                // 固定到刚解析的提交，避免两次访问之间远程再次变化
+                let outcome = request
+                    .reference(&resolution.commit)
+                    .expect_tree(&resolution.tree)
+                    .update()?;
+                let locked = lock_entry(
+                    entry,
+                    outcome.repo_url.clone(),
+                    resolution.reference,
+                    outcome.path.clone(),
+                    outcome.commit.clone(),
+                    outcome.tree.clone(),
+                );
+                return Ok((SyncStatus::Updated(Box::new(outcome)), locked));
+            }
+
+            let outcome = request.fetch()?;
+            let locked = lock_entry(
+                entry,
+                outcome.repo_url.clone(),
+                outcome.reference.clone(),
+                outcome.path.clone(),
+                outcome.commit.clone(),
+                outcome.tree.clone(),
+            );
+            Ok((SyncStatus::Fetched(Box::new(outcome)), locked))
+        }
+    }
+}
+
+/// 由解析结果构造锁定信息
+fn lock_entry(
+    entry: &ManifestEntry,
+    url: String,
+    resolved: String,
+    path: Option<String>,
+    commit: String,
+    tree: String,
+) -> LockEntry {
+    LockEntry {
+        dest: entry.dest.clone(),
+        source: entry.source.clone(),
+        reference: entry.reference.clone(),
+        url,
+        resolved,
+        path,
+        commit,
+        tree,
+    }
+}
+
+/// 目标目录中的来源记录，没有来源记录时为 None
+fn recorded(dest: &Path) -> Result<Option<Provenance>> {
+    if !dest.is_dir() {
+        return Ok(None);
+    }
+    Provenance::load(dest)
+}
+
+/// 目标路径没有来源记录（--no-metadata 或单个文件）时，重新抓取 commit 逐个文件比较内容
+///
+/// 只有内容一致才能确认目标路径是该提交的抓取结果；不一致时报错，不覆盖本地内容。
+fn verify_unrecorded(
+    request: &FetchRequest,
+    dest: &Path,
+    commit: &str,
+    progress: &Progress,
+) -> Result<()> {
+    progress.emit(format!("🔍 目标路径没有来源记录，正在与提交 {} 比对内容...", short(commit)));
+    let staging = Staging::new(dest)?;
+    let fetched = staging.join("verify");
+    request
+        .clone()
+        .dest(&fetched)
+        .metadata(false)
+        .progress(Progress::silent())
+        .fetch()?;
+    let same = match (fetched.is_dir(), dest.is_dir()) {
+        (true, true) => collect_files(&fetched)? == collect_files(dest)?,
+        (false, false) => hash_file(&fetched)? == hash_file(dest)?,
+        _ => false,
+    };
+    if !same {
+        bail!(
+            "目标路径的内容与提交 {} 不一致，且没有来源记录，无法原地更新: {}\n提示: 删除该路径后重新同步",
+            short(commit),
+            dest.display()
+        );
+    }
+    Ok(())
+}
+
+/// 来源记录中的过滤规则是否与清单条目一致
+fn same_filter(provenance: &Provenance, entry: &ManifestEntry) -> bool {
+    provenance.include == entry.include && provenance.exclude == entry.exclude
+}
+
+/// 目标路径是否已有抓取结果（非空目录或已存在的文件）
+fn is_materialized(dest: &Path) -> bool {
+    if dest.is_file() {
+        return true;
+    }
+    std::fs::read_dir(dest).is_ok_and(|mut entries| entries.next().is_some())
+}
+
+/// 提交 SHA 的缩写
+fn short(commit: &str) -> &str {
+    &commit[..commit.len().min(12)]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manifest::MANIFEST_FILE;
+    use crate::provenance::PROVENANCE_FILE;
+    use crate::test_support::{git, FixtureRepo};
+    use tempfile::TempDir;
+
+    /// 在 dir 中写入清单，entries 为 (path, dest) 列表
+    fn manifest(dir: &Path, repo: &FixtureRepo, entries: &[(&str, &str)]) -> Manifest {
+        let content: String = entries
+            .iter()
+            .map(|(path, dest)| {
+                format!(
+                    "[[entry]]\nsource = \"{}\"\npath = \"{}\"\ndest = \"{}\"\n\n",
+                    repo.url(),
+                    path,
+                    dest
+                )
+            })
+            .collect();
+        let path = dir.join(MANIFEST_FILE);
+        std::fs::write(&path, content).unwrap();
+        Manifest::load(&path).unwrap()
+    }
+
+    fn options(metadata: bool) -> FetchOptions {
+        FetchOptions {
+            metadata,
+            ..FetchOptions::default()
+        }
+    }
+
+    fn locked_commits(manifest: &Manifest) -> Vec<String> {
+        let lock = Lockfile::load(&Lockfile::path_for(&manifest.path)).unwrap();
+        lock.entries.into_iter().map(|entry| entry.commit).collect()
+    }
+
+    fn status(report: &SyncReport) -> Vec<&'static str> {
+        report
+            .results
+            .iter()
+            .map(|result| match &result.status {
+                SyncStatus::Fetched(_) => "fetched",
+                SyncStatus::Updated(_) => "updated",
+                SyncStatus::UpToDate => "up-to-date",
+                SyncStatus::Failed(_) => "failed",
+            })
+            .collect()
+    }
+
+    fn read(path: &Path) -> String {
+        std::fs::read_to_string(path).unwrap()
+    }
+
+    /// 提交中 dir 目录的 tree 哈希
+    fn dir_tree(repo: &FixtureRepo, commit: &str) -> String {
+        git(repo.path(), &["rev-parse", &format!("{}:dir", commit)])
+    }
+
+    #[test]
+    fn locked_commits_are_kept_until_update() {
+        let repo = FixtureRepo::new();
+        repo.write("dir/a.txt", "v1\n");
+        let first = repo.commit("v1");
+        let dir = TempDir::new().unwrap();
+        let manifest = manifest(dir.path(), &repo, &[("dir", "vendor/dir")]);
+        let dest = dir.path().join("vendor/dir");
+
+        let report = sync(&manifest, &options(true), LockMode::Locked).unwrap();
+        assert_eq!(status(&report), ["fetched"]);
+        assert!(report.lock_updated);
+        assert_eq!(locked_commits(&manifest), vec![first.clone()]);
+
+        repo.write("dir/a.txt", "v2\n");
+        let second = repo.commit("v2");
+
+        // 锁文件固定了提交，远程的更新不会被拉取
+        let report = sync(&manifest, &options(true), LockMode::Locked).unwrap();
+        assert_eq!(status(&report), ["up-to-date"]);
+        assert!(!report.lock_updated);
+        assert_eq!(read(&dest.join("a.txt")), "v1\n");
+
+        let report = sync(&manifest, &options(true), LockMode::Update).unwrap();
+        assert_eq!(status(&report), ["updated"]);
+        assert!(report.lock_updated);
+        assert_eq!(locked_commits(&manifest), [second]);
+        assert_eq!(read(&dest.join("a.txt")), "v2\n");
+
+        // 锁文件被改回旧提交（例如切换分支）时原地更新回去
+        let lock_path = Lockfile::path_for(&manifest.path);
+        let mut lock = Lockfile::load(&lock_path).unwrap();
+        lock.entries[0].commit = first.clone();
+        lock.entries[0].tree = dir_tree(&repo, &first);
+        lock.save(&lock_path).unwrap();
+        let report = sync(&manifest, &options(true), LockMode::Locked).unwrap();
+        assert_eq!(status(&report), ["updated"]);
+        assert_eq!(read(&dest.join("a.txt")), "v1\n");
+    }
+
+    #[test]
+    fn frozen_never_writes_the_lock_file() {
+        let repo = FixtureRepo::new();
+        repo.write("dir/a.txt", "a\n").write("other/b.txt", "b\n");
+        repo.commit("init");
+        let dir = TempDir::new().unwrap();
+
+        // 没有锁文件时条目失败，也不会创建锁文件
+        let single = manifest(dir.path(), &repo, &[("dir", "vendor/dir")]);
+        let report = sync(&single, &options(true), LockMode::Frozen).unwrap();
+        assert_eq!(status(&report), ["failed"]);
+        assert!(!Lockfile::path_for(&single.path).exists());
+        assert!(!dir.path().join("vendor/dir").exists());
+
+        sync(&single, &options(true), LockMode::Locked).unwrap();
+        let lock_path = Lockfile::path_for(&single.path);
+        let locked = read(&lock_path);
+        let report = sync(&single, &options(true), LockMode::Frozen).unwrap();
+        assert_eq!(status(&report), ["up-to-date"]);
+
+        // 清单新增条目：该条目失败，锁文件保持不变
+        let both = manifest(dir.path(), &repo, &[("dir", "vendor/dir"), ("other", "vendor/other")]);
+        let report = sync(&both, &options(true), LockMode::Frozen).unwrap();
+        assert_eq!(status(&report), ["up-to-date", "failed"]);
+        assert_eq!(read(&lock_path), locked);
+
+        // 条目顺序与锁文件不同时也需要更新锁文件，--frozen 下报错
+        sync(&both, &options(true), LockMode::Locked).unwrap();
+        let locked = read(&lock_path);
+        let reversed = manifest(dir.path(), &repo, &[("other", "vendor/other"), ("dir", "vendor/dir")]);
+        assert!(sync(&reversed, &options(true), LockMode::Frozen).is_err());
+        assert_eq!(read(&lock_path), locked);
+
+        // 锁文件中有清单已删除的条目
+        let removed = manifest(dir.path(), &repo, &[("dir", "vendor/dir")]);
+        assert!(sync(&removed, &options(true), LockMode::Frozen).is_err());
+        assert_eq!(read(&lock_path), locked);
+    }
+
+    #[test]
+    fn destinations_without_provenance_are_verified() {
+        let repo = FixtureRepo::new();
+        repo.write("dir/a.txt", "a\n").write("dir/b.txt", "b\n");
+        repo.commit("init");
+        let dir = TempDir::new().unwrap();
+        let manifest = manifest(
+            dir.path(),
+            &repo,
+            &[("dir", "vendor/dir"), ("dir/a.txt", "vendor/a.txt")],
+        );
+
+        for mode in [LockMode::Locked, LockMode::Update] {
+            let report = sync(&manifest, &options(false), mode).unwrap();
+            assert!(status(&report).iter().all(|status| *status != "failed"));
+            assert!(!dir.path().join("vendor/dir").join(PROVENANCE_FILE).exists());
+        }
+        for mode in [LockMode::Locked, LockMode::Frozen, LockMode::Update] {
+            let report = sync(&manifest, &options(false), mode).unwrap();
+            assert_eq!(status(&report), ["up-to-date", "up-to-date"], "{:?}", mode);
+        }
+
+        // 本地内容被修改过时不再报告为最新，也不覆盖本地内容
+        std::fs::write(dir.path().join("vendor/dir/b.txt"), "local\n").unwrap();
+        std::fs::write(dir.path().join("vendor/a.txt"), "local\n").unwrap();
+        for mode in [LockMode::Locked, LockMode::Frozen, LockMode::Update] {
+            let report = sync(&manifest, &options(false), mode).unwrap();
+            assert_eq!(status(&report), ["failed", "failed"], "{:?}", mode);
+        }
+        assert_eq!(read(&dir.path().join("vendor/dir/b.txt")), "local\n");
+        assert_eq!(read(&dir.path().join("vendor/a.txt")), "local\n");
+        // 比对用的暂存区已清理
+        let leftovers: Vec<_> = std::fs::read_dir(dir.path().join("vendor"))
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        assert_eq!(leftovers.len(), 2, "{:?}", leftovers);
+    }
+}
@@ -0,0 +1,309 @@
+//! 测试夹具：本地 git 仓库与最小的 HTTP 服务
+//!
+//! HTTP 服务只实现测试需要的部分：每个连接处理一个请求，响应后关闭连接。
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::Arc;
+use tempfile::TempDir;
+
+/// 执行 git 命令，失败时 panic，返回标准输出
+pub(crate) fn git(dir: &Path, args: &[&str]) -> String {
+    let output = Command::new("git")
+        .current_dir(dir)
+        .args(["-c", "user.name=git-get", "-c", "user.email=git-get@example.com"])
+        .args(["-c", "init.defaultBranch=main", "-c", "core.symlinks=true"])
+        .args(args)
+        .output()
+        .expect("无法执行 git");
+    assert!(
+        output.status.success(),
+        "git {} 执行失败: {}",
+        args.join(" "),
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8_lossy(&output.stdout).trim().to_string()
+}
+
+/// 本地夹具仓库：先写入文件，再提交
+pub(crate) struct FixtureRepo {
+    dir: TempDir,
+}
+
+impl FixtureRepo {
+    /// 创建空仓库（默认分支 main，允许部分克隆与按 SHA 拉取）
+    pub(crate) fn new() -> Self {
+        let dir = TempDir::new().unwrap();
+        git(dir.path(), &["init", "--quiet"]);
+        git(dir.path(), &["config", "uploadpack.allowFilter", "true"]);
+        git(dir.path(), &["config", "uploadpack.allowAnySHA1InWant", "true"]);
+        Self { dir }
+    }
+
+    /// 工作区路径
+    pub(crate) fn path(&self) -> &Path {
+        self.dir.path()
+    }
+
+    /// file:// 地址
+    pub(crate) fn url(&self) -> String {
+        format!("file://{}", self.path().display())
+    }
+
+    /// 写入文件（自动创建上级目录）
+    pub(crate) fn write(&self, path: &str, content: impl AsRef<[u8]>) -> &Self {
+        let file = self.path().join(path);
+        std::fs::create_dir_all(file.parent().unwrap()).unwrap();
+        std::fs::write(file, content).unwrap();
+        self
+    }
+
+    /// 创建符号链接
+    #[cfg(unix)]
+    pub(crate) fn symlink(&self, path: &str, target: &str) -> &Self {
+        let link = self.path().join(path);
+        std::fs::create_dir_all(link.parent().unwrap()).unwrap();
+        std::os::unix::fs::symlink(target, link).unwrap();
+        self
+    }
+
+    /// 提交全部改动，返回提交 SHA
+    pub(crate) fn commit(&self, message: &str) -> String {
+        git(self.path(), &["add", "-A"]);
+        git(self.path(), &["commit", "--quiet", "-m", message]);
+        git(self.path(), &["rev-parse", "HEAD"])
+    }
+}
+
+/// 收到的 HTTP 请求
+pub(crate) struct Request {
+    pub method: String,
+    /// 路径，含查询串
+    pub target: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Request {
+    /// 按名称（忽略大小写）读取请求头
+    pub(crate) fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// 要返回的 HTTP 响应
+pub(crate) struct Response {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Response {
+    pub(crate) fn new(status: u16, body: impl Into<Vec<u8>>) -> Self {
+        Self {
+            status,
+            headers: Vec::new(),
+            body: body.into(),
+        }
+    }
+
+    pub(crate) fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+}
+
+type Handler = dyn Fn(&Request) -> Response + Send + Sync;
+
+/// 在随机端口上启动 HTTP 服务，返回 `http://127.0.0.1:<port>`
+///
+/// 服务线程随测试进程结束。
+pub(crate) fn serve(handler: impl Fn(&Request) -> Response + Send + Sync + 'static) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let handler: Arc<Handler> = Arc::new(handler);
+    std::thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let handler = Arc::clone(&handler);
+            std::thread::spawn(move || {
+                let _ = handle(stream, handler.as_ref());
+            });
+        }
+    });
+    url
+}
+
+fn handle(stream: TcpStream, handler: &Handler) -> std::io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    let mut parts = line.split_whitespace();
+    let method = parts.next().unwrap_or_default().to_string();
+    let target = parts.next().unwrap_or_default().to_string();
+
+    let mut headers = Vec::new();
+    loop {
+        line.clear();
+        reader.read_line(&mut line)?;
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((key, value)) = line.split_once(':') {
+            headers.push((key.trim().to_string(), value.trim().to_string()));
+        }
+    }
+    let mut request = Request {
+        method,
+        target,
+        headers,
+        body: Vec::new(),
+    };
+
+    if request
+        .header("Transfer-Encoding")
+        .is_some_and(|value| value.eq_ignore_ascii_case("chunked"))
+    {
+        loop {
+            line.clear();
+            reader.read_line(&mut line)?;
+            let size = usize::from_str_radix(line.trim(), 16).unwrap_or(0);
+            let mut chunk = vec![0; size + 2];
+            reader.read_exact(&mut chunk)?;
+            if size == 0 {
+                break;
+            }
+            request.body.extend_from_slice(&chunk[..size]);
+        }
+    } else if let Some(length) = request.header("Content-Length") {
+        let mut body = vec![0; length.parse().unwrap_or(0)];
+        reader.read_exact(&mut body)?;
+        request.body = body;
+    }
+
+    let response = handler(&request);
+    let mut stream = stream;
+    write!(stream, "HTTP/1.1 {} X\r\n", response.status)?;
+    for (key, value) in &response.headers {
+        write!(stream, "{}: {}\r\n", key, value)?;
+    }
+    write!(
+        stream,
+        "Content-Length: {}\r\nConnection: close\r\n\r\n",
+        response.body.len()
+    )?;
+    stream.write_all(&response.body)?;
+    stream.flush()
+}
+
+/// 用 `git http-backend`（CGI）处理请求，提供 root 下各仓库的智能 HTTP 协议访问
+pub(crate) fn git_http_backend(root: &Path, request: &Request) -> Response {
+    let (path, query) = request
+        .target
+        .split_once('?')
+        .unwrap_or((&request.target, ""));
+    let mut command = Command::new("git");
+    command
+        .arg("http-backend")
+        .env("GIT_PROJECT_ROOT", root)
+        .env("GIT_HTTP_EXPORT_ALL", "1")
+        .env("PATH_INFO", path)
+        .env("QUERY_STRING", query)
+        .env("REQUEST_METHOD", &request.method)
+        .env("REMOTE_ADDR", "127.0.0.1")
+        .env("CONTENT_LENGTH", request.body.len().to_string())
+        .env("CONTENT_TYPE", request.header("Content-Type").unwrap_or_default())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null());
+    if let Some(encoding) = request.header("Content-Encoding") {
+        command.env("HTTP_CONTENT_ENCODING", encoding);
+    }
+    if let Some(protocol) = request.header("Git-Protocol") {
+        command.env("HTTP_GIT_PROTOCOL", protocol);
+    }
+
+    let mut child = command.spawn().expect("无法执行 git http-backend");
+    child.stdin.take().unwrap().write_all(&request.body).unwrap();
+    let output = child.wait_with_output().unwrap();
+
+    // CGI 输出：响应头与响应体之间以空行分隔
+    let stdout = output.stdout;
+    let split = stdout
+        .windows(4)
+        .position(|w| w == b"\r\n\r\n")
+        .map(|i| (i, i + 4))
+        .or_else(|| stdout.windows(2).position(|w| w == b"\n\n").map(|i| (i, i + 2)))
+        .unwrap_or((stdout.len(), stdout.len()));
+    let mut response = Response::new(200, stdout[split.1..].to_vec());
+    for line in String::from_utf8_lossy(&stdout[..split.0]).lines() {
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        let value = value.trim();
+        if key.eq_ignore_ascii_case("Status") {
+            response.status = value
+                .split_whitespace()
+                .next()
+                .and_then(|code| code.parse().ok())
+                .unwrap_or(500);
+        } else {
+            response = response.header(key.trim(), value);
+        }
+    }
+    response
+}
+
+/// 目录内容的快照：按路径排序的 (路径字节, 类型与内容, 权限位)
+pub(crate) type Snapshot = Vec<(Vec<u8>, String, u32)>;
+
+/// 不跟随符号链接地记录目录内容，用于比较不同后端的输出是否逐字节一致
+#[cfg(unix)]
+pub(crate) fn snapshot(dir: &Path) -> Snapshot {
+    use std::os::unix::ffi::OsStrExt;
+    use std::os::unix::fs::PermissionsExt;
+
+    fn walk(dir: &Path, prefix: &[u8], entries: &mut Snapshot) {
+        for entry in std::fs::read_dir(dir).unwrap() {
+            let entry = entry.unwrap();
+            let mut path = prefix.to_vec();
+            path.extend_from_slice(entry.file_name().as_bytes());
+            let metadata = entry.path().symlink_metadata().unwrap();
+            if metadata.is_symlink() {
+                let target = std::fs::read_link(entry.path()).unwrap();
+                let content = format!("link:{}", target.display());
+                entries.push((path, content, 0));
+            } else if metadata.is_dir() {
+                entries.push((path.clone(), "dir".to_string(), 0));
+                path.push(b'/');
+                walk(&entry.path(), &path, entries);
+            } else {
+                let content = std::fs::read(entry.path()).unwrap();
+                let content = format!("file:{}", String::from_utf8_lossy(&content));
+                entries.push((path, content, metadata.permissions().mode() & 0o777));
+            }
+        }
+    }
+
+    let mut entries = Vec::new();
+    walk(dir, b"", &mut entries);
+    entries.sort();
+    entries
+}
+
+/// 把夹具仓库复制为 root 下的裸仓库 name，供 HTTP 服务使用
+pub(crate) fn bare_clone(repo: &FixtureRepo, root: &Path, name: &str) -> PathBuf {
+    let target = root.join(name);
+    git(
+        root,
+        &["clone", "--quiet", "--bare", &repo.path().to_string_lossy(), name],
+    );
+    git(&target, &["config", "uploadpack.allowFilter", "true"]);
+    git(&target, &["config", "uploadpack.allowAnySHA1InWant", "true"]);
+    target
+}
@@ -0,0 +1,552 @@
+//! `git-get update`：按来源记录原地刷新之前抓取的目录
+//!
+//! 新版本先抓取到临时目录，再与 `.git-get.json` 中记录的文件逐个比较：
+//! 上游新增或修改的文件写入目标目录，上游删除的文件从目标目录删除，
+//! 不在记录中的文件（用户自己添加的）保持不动。
+//!
+//! 受管理的文件在本地被修改过时，以记录中的提交为共同祖先做三方合并：
+//! 文本文件由 `git merge-file` 合并，改动重叠处写入冲突标记；二进制文件
+//! 无法合并，写入上游版本并把本地版本另存为 `<文件>.orig`（已存在时改用
+//! `<文件>.1.orig` 等，不覆盖已有文件）。
+
+use crate::copy::{copy_file, is_text, unused_suffix, with_suffix, Staging};
+use crate::fetch::{fetch, FetchRequest};
+use crate::git::{merge_file, MergedFile};
+use crate::provenance::{hash_file, Provenance, PROVENANCE_FILE};
+use anyhow::{bail, Context, Result};
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// 一次原地更新的结果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UpdateOutcome {
+    /// 实际使用的 Git 仓库 URL
+    pub repo_url: String,
+    /// 实际拉取的引用
+    pub reference: String,
+    /// 仓库内路径，None 表示整个仓库
+    pub path: Option<String>,
+    /// 更新前记录的提交 SHA
+    pub previous_commit: String,
+    /// 更新后的提交 SHA
+    pub commit: String,
+    /// 更新后路径对应的 tree 哈希
+    pub tree: String,
+    /// 更新的目标目录
+    pub dest: PathBuf,
+    /// 新增的文件（相对目标目录）
+    pub added: Vec<String>,
+    /// 内容变化的文件
+    pub changed: Vec<String>,
+    /// 上游已删除、因此从目标目录删除的文件
+    pub removed: Vec<String>,
+    /// 本地修改与上游修改已自动合并的文件
+    pub merged: Vec<String>,
+    /// 无法自动合并的文件
+    pub conflicts: Vec<Conflict>,
+}
+
+impl UpdateOutcome {
+    /// 是否没有任何文件变化
+    pub fn is_unchanged(&self) -> bool {
+        self.added.is_empty()
+            && self.changed.is_empty()
+            && self.removed.is_empty()
+            && self.merged.is_empty()
+            && self.conflicts.is_empty()
+    }
+}
+
+/// 一个无法自动合并的文件
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Conflict {
+    /// 相对目标目录的路径
+    pub path: String,
+    /// 冲突类型
+    pub kind: ConflictKind,
+    /// 本地版本另存的路径（相对目标目录），只有 [`ConflictKind::Binary`] 时有值
+    pub saved_as: Option<String>,
+}
+
+/// 冲突类型，以及 git-get 对该文件的处理方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConflictKind {
+    /// 本地与上游改动了同一处，文件中已写入冲突标记
+    Content,
+    /// 二进制文件两边都有改动：写入上游版本，本地版本另存为 `<文件>.orig`
+    Binary,
+    /// 本地修改过的文件在上游被删除：保留本地文件
+    DeletedUpstream,
+    /// 本地删除的文件在上游被修改：恢复为上游版本
+    DeletedLocally,
+}
+
+impl fmt::Display for ConflictKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ConflictKind::Content => "内容冲突，已写入冲突标记",
+            ConflictKind::Binary => "二进制文件冲突，已写入上游版本，本地版本另存为",
+            ConflictKind::DeletedUpstream => "上游已删除，保留了本地修改的版本",
+            ConflictKind::DeletedLocally => "本地已删除，上游有修改，已恢复为上游版本",
+        })
+    }
+}
+
+/// 受管理文件在本地的状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LocalState {
+    Unmodified,
+    Modified,
+    Deleted,
+}
+
+/// 一个文件的写入来源
+enum Content {
+    /// 临时目录中的上游版本
+    Upstream,
+    /// 合并得到的内容
+    Merged(Vec<u8>),
+}
+
+/// 按目标目录中的来源记录构造更新请求（仓库、引用、路径与过滤规则沿用记录中的值）
+pub fn update_request(dest: impl Into<PathBuf>) -> Result<FetchRequest> {
+    let dest = dest.into();
+    let provenance = load_provenance(&dest)?;
+    let mut request = FetchRequest::new(provenance.source).dest(dest);
+    if let Some(reference) = provenance.reference {
+        request = request.reference(reference);
+    }
+    if let Some(path) = provenance.path {
+        request = request.path(path);
+    }
+    for pattern in provenance.include {
+        request = request.include(pattern);
+    }
+    for pattern in provenance.exclude {
+        request = request.exclude(pattern);
+    }
+    Ok(request)
+}
+
+/// 原地更新请求的目标目录
+///
+/// 目标目录必须包含来源记录；请求中的仓库、引用与路径决定要更新到的版本。
+/// 本地修改过的文件与上游改动三方合并，无法自动合并的文件记录在
+/// [`UpdateOutcome::conflicts`] 中。
+pub fn update(request: &FetchRequest) -> Result<UpdateOutcome> {
+    let Some(dest) = request.destination() else {
+        bail!("原地更新需要指定目标目录");
+    };
+    let progress = &request.fetch_options().progress;
+    let previous = load_provenance(dest)?;
+    let local = local_states(dest, &previous)?;
+
+    // 新版本先抓取到与目标目录同目录的暂存区，全部修改完成后再整体替换
+    let staging = Staging::new(dest)?;
+    let staged_dir = staging.join("new");
+    let staged = request.clone().dest(&staged_dir).metadata(true);
+    let outcome = fetch(&staged)?;
+    let Some(current) = Provenance::load(&staged_dir)? else {
+        bail!("只能原地更新目录，{} 不是目录", outcome.path.as_deref().unwrap_or(""));
+    };
+
+    let old: HashMap<&str, &str> = previous
+        .files
+        .iter()
+        .map(|file| (file.path.as_str(), file.sha256.as_str()))
+        .collect();
+
+    // 两边都修改过的文件需要基础版本，只在需要时抓取
+    let needs_base = current.files.iter().any(|file| {
+        local.get(&file.path) == Some(&LocalState::Modified)
+            && old.get(file.path.as_str()) != Some(&file.sha256.as_str())
+    });
+    let base_dir = staging.join("base");
+    if needs_base {
+        progress.emit(format!(
+            "🧬 正在抓取基础版本 {} 用于合并本地修改...",
+            short(&previous.commit)
+        ));
+        let mut base = request
+            .clone()
+            .reference(&previous.commit)
+            .dest(&base_dir)
+            .metadata(false)
+            .expect_tree(&previous.tree);
+        if let Some(path) = &previous.path {
+            base = base.path(path);
+        }
+        fetch(&base).context("无法抓取记录中的基础版本")?;
+    }
+    let empty = staging.join("empty");
+    std::fs::write(&empty, b"").context("无法创建临时文件")?;
+    let base_label = format!("基础 {}", short(&previous.commit));
+    let upstream_label = format!("上游 {}", short(&outcome.commit));
+    let labels = ["本地", base_label.as_str(), upstream_label.as_str()];
+
+    let mut writes = Vec::new();
+    let mut backups = Vec::new();
+    let mut removals = Vec::new();
+    let mut added = Vec::new();
+    let mut changed = Vec::new();
+    let mut removed = Vec::new();
+    let mut merged = Vec::new();
+    let mut conflicts = Vec::new();
+
+    for file in &current.files {
+        let path = &file.path;
+        let target = dest.join(path);
+        let upstream_changed = match old.get(path.as_str()) {
+            Some(old_hash) => *old_hash != file.sha256,
+            None => true,
+        };
+        let state = match old.get(path.as_str()) {
+            Some(_) => local[path],
+            // 上游新增的文件与本地未受管理的同名文件，按空的共同祖先合并
+            None if is_present(&target) && hash_file(&target)? != file.sha256 => {
+                LocalState::Modified
+            }
+            None if is_present(&target) => LocalState::Unmodified,
+            None => {
+                added.push(path.clone());
+                writes.push((path.clone(), Content::Upstream));
+                continue;
+            }
+        };
+
+        match state {
+            LocalState::Unmodified if upstream_changed && old.contains_key(path.as_str()) => {
+                changed.push(path.clone());
+                writes.push((path.clone(), Content::Upstream));
+            }
+            // 本地已有与上游新增内容相同的文件，直接纳入管理
+            LocalState::Unmodified if !old.contains_key(path.as_str()) => added.push(path.clone()),
+            LocalState::Modified if upstream_changed => {
+                let base = if old.contains_key(path.as_str()) {
+                    base_dir.join(path)
+                } else {
+                    empty.clone()
+                };
+                match merge_text(&target, &base, &staged_dir.join(path), labels)? {
+                    Some(result) => {
+                        if result.conflicted {
+                            conflicts.push(conflict(path, ConflictKind::Content));
+                        } else {
+                            merged.push(path.clone());
+                        }
+                        writes.push((path.clone(), Content::Merged(result.content)));
+                    }
+                    None => {
+                        conflicts.push(conflict(path, ConflictKind::Binary));
+                        backups.push(path.clone());
+                    }
+                }
+            }
+            LocalState::Deleted if upstream_changed => {
+                conflicts.push(conflict(path, ConflictKind::DeletedLocally));
+                writes.push((path.clone(), Content::Upstream));
+            }
+            // 上游未变化：保留本地的状态（包括本地的修改与删除）
+            _ => {}
+        }
+    }
+    for file in &previous.files {
+        if current.files.iter().any(|new| new.path == file.path) {
+            continue;
+        }
+        match local[&file.path] {
+            LocalState::Unmodified => {
+                removed.push(file.path.clone());
+                removals.push(file.path.clone());
+            }
+            LocalState::Modified => {
+                conflicts.push(conflict(&file.path, ConflictKind::DeletedUpstream))
+            }
+            LocalState::Deleted => {}
+        }
+    }
+
+    // 在目标目录的副本上修改，中途失败时目标目录保持原样
+    progress.emit(format!("🔁 正在更新: {}", dest.display()));
+    staging.seed(dest)?;
+    let work = staging.content();
+    // 先删除再写入，上游把文件换成同名目录（或相反）时也能写入
+    for path in &removals {
+        let target = work.join(path);
+        if target.exists() || target.is_symlink() {
+            std::fs::remove_file(&target)
+                .with_context(|| format!("无法删除文件: {}", target.display()))?;
+        }
+        remove_empty_parents(&work, &target);
+    }
+    for (path, content) in writes {
+        let target = work.join(&path);
+        if let Some(parent) = target.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("无法创建目录: {}", parent.display()))?;
+        }
+        match content {
+            Content::Upstream => copy_file(&staged_dir.join(&path), &target).map(|_| ())?,
+            Content::Merged(bytes) => std::fs::write(&target, bytes)
+                .with_context(|| format!("无法写入文件: {}", target.display()))?,
+        }
+    }
+    // 其他文件写完后再选择 .orig 的名称，不会与上游的文件重名
+    for path in &backups {
+        let target = work.join(path);
+        let suffix = unused_suffix(&target, "orig");
+        let backup = with_suffix(&target, &suffix);
+        std::fs::rename(&target, &backup)
+            .with_context(|| format!("无法保存本地版本: {}", backup.display()))?;
+        copy_file(&staged_dir.join(path), &target)?;
+        if let Some(conflict) = conflicts.iter_mut().find(|conflict| conflict.path == *path) {
+            conflict.saved_as = Some(format!("{}{}", path, suffix));
+        }
+    }
+    // 来源记录始终描述上游版本，本地修改在下次更新时会再次参与合并
+    current.save(&work)?;
+    staging.commit(dest, None)?;
+
+    Ok(UpdateOutcome {
+        repo_url: outcome.repo_url,
+        reference: outcome.reference,
+        path: outcome.path,
+        previous_commit: previous.commit,
+        commit: outcome.commit,
+        tree: outcome.tree,
+        dest: dest.to_path_buf(),
+        added,
+        changed,
+        removed,
+        merged,
+        conflicts,
+    })
+}
+
+fn conflict(path: &str, kind: ConflictKind) -> Conflict {
+    Conflict {
+        path: path.to_string(),
+        kind,
+        saved_as: None,
+    }
+}
+
+/// 读取目标目录中的来源记录，缺失时报错
+fn load_provenance(dest: &Path) -> Result<Provenance> {
+    if !dest.is_dir() {
+        bail!("目标目录不存在: {}", dest.display());
+    }
+    Provenance::load(dest)?.with_context(|| {
+        format!(
+            "目标目录中没有来源记录 {}，无法确定其来源: {}\n提示: 删除该目录后使用 git-get 重新抓取",
+            PROVENANCE_FILE,
+            dest.display()
+        )
+    })
+}
+
+/// 路径上是否有文件或符号链接（不跟随链接）
+fn is_present(path: &Path) -> bool {
+    path.symlink_metadata().is_ok_and(|metadata| !metadata.is_dir())
+}
+
+/// 与来源记录相比，各受管理文件在本地的状态
+fn local_states(dest: &Path, provenance: &Provenance) -> Result<HashMap<String, LocalState>> {
+    let mut states = HashMap::new();
+    for file in &provenance.files {
+        let path = dest.join(&file.path);
+        let state = if !is_present(&path) {
+            LocalState::Deleted
+        } else if hash_file(&path)? != file.sha256 {
+            LocalState::Modified
+        } else {
+            LocalState::Unmodified
+        };
+        states.insert(file.path.clone(), state);
+    }
+    Ok(states)
+}
+
+/// 三方合并文本文件，任一版本是二进制文件时返回 None
+fn merge_text(
+    ours: &Path,
+    base: &Path,
+    theirs: &Path,
+    labels: [&str; 3],
+) -> Result<Option<MergedFile>> {
+    for path in [ours, base, theirs] {
+        if !is_text(path)? {
+            return Ok(None);
+        }
+    }
+    merge_file(ours, base, theirs, labels).map(Some)
+}
+
+/// 删除文件后，自下而上清理变空的父目录（不删除目标目录本身）
+fn remove_empty_parents(dest: &Path, file: &Path) {
+    let mut dir = file.parent();
+    while let Some(current) = dir.filter(|dir| *dir != dest && dir.starts_with(dest)) {
+        if std::fs::remove_dir(current).is_err() {
+            break;
+        }
+        dir = current.parent();
+    }
+}
+
+/// 提交 SHA 的缩写
+fn short(commit: &str) -> &str {
+    &commit[..commit.len().min(12)]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::FixtureRepo;
+    use tempfile::TempDir;
+
+    /// 抓取夹具仓库的 dir 目录到 dest，并写入来源记录
+    fn request(repo: &FixtureRepo, dest: &Path) -> FetchRequest {
+        FetchRequest::new(repo.url()).path("dir").dest(dest).metadata(true)
+    }
+
+    fn read(path: &Path) -> String {
+        std::fs::read_to_string(path).unwrap()
+    }
+
+    #[test]
+    fn unmodified_files_follow_upstream() {
+        let repo = FixtureRepo::new();
+        repo.write("dir/kept.txt", "kept\n")
+            .write("dir/changed.txt", "v1\n")
+            .write("dir/removed/old.txt", "old\n");
+        let first = repo.commit("init");
+        let out = TempDir::new().unwrap();
+        let dest = out.path().join("dir");
+        fetch(&request(&repo, &dest)).unwrap();
+        // 用户自己添加的文件不受影响
+        std::fs::write(dest.join("notes.txt"), "mine\n").unwrap();
+
+        repo.write("dir/changed.txt", "v2\n").write("dir/new/added.txt", "added\n");
+        std::fs::remove_dir_all(repo.path().join("dir/removed")).unwrap();
+        let second = repo.commit("upstream");
+
+        let outcome = update(&request(&repo, &dest)).unwrap();
+        assert_eq!(outcome.previous_commit, first);
+        assert_eq!(outcome.commit, second);
+        assert_eq!(outcome.added, ["new/added.txt"]);
+        assert_eq!(outcome.changed, ["changed.txt"]);
+        assert_eq!(outcome.removed, ["removed/old.txt"]);
+        assert!(outcome.conflicts.is_empty());
+        assert_eq!(read(&dest.join("changed.txt")), "v2\n");
+        assert_eq!(read(&dest.join("new/added.txt")), "added\n");
+        assert!(!dest.join("removed").exists());
+        assert_eq!(read(&dest.join("kept.txt")), "kept\n");
+        assert_eq!(read(&dest.join("notes.txt")), "mine\n");
+        assert_eq!(Provenance::load(&dest).unwrap().unwrap().commit, second);
+        // 暂存区已清理
+        assert_eq!(std::fs::read_dir(out.path()).unwrap().count(), 1);
+
+        // 再次更新时没有变化
+        assert!(update(&request(&repo, &dest)).unwrap().is_unchanged());
+    }
+
+    #[test]
+    fn directories_without_provenance_are_refused() {
+        let repo = FixtureRepo::new();
+        repo.write("dir/a.txt", "a\n");
+        repo.commit("init");
+        let out = TempDir::new().unwrap();
+        let dest = out.path().join("dir");
+        fetch(&request(&repo, &dest).metadata(false)).unwrap();
+        repo.write("dir/a.txt", "b\n");
+        repo.commit("upstream");
+
+        let err = update(&request(&repo, &dest)).unwrap_err();
+        assert!(format!("{:#}", err).contains(PROVENANCE_FILE), "{:#}", err);
+        assert!(update_request(&dest).is_err());
+        assert_eq!(read(&dest.join("a.txt")), "a\n");
+        assert!(update_request(out.path().join("missing")).is_err());
+    }
+
+    #[test]
+    fn local_edits_are_merged_with_upstream_changes() {
+        let repo = FixtureRepo::new();
+        repo.write("dir/clean.txt", "one\ntwo\nthree\nfour\nfive\n")
+            .write("dir/conflict.txt", "one\ntwo\nthree\n")
+            .write("dir/plain.txt", "plain\n")
+            .write("dir/gone-upstream.txt", "gone\n")
+            .write("dir/gone-locally.txt", "local\n");
+        repo.commit("init");
+        let out = TempDir::new().unwrap();
+        let dest = out.path().join("dir");
+        fetch(&request(&repo, &dest)).unwrap();
+
+        std::fs::write(dest.join("clean.txt"), "ONE\ntwo\nthree\nfour\nfive\n").unwrap();
+        std::fs::write(dest.join("conflict.txt"), "one\nlocal\nthree\n").unwrap();
+        std::fs::write(dest.join("gone-upstream.txt"), "kept\n").unwrap();
+        std::fs::remove_file(dest.join("gone-locally.txt")).unwrap();
+
+        repo.write("dir/clean.txt", "one\ntwo\nthree\nfour\nFIVE\n")
+            .write("dir/conflict.txt", "one\nupstream\nthree\n")
+            .write("dir/plain.txt", "plain v2\n")
+            .write("dir/gone-locally.txt", "local v2\n");
+        std::fs::remove_file(repo.path().join("dir/gone-upstream.txt")).unwrap();
+        repo.commit("upstream");
+
+        let outcome = update(&request(&repo, &dest)).unwrap();
+        assert_eq!(outcome.merged, ["clean.txt"]);
+        assert_eq!(outcome.changed, ["plain.txt"]);
+        let mut kinds: Vec<(&str, ConflictKind)> = outcome
+            .conflicts
+            .iter()
+            .map(|conflict| (conflict.path.as_str(), conflict.kind))
+            .collect();
+        kinds.sort_unstable_by_key(|(path, _)| *path);
+        assert_eq!(
+            kinds,
+            [
+                ("conflict.txt", ConflictKind::Content),
+                ("gone-locally.txt", ConflictKind::DeletedLocally),
+                ("gone-upstream.txt", ConflictKind::DeletedUpstream),
+            ]
+        );
+
+        assert_eq!(read(&dest.join("clean.txt")), "ONE\ntwo\nthree\nfour\nFIVE\n");
+        let conflicted = read(&dest.join("conflict.txt"));
+        assert!(conflicted.contains("<<<<<<< 本地\nlocal\n"), "{}", conflicted);
+        assert!(conflicted.contains("upstream\n>>>>>>> 上游"), "{}", conflicted);
+        assert_eq!(read(&dest.join("plain.txt")), "plain v2\n");
+        assert_eq!(read(&dest.join("gone-upstream.txt")), "kept\n");
+        assert_eq!(read(&dest.join("gone-locally.txt")), "local v2\n");
+    }
+
+    #[test]
+    fn binary_conflicts_keep_the_local_version_without_clobbering() {
+        let repo = FixtureRepo::new();
+        repo.write("dir/image.bin", b"base\0");
+        repo.commit("init");
+        let out = TempDir::new().unwrap();
+        let dest = out.path().join("dir");
+        fetch(&request(&repo, &dest)).unwrap();
+
+        std::fs::write(dest.join("image.bin"), b"local\0").unwrap();
+        // 用户自己的 .orig 文件不会被覆盖
+        std::fs::write(dest.join("image.bin.orig"), "mine\n").unwrap();
+        repo.write("dir/image.bin", b"upstream\0");
+        repo.commit("upstream");
+
+        let outcome = update(&request(&repo, &dest)).unwrap();
+        assert_eq!(
+            outcome.conflicts,
+            [Conflict {
+                path: "image.bin".to_string(),
+                kind: ConflictKind::Binary,
+                saved_as: Some("image.bin.1.orig".to_string()),
+            }]
+        );
+        assert_eq!(std::fs::read(dest.join("image.bin")).unwrap(), b"upstream\0");
+        assert_eq!(std::fs::read(dest.join("image.bin.1.orig")).unwrap(), b"local\0");
+        assert_eq!(read(&dest.join("image.bin.orig")), "mine\n");
+    }
+}